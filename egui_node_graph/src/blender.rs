@@ -0,0 +1,227 @@
+use std::collections::HashMap;
+
+use super::*;
+use egui::pos2;
+
+#[cfg(feature = "persistence")]
+use serde::{Deserialize, Serialize};
+
+/// A node tree exported from Blender, e.g. by a script that dumps
+/// `material.node_tree` to JSON. With the `persistence` feature, this can be
+/// deserialized from a dump like:
+///
+/// ```json
+/// {
+///   "nodes": [
+///     { "name": "Math", "bl_idname": "ShaderNodeMath", "location": [0, 0],
+///       "inputs": [{ "name": "Value", "identifier": "Value", "default_value": 0.5 }],
+///       "outputs": [{ "name": "Value" }] }
+///   ],
+///   "links": [
+///     { "from_node": "Math", "from_socket": "Value",
+///       "to_node": "Output", "to_socket": "Surface" }
+///   ]
+/// }
+/// ```
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "persistence", derive(Serialize, Deserialize))]
+pub struct BlenderNodeTree {
+    pub nodes: Vec<BlenderNode>,
+    #[cfg_attr(feature = "persistence", serde(default))]
+    pub links: Vec<BlenderLink>,
+}
+
+/// A node of a [`BlenderNodeTree`].
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "persistence", derive(Serialize, Deserialize))]
+pub struct BlenderNode {
+    /// The unique name of the node in its tree.
+    pub name: String,
+    /// The node type, e.g. `ShaderNodeMath`.
+    pub bl_idname: String,
+    /// The label set by the user, empty when not set.
+    #[cfg_attr(feature = "persistence", serde(default))]
+    pub label: String,
+    /// The location in the Blender node editor, where y points up.
+    #[cfg_attr(feature = "persistence", serde(default))]
+    pub location: [f32; 2],
+    #[cfg_attr(feature = "persistence", serde(default))]
+    pub inputs: Vec<BlenderSocket>,
+    #[cfg_attr(feature = "persistence", serde(default))]
+    pub outputs: Vec<BlenderSocket>,
+}
+
+/// A socket of a [`BlenderNode`].
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "persistence", derive(Serialize, Deserialize))]
+pub struct BlenderSocket {
+    pub name: String,
+    /// The unique id of the socket in its node. Names can repeat, e.g. the
+    /// two `Color` inputs of a mix node, so links refer to sockets by their
+    /// identifier when they have one.
+    #[cfg_attr(feature = "persistence", serde(default))]
+    pub identifier: Option<String>,
+    /// The value of an unconnected input.
+    #[cfg_attr(feature = "persistence", serde(default))]
+    pub default_value: Option<BlenderValue>,
+}
+
+impl BlenderSocket {
+    fn id(&self) -> &str {
+        self.identifier.as_deref().unwrap_or(&self.name)
+    }
+}
+
+/// The default value of a [`BlenderSocket`].
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "persistence", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "persistence", serde(untagged))]
+pub enum BlenderValue {
+    Bool(bool),
+    Number(f64),
+    /// Vectors and colors.
+    Array(Vec<f64>),
+    String(String),
+}
+
+/// A link of a [`BlenderNodeTree`], from an output socket to an input
+/// socket. Nodes are referred to by name, and sockets by identifier.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "persistence", derive(Serialize, Deserialize))]
+pub struct BlenderLink {
+    pub from_node: String,
+    pub from_socket: String,
+    pub to_node: String,
+    pub to_socket: String,
+}
+
+/// Maps the nodes of a [`BlenderNodeTree`] onto the templates of the
+/// application, see [`GraphEditorState::import_blender_tree`].
+pub trait BlenderMapping {
+    type NodeTemplate;
+    type ValueType;
+
+    /// Returns the template to create for a Blender node, usually chosen by
+    /// its `bl_idname`. Nodes without a template are skipped.
+    fn template(&self, node: &BlenderNode) -> Option<Self::NodeTemplate>;
+
+    /// Returns the name of the input parameter for a socket of `node`.
+    /// Defaults to the name of the socket.
+    fn input_name(&self, _node: &BlenderNode, socket: &BlenderSocket) -> String {
+        socket.name.clone()
+    }
+
+    /// Returns the name of the output parameter for a socket of `node`.
+    /// Defaults to the name of the socket.
+    fn output_name(&self, _node: &BlenderNode, socket: &BlenderSocket) -> String {
+        socket.name.clone()
+    }
+
+    /// Converts the default value of an input socket. When `None`, the input
+    /// keeps the value set by the template. Defaults to `None`.
+    fn input_value(
+        &self,
+        _node: &BlenderNode,
+        _socket: &BlenderSocket,
+        _value: &BlenderValue,
+    ) -> Option<Self::ValueType> {
+        None
+    }
+}
+
+/// The result of [`GraphEditorState::import_blender_tree`].
+#[derive(Clone, Debug, Default)]
+pub struct BlenderImport {
+    /// The created nodes, keyed by their Blender name.
+    pub nodes: HashMap<String, NodeId>,
+    /// The names of the nodes without a template.
+    pub skipped_nodes: Vec<String>,
+    /// The links between nodes or sockets that weren't imported, or between
+    /// parameters with different data types.
+    pub skipped_links: Vec<BlenderLink>,
+}
+
+impl<NodeData, DataType, ValueType, NodeTemplate, UserState, ConnectionData>
+    GraphEditorState<NodeData, DataType, ValueType, NodeTemplate, UserState, ConnectionData>
+where
+    NodeTemplate: NodeTemplateTrait<
+        NodeData = NodeData,
+        DataType = DataType,
+        ValueType = ValueType,
+        ConnectionData = ConnectionData,
+        UserState = UserState,
+    >,
+    DataType: PartialEq,
+    ConnectionData: Default,
+{
+    /// Adds the nodes and links of a Blender node tree to the graph, using
+    /// `mapping` to pick the template of each node and the parameter of each
+    /// socket. Locations are converted to graph coordinates by flipping the
+    /// y axis.
+    pub fn import_blender_tree(
+        &mut self,
+        tree: &BlenderNodeTree,
+        mapping: &impl BlenderMapping<NodeTemplate = NodeTemplate, ValueType = ValueType>,
+        user_state: &mut UserState,
+    ) -> BlenderImport {
+        let mut import = BlenderImport::default();
+        let mut blender_nodes = HashMap::new();
+
+        for node in &tree.nodes {
+            let template = match mapping.template(node) {
+                Some(template) => template,
+                None => {
+                    import.skipped_nodes.push(node.name.clone());
+                    continue;
+                }
+            };
+            let node_id = self.graph.add_node(
+                template.node_graph_label(user_state),
+                template.user_data(user_state),
+                |graph, node_id| {
+                    template.build_node(graph, user_state, node_id);
+                    graph[node_id].template_version = template.version(user_state);
+                },
+            );
+            if !node.label.is_empty() {
+                self.graph[node_id].label = node.label.clone();
+            }
+            for socket in &node.inputs {
+                let name = mapping.input_name(node, socket);
+                let value = socket
+                    .default_value
+                    .as_ref()
+                    .and_then(|value| mapping.input_value(node, socket, value));
+                if let (Ok(input), Some(value)) = (self.graph[node_id].get_input(&name), value) {
+                    self.graph[input].value = value;
+                }
+            }
+            let [x, y] = node.location;
+            self.node_positions.insert(node_id, pos2(x, -y));
+            self.node_order.push(node_id);
+            import.nodes.insert(node.name.clone(), node_id);
+            blender_nodes.insert(node.name.as_str(), node);
+        }
+
+        for link in &tree.links {
+            let input = blender_nodes.get(link.to_node.as_str()).and_then(|node| {
+                let socket = node.inputs.iter().find(|s| s.id() == link.to_socket)?;
+                let name = mapping.input_name(node, socket);
+                self.graph[import.nodes[&node.name]].get_input(&name).ok()
+            });
+            let output = blender_nodes.get(link.from_node.as_str()).and_then(|node| {
+                let socket = node.outputs.iter().find(|s| s.id() == link.from_socket)?;
+                let name = mapping.output_name(node, socket);
+                self.graph[import.nodes[&node.name]].get_output(&name).ok()
+            });
+            match (input, output) {
+                (Some(input), Some(output)) if self.graph[input].typ == self.graph[output].typ => {
+                    let _ = self.graph.add_connection(output, input);
+                }
+                _ => import.skipped_links.push(link.clone()),
+            }
+        }
+
+        import
+    }
+}
@@ -0,0 +1,379 @@
+use std::collections::HashMap;
+
+use super::*;
+use egui::{Event, Key, Modifiers, Pos2, Rect, Vec2};
+
+/// Nodes copied with [`GraphEditorState::copy_selection`], which
+/// [`GraphEditorState::paste`] adds back to the graph with new ids.
+#[derive(Clone, Debug)]
+pub struct NodeClipboard<NodeData, DataType, ValueType, ConnectionData = ()> {
+    /// The copied nodes, and the connections between them. Connections to
+    /// nodes that weren't copied are left out.
+    pub graph: Graph<NodeData, DataType, ValueType, ConnectionData>,
+    /// The copied nodes in drawing order, with their position relative to
+    /// the top left corner of the copied nodes.
+    pub nodes: Vec<(NodeId, Vec2)>,
+    pub node_widths: SecondaryMap<NodeId, f32>,
+}
+
+impl<NodeData, DataType, ValueType, ConnectionData>
+    Graph<NodeData, DataType, ValueType, ConnectionData>
+where
+    NodeData: Clone,
+    DataType: Clone,
+    ValueType: Clone,
+{
+    /// Adds a copy of the node `node_id` of `source` to this graph, and
+    /// returns its id. The new ids of its parameters are added to `inputs`
+    /// and `outputs`, keyed by the ids in `source`. Connections aren't
+    /// copied.
+    pub fn copy_node_from(
+        &mut self,
+        source: &Self,
+        node_id: NodeId,
+        inputs: &mut HashMap<InputId, InputId>,
+        outputs: &mut HashMap<OutputId, OutputId>,
+    ) -> NodeId {
+        let node = &source[node_id];
+        let new_id = self.add_node(
+            node.label.clone(),
+            node.user_data.clone(),
+            |graph, new_id| {
+                for (name, input) in &node.inputs {
+                    let param = &source[*input];
+                    let new_input = graph.add_input_param(
+                        new_id,
+                        name.clone(),
+                        param.typ.clone(),
+                        param.value.clone(),
+                        param.kind,
+                        param.shown_inline,
+                    );
+                    graph[new_input].side = param.side;
+                    inputs.insert(*input, new_input);
+                }
+                for (name, output) in &node.outputs {
+                    let param = &source[*output];
+                    let new_output =
+                        graph.add_output_param(new_id, name.clone(), param.typ.clone());
+                    graph[new_output].side = param.side;
+                    outputs.insert(*output, new_output);
+                }
+            },
+        );
+        for input in node.input_ids() {
+            self[inputs[&input]].paired_output =
+                source[input].paired_output.map(|output| outputs[&output]);
+        }
+        for output in node.output_ids() {
+            self[outputs[&output]].paired_input =
+                source[output].paired_input.map(|input| inputs[&input]);
+        }
+        self[new_id].template_version = node.template_version;
+        self[new_id].groups = node
+            .groups
+            .iter()
+            .map(|group| ParamGroup {
+                params: group
+                    .params
+                    .iter()
+                    .map(|param| match param {
+                        AnyParameterId::Input(input) => AnyParameterId::Input(inputs[input]),
+                        AnyParameterId::Output(output) => AnyParameterId::Output(outputs[output]),
+                    })
+                    .collect(),
+                ..group.clone()
+            })
+            .collect();
+        new_id
+    }
+}
+
+impl<NodeData, DataType, ValueType, ConnectionData>
+    Graph<NodeData, DataType, ValueType, ConnectionData>
+where
+    NodeData: Clone,
+    DataType: Clone,
+    ValueType: Clone,
+    ConnectionData: Clone,
+{
+    /// Copies `nodes` of `source`, and the connections between them, to this
+    /// graph. Returns the new id of each node, in the same order, and the
+    /// copied connections.
+    fn copy_nodes_from(
+        &mut self,
+        source: &Self,
+        nodes: &[NodeId],
+    ) -> (Vec<NodeId>, Vec<(OutputId, InputId)>) {
+        let mut inputs = HashMap::new();
+        let mut outputs = HashMap::new();
+        let new_ids = nodes
+            .iter()
+            .map(|&node_id| self.copy_node_from(source, node_id, &mut inputs, &mut outputs))
+            .collect();
+        let mut connections = Vec::new();
+        for (&old_input, &input) in &inputs {
+            for &id in source.connection_ids(old_input) {
+                let connection = &source[id];
+                if let Some(&output) = outputs.get(&connection.output) {
+                    self.insert_connection(output, input, connection.data.clone());
+                    connections.push((output, input));
+                }
+            }
+        }
+        (new_ids, connections)
+    }
+}
+
+impl<NodeData, DataType, ValueType, NodeTemplate, UserState, ConnectionData>
+    GraphEditorState<NodeData, DataType, ValueType, NodeTemplate, UserState, ConnectionData>
+where
+    NodeData: Clone,
+    DataType: Clone,
+    ValueType: Clone,
+    ConnectionData: Clone,
+{
+    /// Copies `nodes`, and the connections between them, e.g. to paste them
+    /// in another editor. Returns `None` when there is nothing to copy.
+    pub fn copy_nodes(
+        &self,
+        nodes: &[NodeId],
+    ) -> Option<NodeClipboard<NodeData, DataType, ValueType, ConnectionData>> {
+        // Keep the drawing order, so pasted nodes overlap the same way.
+        let nodes: Vec<NodeId> = self
+            .node_order
+            .iter()
+            .copied()
+            .filter(|node_id| {
+                nodes.contains(node_id)
+                    && self.graph.nodes.contains_key(*node_id)
+                    && self.node_positions.contains_key(*node_id)
+            })
+            .collect();
+        let origin = self.nodes_bounds(&nodes)?.min;
+
+        let mut graph = Graph::new();
+        let (new_ids, _) = graph.copy_nodes_from(&self.graph, &nodes);
+        let mut node_widths = SecondaryMap::new();
+        for (node_id, new_id) in nodes.iter().zip(&new_ids) {
+            if let Some(&width) = self.node_widths.get(*node_id) {
+                node_widths.insert(*new_id, width);
+            }
+        }
+        let nodes = nodes
+            .iter()
+            .zip(new_ids)
+            .map(|(node_id, new_id)| (new_id, self.node_positions[*node_id] - origin))
+            .collect();
+        Some(NodeClipboard {
+            graph,
+            nodes,
+            node_widths,
+        })
+    }
+
+    /// Copies the selected nodes to [`GraphEditorState::clipboard`]. Returns
+    /// false when no nodes are selected, leaving the clipboard untouched.
+    pub fn copy_selection(&mut self) -> bool {
+        match self.copy_nodes(&self.selected_nodes) {
+            Some(clipboard) => {
+                self.clipboard = Some(clipboard);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Adds the nodes of [`GraphEditorState::clipboard`] to the graph with
+    /// new ids, with their top left corner at `pos`, in graph coordinates.
+    /// The pasted nodes are selected.
+    ///
+    /// Returns a [`NodeResponse::CreatedNode`] for each node, a
+    /// [`NodeResponse::ConnectEventEnded`] for each connection between them,
+    /// and a [`NodeResponse::PastedNodes`] with all of them.
+    pub fn paste<UserResponse>(&mut self, pos: Pos2) -> Vec<NodeResponse<UserResponse, NodeData>>
+    where
+        UserResponse: UserResponseTrait,
+        NodeData: NodeDataTrait,
+    {
+        match self.clipboard.take() {
+            Some(clipboard) => {
+                let responses = self.paste_nodes(&clipboard, pos);
+                self.clipboard = Some(clipboard);
+                responses
+            }
+            None => Vec::new(),
+        }
+    }
+
+    /// Like [`GraphEditorState::paste`], with the nodes of `clipboard`.
+    pub fn paste_nodes<UserResponse>(
+        &mut self,
+        clipboard: &NodeClipboard<NodeData, DataType, ValueType, ConnectionData>,
+        pos: Pos2,
+    ) -> Vec<NodeResponse<UserResponse, NodeData>>
+    where
+        UserResponse: UserResponseTrait,
+        NodeData: NodeDataTrait,
+    {
+        if self.settings.lock_layout || clipboard.nodes.is_empty() {
+            return Vec::new();
+        }
+        let nodes: Vec<NodeId> = clipboard
+            .nodes
+            .iter()
+            .map(|(node_id, _)| *node_id)
+            .collect();
+        let (new_ids, connections) = self.graph.copy_nodes_from(&clipboard.graph, &nodes);
+
+        let mut responses = Vec::new();
+        for (&(node_id, offset), &new_id) in clipboard.nodes.iter().zip(&new_ids) {
+            self.node_positions.insert(new_id, pos + offset);
+            if let Some(&width) = clipboard.node_widths.get(node_id) {
+                self.node_widths.insert(new_id, width);
+            }
+            self.node_order.push(new_id);
+            responses.push(NodeResponse::CreatedNode(new_id));
+        }
+        for (output, input) in connections {
+            responses.push(NodeResponse::ConnectEventEnded { output, input });
+        }
+        self.selected_nodes = new_ids.clone();
+        responses.push(NodeResponse::PastedNodes(new_ids));
+        responses
+    }
+
+    /// Pastes a copy of the selected nodes next to them, without touching
+    /// [`GraphEditorState::clipboard`]. Returns the same responses as
+    /// [`GraphEditorState::paste`].
+    pub fn duplicate_selection<UserResponse>(&mut self) -> Vec<NodeResponse<UserResponse, NodeData>>
+    where
+        UserResponse: UserResponseTrait,
+        NodeData: NodeDataTrait,
+    {
+        let copy = self.copy_nodes(&self.selected_nodes);
+        match (copy, self.nodes_bounds(&self.selected_nodes)) {
+            (Some(copy), Some(bounds)) => {
+                let offset = self.settings.placement_offset.unwrap_or(Vec2::ZERO);
+                self.paste_nodes(&copy, bounds.min + offset)
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Handles the clipboard shortcuts: ctrl+C copies the selected nodes,
+    /// ctrl+V pastes them at the mouse cursor, and ctrl+D duplicates them.
+    /// On macOS, cmd is used instead of ctrl. Only does something when
+    /// [`EditorSettings::clipboard_shortcuts`] is set and no widget has
+    /// focus, e.g. a text field of a node.
+    ///
+    /// Call this after drawing the editor each frame, with its response.
+    /// Returns a [`NodeResponse::CopiedNodes`] when nodes were copied, and
+    /// the responses of [`GraphEditorState::paste`] when nodes were pasted.
+    pub fn update_clipboard<UserResponse>(
+        &mut self,
+        ctx: &egui::Context,
+        response: &GraphResponse<UserResponse, NodeData>,
+    ) -> Vec<NodeResponse<UserResponse, NodeData>>
+    where
+        UserResponse: UserResponseTrait,
+        NodeData: NodeDataTrait,
+    {
+        if !self.settings.clipboard_shortcuts || ctx.memory(|mem| mem.focus().is_some()) {
+            return Vec::new();
+        }
+        // The integration turns ctrl+C and ctrl+V into clipboard events, but
+        // only sends a paste event when the system clipboard holds text.
+        let (copy, paste, duplicate) = ctx.input(|i| {
+            let command = i.modifiers.matches(Modifiers::COMMAND);
+            (
+                i.events.contains(&Event::Copy) || (command && i.key_pressed(Key::C)),
+                i.events
+                    .iter()
+                    .any(|event| matches!(event, Event::Paste(_)))
+                    || (command && i.key_pressed(Key::V)),
+                command && i.key_pressed(Key::D),
+            )
+        });
+
+        if copy && self.copy_selection() {
+            vec![NodeResponse::CopiedNodes(self.selected_nodes.clone())]
+        } else if paste && response.cursor_in_editor {
+            let pos = match ctx.pointer_hover_pos() {
+                Some(pos) => {
+                    CanvasView::new(response.editor_rect, &self.pan_zoom).screen_to_graph(pos)
+                }
+                None => return Vec::new(),
+            };
+            self.paste(pos)
+        } else if duplicate {
+            self.duplicate_selection()
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// The bounding box of the top left corners of `nodes`.
+    fn nodes_bounds(&self, nodes: &[NodeId]) -> Option<Rect> {
+        nodes
+            .iter()
+            .filter_map(|node_id| self.node_positions.get(*node_id))
+            .fold(None, |bounds: Option<Rect>, &pos| match bounds {
+                Some(bounds) => Some(bounds.union(Rect::from_min_max(pos, pos))),
+                None => Some(Rect::from_min_max(pos, pos)),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use egui::pos2;
+
+    type TestState = GraphEditorState<TestNode, u32, u32, (), (), u32>;
+
+    #[test]
+    fn test_copy_paste() {
+        let mut state = TestState::default();
+        let outside = add_editor_node(&mut state, "outside", pos2(0.0, 0.0));
+        let a = add_editor_node(&mut state, "a", pos2(100.0, 50.0));
+        let b = add_editor_node(&mut state, "b", pos2(300.0, 80.0));
+        connect_with_data(&mut state.graph, outside, a, 1);
+        connect_with_data(&mut state.graph, a, b, 2);
+
+        state.selected_nodes = vec![b, a];
+        assert!(state.copy_selection());
+        let responses = state.paste::<TestResponse>(pos2(1000.0, 1000.0));
+
+        let new_ids = match responses.last() {
+            Some(NodeResponse::PastedNodes(new_ids)) => new_ids.clone(),
+            other => panic!("unexpected response {:?}", other),
+        };
+        assert_eq!(new_ids.len(), 2);
+        assert_eq!(state.selected_nodes, new_ids);
+        // The copies keep the drawing order, and their relative positions.
+        let (new_a, new_b) = (new_ids[0], new_ids[1]);
+        assert_eq!(state.graph[new_a].label, "a");
+        assert_eq!(state.graph[new_b].label, "b");
+        assert_eq!(state.node_positions[new_a], pos2(1000.0, 1000.0));
+        assert_eq!(state.node_positions[new_b], pos2(1200.0, 1030.0));
+
+        // The connection between the copied nodes points to the copies, and
+        // keeps its data. The connection from outside is left out.
+        let new_a_in = state.graph[new_a].get_input("in").unwrap();
+        let new_a_out = state.graph[new_a].get_output("out").unwrap();
+        let new_b_in = state.graph[new_b].get_input("in").unwrap();
+        assert_eq!(state.graph.connection_count(new_a_in), 0);
+        let ids = state.graph.connection_ids(new_b_in);
+        assert_eq!(ids.len(), 1);
+        assert_eq!(state.graph[ids[0]].output, new_a_out);
+        assert_eq!(state.graph[ids[0]].data, 2);
+        assert!(responses.iter().any(|response| matches!(
+            response,
+            NodeResponse::ConnectEventEnded { output, input }
+                if *output == new_a_out && *input == new_b_in
+        )));
+        assert_eq!(state.graph.connections.len(), 3);
+    }
+}
@@ -0,0 +1,151 @@
+use super::*;
+
+/// The differences between two versions of a [`Graph`], returned by
+/// [`Graph::diff`]. Nodes, parameters and connections are matched by id, so
+/// both versions should come from the same document, e.g. a saved copy and
+/// the one being edited.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GraphDiff {
+    /// Nodes that only exist in the new version.
+    pub added_nodes: Vec<NodeId>,
+    /// Nodes that only exist in the old version.
+    pub removed_nodes: Vec<NodeId>,
+    /// Nodes in both versions whose label or parameters changed. The values
+    /// of the inputs are reported separately, in `changed_values`.
+    pub changed_nodes: Vec<NodeId>,
+    /// Connections that only exist in the new version, by their id in it.
+    /// Each of the connections of an input is compared on its own, so
+    /// parallel connections and inputs with several connections are
+    /// reported one by one.
+    pub added_connections: Vec<ConnectionId>,
+    /// Connections that only exist in the old version, by their id in it.
+    pub removed_connections: Vec<ConnectionId>,
+    /// Inputs in both versions whose value changed.
+    pub changed_values: Vec<InputId>,
+}
+
+impl GraphDiff {
+    /// Returns true when both versions are the same.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl<NodeData, DataType, ValueType, ConnectionData>
+    Graph<NodeData, DataType, ValueType, ConnectionData>
+where
+    DataType: PartialEq,
+    ValueType: PartialEq,
+{
+    /// Compares this graph with a newer version of it, `other`. The user data
+    /// of the nodes is not compared.
+    pub fn diff(&self, other: &Self) -> GraphDiff {
+        let mut diff = GraphDiff::default();
+
+        for (node_id, node) in &self.nodes {
+            match other.nodes.get(node_id) {
+                None => diff.removed_nodes.push(node_id),
+                Some(other_node) => {
+                    if !self.same_node(node, other, other_node) {
+                        diff.changed_nodes.push(node_id);
+                    }
+                }
+            }
+        }
+        diff.added_nodes = other
+            .iter_nodes()
+            .filter(|node_id| !self.nodes.contains_key(*node_id))
+            .collect();
+
+        for (input_id, input) in &self.inputs {
+            if let Some(other_input) = other.inputs.get(input_id) {
+                if input.value != other_input.value {
+                    diff.changed_values.push(input_id);
+                }
+            }
+        }
+
+        // A connection id that was reused for other ports counts as removed
+        // and added.
+        let same_connection = |a: &Connection<ConnectionData>, b: &Connection<ConnectionData>| {
+            a.input == b.input && a.output == b.output
+        };
+        diff.removed_connections = self
+            .connections
+            .iter()
+            .filter(|(id, connection)| match other.connections.get(*id) {
+                Some(other_connection) => !same_connection(connection, other_connection),
+                None => true,
+            })
+            .map(|(id, _)| id)
+            .collect();
+        diff.added_connections = other
+            .connections
+            .iter()
+            .filter(|(id, connection)| match self.connections.get(*id) {
+                Some(old_connection) => !same_connection(old_connection, connection),
+                None => true,
+            })
+            .map(|(id, _)| id)
+            .collect();
+
+        diff
+    }
+
+    /// Returns whether `node` has the same label and parameters as
+    /// `other_node`, which is in the `other` graph.
+    fn same_node(&self, node: &Node<NodeData>, other: &Self, other_node: &Node<NodeData>) -> bool {
+        let same_inputs = node.inputs == other_node.inputs
+            && node.input_ids().all(|id| match other.inputs.get(id) {
+                Some(other_input) => self[id].typ == other_input.typ,
+                None => false,
+            });
+        let same_outputs = node.outputs == other_node.outputs
+            && node.output_ids().all(|id| match other.outputs.get(id) {
+                Some(other_output) => self[id].typ == other_output.typ,
+                None => false,
+            });
+        node.label == other_node.label && same_inputs && same_outputs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    type TestGraph = Graph<(), u32, u32>;
+
+    #[test]
+    fn test_diff() {
+        let mut old = TestGraph::new();
+        let a = add_node(&mut old, "a");
+        let b = add_node(&mut old, "b");
+        let c = add_node(&mut old, "c");
+        let a_out = old[a].get_output("out").unwrap();
+        let b_in = old[b].get_input("in").unwrap();
+        let c_in = old[c].get_input("in").unwrap();
+        let a_to_c = old.add_connection(a_out, c_in).unwrap();
+        assert!(old.diff(&old.clone()).is_empty());
+
+        let mut new = old.clone();
+        new[a].label = "renamed".into();
+        new[b_in].value = 1;
+        new.remove_node(c).unwrap();
+        let d = add_node(&mut new, "d");
+        let d_in = new[d].get_input("in").unwrap();
+        let a_to_d = new.append_connection(a_out, d_in).unwrap();
+
+        assert_eq!(
+            old.diff(&new),
+            GraphDiff {
+                added_nodes: vec![d],
+                removed_nodes: vec![c],
+                changed_nodes: vec![a],
+                added_connections: vec![a_to_d],
+                removed_connections: vec![a_to_c],
+                changed_values: vec![b_in],
+            }
+        );
+    }
+}
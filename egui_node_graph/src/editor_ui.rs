@@ -1,7 +1,6 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
-use crate::color_hex_utils::*;
-use crate::utils::ColorUtils;
+use crate::utils::{fade_visuals, ColorUtils};
 
 use super::*;
 use egui::epaint::{CubicBezierShape, RectShape};
@@ -9,8 +8,29 @@ use egui::*;
 
 pub type PortLocations = std::collections::HashMap<AnyParameterId, Pos2>;
 pub type NodeRects = std::collections::HashMap<NodeId, Rect>;
-
-const DISTANCE_TO_CONNECT: f32 = 10.0;
+/// The number of connections of each output, counted once per frame.
+pub type OutputConnectionCounts = std::collections::HashMap<OutputId, usize>;
+
+const DISTANCE_TO_HOVER_CONNECTION: f32 = 6.0;
+/// The radius of the button that removes the selected connection.
+const DELETE_CONNECTION_BUTTON_RADIUS: f32 = 7.0;
+const DISTANCE_TO_GRAB_REROUTE_POINT: f32 = 10.0;
+const DISTANCE_TO_GRAB_CONNECTION_END: f32 = 50.0;
+/// The horizontal space between a node created for a port and the node of
+/// that port.
+const PARTNER_GAP: f32 = 50.0;
+/// See [`GraphNodeWidget::MAX_NODE_SIZE`]. Kept outside of the widget, so the
+/// default of [`NodeDataTrait::max_size`] can use it without its bounds.
+pub(crate) const DEFAULT_MAX_NODE_SIZE: [f32; 2] = [200.0, 400.0];
+/// The distance between a node and the connections that loop around it, in
+/// graph coordinates.
+const SELF_LOOP_GAP: f32 = 20.0;
+/// The angle between parallel connections where they meet their ports, in
+/// radians.
+const PARALLEL_CONNECTION_ANGLE: f32 = 0.25;
+/// The space left around the nodes by the overview, the minimap and
+/// [`GraphEditorState::frame_nodes`], in graph coordinates.
+const OVERVIEW_MARGIN: f32 = 50.0;
 
 /// Nodes communicate certain events to the parent graph when drawn. There is
 /// one special `User` variant which can be used by users as the return value
@@ -37,6 +57,23 @@ pub enum NodeResponse<UserResponse: UserResponseTrait, NodeData: NodeDataTrait>
     DisconnectEvent {
         output: OutputId,
         input: InputId,
+        connection: ConnectionId,
+    },
+    /// Emitted when the data of a connection is edited in its popup, see
+    /// [`ConnectionDataTrait::data_widget`]. Carries the new data.
+    ConnectionDataChanged {
+        output: OutputId,
+        input: InputId,
+        connection: ConnectionId,
+        data: NodeData::ConnectionData,
+    },
+    /// Emitted when a node is replaced with one from a different template,
+    /// see [`GraphEditorState::replace_node`]. The node keeps its id, and the
+    /// previous label, parameters and user data are passed along with the
+    /// event.
+    ReplacedNode {
+        node_id: NodeId,
+        old_node: Node<NodeData>,
     },
     /// Emitted when a node is interacted with, and should be raised
     RaiseNode(NodeId),
@@ -44,9 +81,68 @@ pub enum NodeResponse<UserResponse: UserResponseTrait, NodeData: NodeDataTrait>
         node: NodeId,
         drag_delta: Vec2,
     },
+    /// Emitted when the label of a node is edited in place, by double clicking
+    /// its title. The new label is already set when this is returned.
+    RenameNode {
+        node: NodeId,
+        old_label: String,
+        new_label: String,
+    },
+    /// Emitted when a node is resized with its resize handle, see
+    /// [`EditorSettings::resizable_nodes`].
+    ResizeNode {
+        node: NodeId,
+        width: f32,
+    },
+    /// Emitted when the heading of a [`ParamGroup`] is clicked. `group` is
+    /// the index in [`Node::groups`].
+    CollapseParamGroup {
+        node: NodeId,
+        group: usize,
+        collapsed: bool,
+    },
+    /// Emitted at the end of a frame where the selected nodes changed, by
+    /// clicking or box selecting nodes, or clicking the background. Holds
+    /// the new [`GraphEditorState::selected_nodes`].
+    SelectionChanged(Vec<NodeId>),
+    /// Emitted by [`GraphEditorState::update_clipboard`] when the selected
+    /// nodes are copied.
+    CopiedNodes(Vec<NodeId>),
+    /// Emitted by [`GraphEditorState::paste`] with the pasted nodes, after
+    /// the responses for each of the created nodes and connections.
+    PastedNodes(Vec<NodeId>),
+    /// Emitted before drawing when the editor state has problems, only when
+    /// [`EditorSettings::check_consistency`] is enabled. They can be fixed
+    /// with [`GraphEditorState::repair`].
+    InconsistentState(ValidationReport),
     User(UserResponse),
 }
 
+/// Describes how the parameters of a node map to the ones of the node that
+/// replaces it, see [`NodeTemplateTrait::conversion_from`]. Parameters are
+/// referred to by name. The ones that are not mapped here are matched by name
+/// and data type.
+#[derive(Clone, Debug)]
+pub struct NodeConversion<ValueType> {
+    /// The new input that takes the value and connection of each old input.
+    pub inputs: HashMap<String, String>,
+    /// The new output that takes the connections of each old output.
+    pub outputs: HashMap<String, String>,
+    /// Values for the new inputs, replacing the ones carried over from the
+    /// old inputs.
+    pub values: HashMap<String, ValueType>,
+}
+
+impl<ValueType> Default for NodeConversion<ValueType> {
+    fn default() -> Self {
+        Self {
+            inputs: HashMap::new(),
+            outputs: HashMap::new(),
+            values: HashMap::new(),
+        }
+    }
+}
+
 /// The return value of [`draw_graph_editor`]. This value can be used to make
 /// user code react to specific events that happened when drawing the graph.
 #[derive(Clone, Debug)]
@@ -60,6 +156,12 @@ pub struct GraphResponse<UserResponse: UserResponseTrait, NodeData: NodeDataTrai
     pub cursor_in_editor: bool,
     /// Is the mouse currently hovering the node finder?
     pub cursor_in_finder: bool,
+    /// The response of the canvas behind the nodes. Hosts can use it to add
+    /// their own context menus, tooltips or drop targets to the empty canvas.
+    /// Note that secondary clicks on the canvas also open the node finder.
+    pub background: Option<Response>,
+    /// The rect of the whole editor, in screen coordinates.
+    pub editor_rect: Rect,
 }
 impl<UserResponse: UserResponseTrait, NodeData: NodeDataTrait> Default
     for GraphResponse<UserResponse, NodeData>
@@ -69,42 +171,208 @@ impl<UserResponse: UserResponseTrait, NodeData: NodeDataTrait> Default
             node_responses: Default::default(),
             cursor_in_editor: false,
             cursor_in_finder: false,
+            background: None,
+            editor_rect: Rect::NOTHING,
+        }
+    }
+}
+/// Describes the part of the graph visible in the editor, passed to
+/// [`NodeDataTrait::canvas_background`].
+#[derive(Clone, Copy, Debug)]
+pub struct CanvasView {
+    /// The visible part of the graph, in graph coordinates.
+    pub visible_rect: Rect,
+    /// The rect of the editor, in screen coordinates.
+    pub editor_rect: Rect,
+}
+
+impl CanvasView {
+    pub fn new(editor_rect: Rect, pan_zoom: &PanZoom) -> Self {
+        Self {
+            visible_rect: Rect::from_min_size(
+                (-pan_zoom.pan / pan_zoom.zoom).to_pos2(),
+                editor_rect.size() / pan_zoom.zoom,
+            ),
+            editor_rect,
         }
     }
+
+    /// Converts a position in graph coordinates to screen coordinates.
+    pub fn graph_to_screen(&self, pos: Pos2) -> Pos2 {
+        let scale = self.editor_rect.width() / self.visible_rect.width();
+        self.editor_rect.min + (pos - self.visible_rect.min) * scale
+    }
+
+    /// Converts a position in screen coordinates to graph coordinates.
+    pub fn screen_to_graph(&self, pos: Pos2) -> Pos2 {
+        let scale = self.visible_rect.width() / self.editor_rect.width();
+        self.visible_rect.min + (pos - self.editor_rect.min) * scale
+    }
+
+    /// Converts a rect in graph coordinates to screen coordinates.
+    pub fn graph_to_screen_rect(&self, rect: Rect) -> Rect {
+        Rect::from_min_max(
+            self.graph_to_screen(rect.min),
+            self.graph_to_screen(rect.max),
+        )
+    }
+
+    /// Converts a rect in screen coordinates to graph coordinates.
+    pub fn screen_to_graph_rect(&self, rect: Rect) -> Rect {
+        Rect::from_min_max(
+            self.screen_to_graph(rect.min),
+            self.screen_to_graph(rect.max),
+        )
+    }
+}
+
+/// Describes a connection to be drawn by [`DataTypeTrait::draw_connection`].
+#[derive(Clone, Copy, Debug)]
+pub struct ConnectionDrawInfo {
+    /// The connected ports. This is `None` for the connection the user is
+    /// currently dragging.
+    pub connection: Option<(OutputId, InputId)>,
+    /// The position of the output end. Connections with reroute points are
+    /// drawn one segment at a time, so this can also be a reroute point.
+    pub src_pos: Pos2,
+    /// The unit direction in which the connection leaves `src_pos`, away from
+    /// the [`PortSide`] of the output port.
+    pub src_dir: Vec2,
+    /// The position of the input end, or of a reroute point.
+    pub dst_pos: Pos2,
+    /// The unit direction in which the connection arrives at `dst_pos`,
+    /// towards the [`PortSide`] of the input port.
+    pub dst_dir: Vec2,
+    /// Whether the mouse is hovering the connection.
+    pub hovered: bool,
+    /// The opacity of the connection, which is reduced when it's attached to
+    /// a disabled node.
+    pub opacity: f32,
+    /// The stroke width, from [`ConnectionStyle::width`] or
+    /// [`ConnectionStyle::hover_width`], already scaled by the zoom.
+    pub width: f32,
+    /// The width of the soft edge around the stroke, from
+    /// [`ConnectionStyle::feathering`] and already scaled by the zoom.
+    pub feathering: f32,
+    /// The [`ConnectionStyle::curvature`] of the bezier curve.
+    pub curvature: f32,
+    /// The [`ConnectionStyle::color`], which overrides the color of the data
+    /// type when set.
+    pub color: Option<Color32>,
+    /// The zoom level of the editor. Sizes like the stroke width should be
+    /// multiplied by it.
+    pub zoom: f32,
+}
+
+/// Describes a port to be drawn by [`DataTypeTrait::draw_port`].
+#[derive(Clone, Copy, Debug)]
+pub struct PortDrawInfo {
+    /// The parameter this port belongs to.
+    pub param_id: AnyParameterId,
+    /// The center of the port.
+    pub center: Pos2,
+    /// Whether the mouse is close enough to the port to interact with it.
+    pub hovered: bool,
+    /// Whether the port has at least one connection.
+    pub connected: bool,
+    /// Whether the port is an end of the connection under the mouse.
+    pub highlighted: bool,
+    /// Whether the port is bidirectional, see
+    /// [`Graph::add_bidirectional_param`]. These are drawn with the id of
+    /// their output.
+    pub bidirectional: bool,
+    /// The opacity of the port, which is reduced when its node is disabled.
+    pub opacity: f32,
+    /// The radius of the port, from [`EditorStyle::port_radius`] and already
+    /// scaled by the zoom.
+    pub radius: f32,
+    /// The zoom level of the editor. Other sizes should be multiplied by it.
+    pub zoom: f32,
 }
-pub struct GraphNodeWidget<'a, NodeData, DataType, ValueType> {
+
+pub struct GraphNodeWidget<'a, NodeData, DataType, ValueType, ConnectionData = ()> {
     pub position: &'a mut Pos2,
-    pub graph: &'a mut Graph<NodeData, DataType, ValueType>,
+    /// The width chosen by the user. When `None`, the node fits its contents.
+    pub width: Option<f32>,
+    /// Whether to show the resize handle.
+    pub resizable: bool,
+    /// Whether the node is pinned in place, see
+    /// [`GraphEditorState::set_pinned`].
+    pub pinned: bool,
+    /// Whether the node can only have its values edited, see
+    /// [`EditorSettings::lock_layout`].
+    pub locked: bool,
+    pub graph: &'a mut Graph<NodeData, DataType, ValueType, ConnectionData>,
     pub port_locations: &'a mut PortLocations,
     pub node_rects: &'a mut NodeRects,
+    /// The tessellated chrome of the nodes, see
+    /// [`GraphEditorState::node_shapes`].
+    pub node_shapes: &'a mut SecondaryMap<NodeId, CachedNodeShapes>,
+    /// The number of connections of each output of the graph.
+    pub output_connections: &'a OutputConnectionCounts,
     pub node_id: NodeId,
     pub ongoing_drag: Option<(NodeId, AnyParameterId)>,
     pub selected: bool,
+    /// Whether this node is the primary selection, see [`SelectionStyle`].
+    pub primary_selected: bool,
+    /// Whether the node is drawn faded, see [`NodeDataTrait::is_disabled`].
+    pub disabled: bool,
+    /// The direction of the contents and ports of the node, see
+    /// [`EditorSettings::layout_direction`].
+    pub layout_direction: LayoutDirection,
+    /// The strength of the outline of a newly created node, fading from 1.0
+    /// to 0.0. See [`EditorStyle::creation_highlight_duration`].
+    pub creation_highlight: f32,
+    /// The connection under the mouse. Its ports, and optionally this node,
+    /// are highlighted when it's attached to this node.
+    pub hovered_connection: Option<(OutputId, InputId)>,
+    pub style: &'a EditorStyle,
+    pub strings: &'a EditorStrings,
+    /// Offset from the scaled graph coordinates to screen coordinates.
     pub pan: egui::Vec2,
+    pub zoom: f32,
 }
 
-impl<NodeData, DataType, ValueType, NodeTemplate, UserResponse, UserState, CategoryType>
-    GraphEditorState<NodeData, DataType, ValueType, NodeTemplate, UserState>
+impl<
+        NodeData,
+        DataType,
+        ValueType,
+        NodeTemplate,
+        UserResponse,
+        UserState,
+        CategoryType,
+        ConnectionData,
+    > GraphEditorState<NodeData, DataType, ValueType, NodeTemplate, UserState, ConnectionData>
 where
     NodeData: NodeDataTrait<
         Response = UserResponse,
         UserState = UserState,
         DataType = DataType,
         ValueType = ValueType,
+        ConnectionData = ConnectionData,
     >,
     UserResponse: UserResponseTrait,
-    ValueType:
-        WidgetValueTrait<Response = UserResponse, UserState = UserState, NodeData = NodeData>,
+    ValueType: WidgetValueTrait<Response = UserResponse, UserState = UserState, NodeData = NodeData>
+        + Clone,
     NodeTemplate: NodeTemplateTrait<
         NodeData = NodeData,
         DataType = DataType,
         ValueType = ValueType,
+        ConnectionData = ConnectionData,
         UserState = UserState,
         CategoryType = CategoryType,
     >,
     DataType: DataTypeTrait<UserState>,
     CategoryType: CategoryTrait,
+    ConnectionData: ConnectionDataTrait<UserState> + Clone,
 {
+    /// Draws the graph editor and handles its interactions. The `user_state`
+    /// is passed mutably to every hook of the traits, so widgets can update
+    /// the application state directly, e.g. when a button is clicked.
+    ///
+    /// The editor fills the available space of `ui`, e.g. a `CentralPanel`.
+    /// To draw it in a given part of a window or a split layout, see
+    /// [`GraphEditorState::draw_graph_editor_inside`].
     #[must_use]
     pub fn draw_graph_editor(
         &mut self,
@@ -112,6 +380,47 @@ where
         all_kinds: impl NodeTemplateIter<Item = NodeTemplate>,
         user_state: &mut UserState,
         prepend_responses: Vec<NodeResponse<UserResponse, NodeData>>,
+    ) -> GraphResponse<UserResponse, NodeData> {
+        self.draw_graph_editor_with_widget(
+            ui,
+            all_kinds,
+            user_state,
+            prepend_responses,
+            &mut DefaultNodeWidget,
+        )
+    }
+
+    /// Like [`GraphEditorState::draw_graph_editor`], but draws the editor in
+    /// `rect` of `ui`, in screen coordinates, e.g. one side of a split
+    /// layout. Nodes and connections are clipped to `rect`, and the mouse is
+    /// only handled within it. The rect is allocated in `ui`.
+    #[must_use]
+    pub fn draw_graph_editor_inside(
+        &mut self,
+        ui: &mut Ui,
+        rect: Rect,
+        all_kinds: impl NodeTemplateIter<Item = NodeTemplate>,
+        user_state: &mut UserState,
+        prepend_responses: Vec<NodeResponse<UserResponse, NodeData>>,
+    ) -> GraphResponse<UserResponse, NodeData> {
+        let mut editor_ui = ui.child_ui_with_id_source(rect, *ui.layout(), "graph_editor");
+        editor_ui.set_clip_rect(rect.intersect(ui.clip_rect()));
+        let response =
+            self.draw_graph_editor(&mut editor_ui, all_kinds, user_state, prepend_responses);
+        ui.allocate_rect(rect, Sense::hover());
+        response
+    }
+
+    /// Like [`GraphEditorState::draw_graph_editor`], but draws the nodes with
+    /// a custom [`NodeWidget`].
+    #[must_use]
+    pub fn draw_graph_editor_with_widget(
+        &mut self,
+        ui: &mut Ui,
+        all_kinds: impl NodeTemplateIter<Item = NodeTemplate>,
+        user_state: &mut UserState,
+        prepend_responses: Vec<NodeResponse<UserResponse, NodeData>>,
+        node_widget: &mut impl NodeWidget<NodeData>,
     ) -> GraphResponse<UserResponse, NodeData> {
         // This causes the graph editor to use as much free space as it can.
         // (so for windows it will use up to the resizeably set limit
@@ -133,6 +442,9 @@ where
         // executed at the end of this function.
         let mut delayed_responses: Vec<NodeResponse<UserResponse, NodeData>> = prepend_responses;
 
+        // Used to report changes to the selection
+        let previous_selection = self.selected_nodes.clone();
+
         // Used to detect when the background was clicked
         let mut click_on_background = false;
 
@@ -140,12 +452,22 @@ where
         let mut drag_started_on_background = false;
         let mut drag_released_on_background = false;
 
-        debug_assert_eq!(
-            self.node_order.iter().copied().collect::<HashSet<_>>(),
-            self.graph.iter_nodes().collect::<HashSet<_>>(),
-            "The node_order field of the GraphEditorself was left in an \
-        inconsistent self. It has either more or less values than the graph."
-        );
+        if self.settings.check_consistency {
+            let report = self.validate();
+            if !report.is_valid() {
+                delayed_responses.push(NodeResponse::InconsistentState(report));
+            }
+        }
+
+        // The minimap takes the clicks and drags over it, so it's allocated
+        // before the background.
+        let minimap = self.settings.show_minimap.then(|| {
+            ui.interact(
+                self.style.minimap.rect(editor_rect),
+                ui.id().with("minimap"),
+                Sense::click_and_drag(),
+            )
+        });
 
         // Allocate rect before the nodes, otherwise this will block the interaction
         // with the nodes.
@@ -158,50 +480,291 @@ where
             drag_released_on_background = true;
         }
 
+        // Forget the reroute points of removed connections
+        let graph = &self.graph;
+        self.reroute_points
+            .retain(|input, _| graph.connection(input).is_some());
+        self.node_layouts
+            .retain(|node_id, _| graph.nodes.contains_key(node_id));
+        self.node_shapes
+            .retain(|node_id, _| graph.nodes.contains_key(node_id));
+
+        // States saved before zoom was implemented have a zero zoom.
+        if self.pan_zoom.zoom <= 0.0 || self.pan_zoom.zoom.is_nan() {
+            self.pan_zoom.zoom = 1.0;
+        }
+
+        // Zoom around the cursor
+        if cursor_in_editor {
+            let zoom_delta = ui.input(|i| i.zoom_delta());
+            if zoom_delta != 1.0 {
+                self.viewport_animation = None;
+                self.pan_zoom.zoom_around(
+                    zoom_delta,
+                    cursor_pos - editor_rect.min,
+                    self.settings.min_zoom,
+                    self.settings.max_zoom,
+                );
+            }
+        }
+
+        if let Some(bounds) = self.pending_frame.take() {
+            let bounds = bounds.expand(OVERVIEW_MARGIN);
+            let zoom = (editor_rect.size() / bounds.size())
+                .min_elem()
+                .clamp(self.settings.min_zoom, self.settings.max_zoom);
+            self.animate_viewport_to(bounds.center(), zoom);
+        }
+
+        if let Some(animation) = &mut self.viewport_animation {
+            let half_size = editor_rect.size() / 2.0;
+            let now = ui.input(|i| i.time);
+            let (start_time, start_center, start_zoom) = *animation.start.get_or_insert((
+                now,
+                ((half_size - self.pan_zoom.pan) / self.pan_zoom.zoom).to_pos2(),
+                self.pan_zoom.zoom,
+            ));
+            let duration = self.settings.viewport_animation_duration as f64;
+            let t = if duration > 0.0 {
+                ((now - start_time) / duration).clamp(0.0, 1.0) as f32
+            } else {
+                1.0
+            };
+            // Ease in and out
+            let t = t * t * (3.0 - 2.0 * t);
+            let center = start_center + (animation.target_center - start_center) * t;
+            self.pan_zoom.zoom = emath::lerp(start_zoom..=animation.target_zoom, t);
+            self.pan_zoom.pan = half_size - center.to_vec2() * self.pan_zoom.zoom;
+            if t < 1.0 {
+                ui.ctx().request_repaint();
+            } else {
+                self.viewport_animation = None;
+            }
+        }
+
+        let canvas_view = CanvasView::new(editor_rect, &self.pan_zoom);
+        NodeData::canvas_background(ui.painter(), &canvas_view, user_state);
+
+        if let Some(bounds) = self.settings.canvas_bounds {
+            ui.painter().rect_stroke(
+                canvas_view.graph_to_screen_rect(bounds),
+                0.0,
+                ui.visuals().widgets.noninteractive.bg_stroke,
+            );
+        }
+
         /* Draw nodes */
+
+        // The contents of the nodes are drawn with a style scaled by the zoom
+        let unzoomed_style = ui.style().clone();
+        ui.set_style(unzoomed_style.scaled(self.pan_zoom.zoom));
+        let mut disabled_nodes = HashSet::new();
+        let now = ui.input(|i| i.time);
+        let mut output_connections = OutputConnectionCounts::new();
+        for (_, output) in self.graph.iter_connections() {
+            *output_connections.entry(output).or_default() += 1;
+        }
         for node_id in self.node_order.iter().copied() {
+            // Skip the nodes the application removed without updating the
+            // editor state, see `GraphEditorState::repair`.
+            if !self.graph.nodes.contains_key(node_id) {
+                continue;
+            }
+            let creation_highlight = match self.created_nodes.get_mut(node_id) {
+                Some(start) => {
+                    let start = *start.get_or_insert(now);
+                    let t = (now - start) as f32 / self.style.creation_highlight_duration;
+                    1.0 - t.clamp(0.0, 1.0)
+                }
+                None => 0.0,
+            };
+            if creation_highlight > 0.0 {
+                ui.ctx().request_repaint();
+            } else {
+                self.created_nodes.remove(node_id);
+            }
+            let disabled =
+                self.graph[node_id]
+                    .user_data
+                    .is_disabled(node_id, &self.graph, user_state);
+            if disabled {
+                disabled_nodes.insert(node_id);
+            }
+            // Nodes outside the editor keep the layout they were last drawn
+            // with. Selected and highlighted nodes are always drawn.
+            let cached_layout = self
+                .node_layouts
+                .get(node_id)
+                .zip(self.node_positions.get(node_id))
+                .filter(|_| {
+                    self.settings.cull_offscreen_nodes
+                        && creation_highlight <= 0.0
+                        && !self.selected_nodes.contains(&node_id)
+                });
+            if let Some((layout, pos)) = cached_layout {
+                let rect = canvas_view.graph_to_screen_rect(layout.rect.translate(pos.to_vec2()));
+                if !editor_rect.intersects(rect) {
+                    node_rects.insert(node_id, rect);
+                    port_locations.extend(layout.ports.iter().map(|(port, offset)| {
+                        (*port, canvas_view.graph_to_screen(*pos + *offset))
+                    }));
+                    continue;
+                }
+            }
+            let hovered_connection = self
+                .hovered_connection
+                .and_then(|id| self.graph.connections.get(id))
+                .map(|connection| (connection.output, connection.input));
+            let position = match self.node_positions.entry(node_id) {
+                Some(entry) => entry.or_insert(Pos2::ZERO),
+                None => continue,
+            };
             let responses = GraphNodeWidget {
-                position: self.node_positions.get_mut(node_id).unwrap(),
+                position,
+                width: self.node_widths.get(node_id).copied(),
+                resizable: self.settings.resizable_nodes && !self.settings.lock_layout,
+                pinned: self.pinned_nodes.contains(&node_id),
+                locked: self.settings.lock_layout,
                 graph: &mut self.graph,
                 port_locations: &mut port_locations,
                 node_rects: &mut node_rects,
+                node_shapes: &mut self.node_shapes,
+                output_connections: &output_connections,
                 node_id,
                 ongoing_drag: self.connection_in_progress,
-                selected: self
-                    .selected_nodes
-                    .iter()
-                    .any(|selected| *selected == node_id),
+                selected: self.selected_nodes.contains(&node_id),
+                primary_selected: self.selected_nodes.first() == Some(&node_id),
+                disabled,
+                creation_highlight,
+                layout_direction: self.settings.layout_direction,
+                hovered_connection,
+                style: &self.style,
+                strings: &self.settings.strings,
                 pan: self.pan_zoom.pan + editor_rect.min.to_vec2(),
+                zoom: self.pan_zoom.zoom,
+            }
+            .show_with_widget(ui, user_state, node_widget);
+
+            if let (Some(rect), Some(pos)) =
+                (node_rects.get(&node_id), self.node_positions.get(node_id))
+            {
+                let node = &self.graph[node_id];
+                let ports = node
+                    .input_ids()
+                    .map(AnyParameterId::Input)
+                    .chain(node.output_ids().map(AnyParameterId::Output))
+                    .filter_map(|port| {
+                        let location = port_locations.get(&port)?;
+                        Some((port, canvas_view.screen_to_graph(*location) - *pos))
+                    })
+                    .collect();
+                let layout = CachedNodeLayout {
+                    rect: canvas_view
+                        .screen_to_graph_rect(*rect)
+                        .translate(-pos.to_vec2()),
+                    ports,
+                };
+                self.node_layouts.insert(node_id, layout);
             }
-            .show(ui, user_state);
 
             // Actions executed later
             delayed_responses.extend(responses);
         }
+        ui.set_style(unzoomed_style);
+
+        if let Some((node_id, port, partner)) = self.pending_alignment.take() {
+            self.align_to_partner(node_id, port, partner, &port_locations, &node_rects);
+        }
+
+        // The topmost node under the cursor
+        self.hovered_node = self
+            .node_order
+            .iter()
+            .rev()
+            .find(|node_id| matches!(node_rects.get(node_id), Some(rect) if rect.contains(cursor_pos)))
+            .copied()
+            .filter(|_| cursor_in_editor);
+
+        /* Draw the radial menu, if open */
+        let mut radial_menu_released = false;
+        let radial_menu_gesture = self
+            .settings
+            .radial_menu_gesture
+            .filter(|_| !self.settings.lock_layout);
+        match radial_menu_gesture {
+            Some(RadialMenuGesture::HoldKey(key)) => {
+                if self.radial_menu.is_none()
+                    && self.node_finder.is_none()
+                    && cursor_in_editor
+                    && ui.input(|i| i.key_pressed(key))
+                {
+                    self.radial_menu = Some(RadialMenu::new_at(cursor_pos));
+                }
+                radial_menu_released = !ui.input(|i| i.key_down(key));
+            }
+            Some(RadialMenuGesture::SecondaryDrag) => {
+                if self.radial_menu.is_none() && r.drag_started_by(PointerButton::Secondary) {
+                    let pos = r.interact_pointer_pos().unwrap_or(cursor_pos);
+                    self.radial_menu = Some(RadialMenu::new_at(pos));
+                }
+                radial_menu_released = !ui.input(|i| i.pointer.secondary_down());
+            }
+            None => self.radial_menu = None,
+        }
+        let radial_menu_closed = self.radial_menu.is_some() && radial_menu_released;
+        if let Some(radial_menu) = self.radial_menu {
+            let entries = RadialMenu::entries(
+                all_kinds.all_kinds(),
+                &self.template_usage,
+                self.settings.radial_menu_size,
+                user_state,
+            );
+            if let Some(node_kind) = radial_menu.show(ui.ctx(), entries, radial_menu_released) {
+                let new_node = self.add_node_from_template(
+                    &node_kind,
+                    canvas_view.screen_to_graph(radial_menu.center),
+                    user_state,
+                );
+                delayed_responses.push(NodeResponse::CreatedNode(new_node));
+            }
+            if radial_menu_released {
+                self.radial_menu = None;
+            }
+        }
 
         /* Draw the node finder, if open */
         let mut should_close_node_finder = false;
+        let mut finder_template = None;
         if let Some(ref mut node_finder) = self.node_finder {
             let mut node_finder_area = Area::new("node_finder").order(Order::Foreground);
             if let Some(pos) = node_finder.position {
                 node_finder_area = node_finder_area.current_pos(pos);
             }
-            node_finder_area.show(ui.ctx(), |ui| {
-                if let Some(node_kind) = node_finder.show(ui, all_kinds, user_state) {
-                    let new_node = self.graph.add_node(
-                        node_kind.node_graph_label(user_state),
-                        node_kind.user_data(user_state),
-                        |graph, node_id| node_kind.build_node(graph, user_state, node_id),
+            // Only offer the templates that can be connected to the port the
+            // finder was opened from. Finding them builds each listed
+            // template in a scratch graph, which the finder only does when
+            // the listed templates change.
+            let source = node_finder
+                .source_port
+                .and_then(|source| Some((source, self.graph.any_param_type(source).ok()?)));
+            let mut scratch = Graph::new();
+            let filter = |kind: &NodeTemplate, user_state: &mut UserState| match source {
+                Some((source, source_type)) => {
+                    let node_id = scratch.add_node(
+                        kind.node_graph_label(user_state),
+                        kind.user_data(user_state),
+                        |graph, node_id| kind.build_node(graph, user_state, node_id),
                     );
-                    self.node_positions.insert(
-                        new_node,
-                        cursor_pos - self.pan_zoom.pan - editor_rect.min.to_vec2(),
-                    );
-                    self.node_order.push(new_node);
-
-                    should_close_node_finder = true;
-                    delayed_responses.push(NodeResponse::CreatedNode(new_node));
+                    first_compatible_port(&scratch, node_id, source, source_type).is_some()
                 }
+                None => true,
+            };
+            node_finder.layout_direction = self.settings.layout_direction;
+            node_finder
+                .hint_text
+                .clone_from(&self.settings.strings.finder_hint);
+            node_finder_area.show(ui.ctx(), |ui| {
+                finder_template = node_finder.show_filtered(ui, &all_kinds, user_state, filter);
                 let finder_rect = ui.min_rect();
                 // If the cursor is not in the main editor, check if the cursor is in the finder
                 // if the cursor is in the finder, then we can consider that also in the editor.
@@ -211,88 +774,393 @@ where
                 }
             });
         }
+        if let Some(node_kind) = finder_template {
+            let source = self.node_finder.as_ref().and_then(|f| f.source_port);
+            // A node created for a port starts next to the node of the port,
+            // and is aligned with it once its size is known, see
+            // `align_to_partner`.
+            let partner = source.and_then(|source| {
+                let port_pos = port_locations.get(&source)?;
+                let node_rect = node_rects.get(&self.graph.any_param_node(source).ok()?)?;
+                Some((source, *port_pos, *node_rect))
+            });
+            let position =
+                match partner {
+                    Some((source, port_pos, node_rect)) if self.partner_on_right(source) => pos2(
+                        node_rect.right() + PARTNER_GAP * self.pan_zoom.zoom,
+                        port_pos.y,
+                    ),
+                    Some((_, port_pos, node_rect)) => {
+                        pos2(
+                            node_rect.left()
+                                - (PARTNER_GAP
+                                    + GraphNodeWidget::<
+                                        NodeData,
+                                        DataType,
+                                        ValueType,
+                                        ConnectionData,
+                                    >::MAX_NODE_SIZE[0])
+                                    * self.pan_zoom.zoom,
+                            port_pos.y,
+                        )
+                    }
+                    None => cursor_pos,
+                };
+            let new_node = self.add_node_from_template(
+                &node_kind,
+                canvas_view.screen_to_graph(position),
+                user_state,
+            );
+            should_close_node_finder = true;
+            delayed_responses.push(NodeResponse::CreatedNode(new_node));
+
+            // Connect the new node to the port the finder was opened from.
+            if let Some(source) = source {
+                let port = self
+                    .graph
+                    .any_param_type(source)
+                    .ok()
+                    .and_then(|source_type| {
+                        first_compatible_port(&self.graph, new_node, source, source_type)
+                    });
+                match (source, port) {
+                    (AnyParameterId::Output(output), Some(AnyParameterId::Input(input)))
+                    | (AnyParameterId::Input(input), Some(AnyParameterId::Output(output))) => {
+                        delayed_responses.push(NodeResponse::ConnectEventEnded { output, input });
+                    }
+                    _ => {}
+                }
+                if let (Some(port), Some(_)) = (port, partner) {
+                    self.pending_alignment = Some((new_node, port, source));
+                }
+            }
+        }
         if should_close_node_finder {
             self.node_finder = None;
         }
 
+        /* Draw the node context menu, if open */
+        let mut replacement = None;
+        let mut extracted = None;
+        let mut toggled_pin = None;
+        let mut cursor_in_node_menu = false;
+        // The node may have been deleted while the menu was open
+        let graph = &self.graph;
+        self.node_menu = self
+            .node_menu
+            .filter(|(node_id, _)| graph.nodes.contains_key(*node_id));
+        if let Some((node_id, pos)) = self.node_menu {
+            let pinned = self.pinned_nodes.contains(&node_id);
+            let strings = &self.settings.strings;
+            let menu = Area::new("node_menu")
+                .order(Order::Foreground)
+                .fixed_pos(pos)
+                .show(ui.ctx(), |ui| {
+                    Frame::menu(ui.style()).show(ui, |ui| {
+                        if ui
+                            .button(&strings.extract)
+                            .on_hover_text(&strings.extract_tooltip)
+                            .clicked()
+                        {
+                            extracted = Some(node_id);
+                        }
+                        if ui
+                            .button(if pinned { &strings.unpin } else { &strings.pin })
+                            .on_hover_text(&strings.pin_tooltip)
+                            .clicked()
+                        {
+                            toggled_pin = Some(node_id);
+                        }
+                        ui.menu_button(&strings.replace_with, |ui| {
+                            let kinds = all_kinds.all_kinds();
+                            let (categories, orphan_kinds) = group_by_category(&kinds, user_state);
+                            let mut template_button = |ui: &mut Ui, kind: &NodeTemplate| {
+                                let label = kind.node_finder_label(user_state).into_owned();
+                                if ui.button(label).clicked() {
+                                    replacement = Some((node_id, kind.clone()));
+                                    ui.close_menu();
+                                }
+                            };
+                            ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                                for (category, kinds) in categories {
+                                    ui.menu_button(category, |ui| {
+                                        for kind in kinds {
+                                            template_button(ui, kind);
+                                        }
+                                    });
+                                }
+                                for kind in orphan_kinds {
+                                    template_button(ui, kind);
+                                }
+                            });
+                        });
+                    });
+                });
+            cursor_in_node_menu = menu.response.rect.contains(cursor_pos);
+            if replacement.is_some()
+                || extracted.is_some()
+                || toggled_pin.is_some()
+                || (ui.input(|i| i.pointer.any_click()) && !cursor_in_node_menu)
+            {
+                self.node_menu = None;
+            }
+        }
+        let mut node_menu_responses = Vec::new();
+        if let Some((node_id, kind)) = replacement {
+            node_menu_responses = self.replace_node(node_id, &kind, user_state);
+        }
+        if let Some(node_id) = extracted {
+            node_menu_responses = self.extract_node(node_id, user_state);
+        }
+        if let Some(node_id) = toggled_pin {
+            let pinned = self.is_pinned(node_id);
+            self.set_pinned(node_id, !pinned);
+        }
+
+        // The connection may have been removed while its popup was open
+        let mut cursor_in_connection_popup = false;
+        let popup_connection = self.connection_popup.and_then(|(id, pos)| {
+            let connection = self.graph.connections.get_mut(id)?;
+            Some((id, connection, pos))
+        });
+        if let Some((id, connection, pos)) = popup_connection {
+            let (output, input) = (connection.output, connection.input);
+            let data = &mut connection.data;
+            let popup = Area::new("connection_popup")
+                .order(Order::Foreground)
+                .fixed_pos(pos)
+                .show(ui.ctx(), |ui| {
+                    Frame::menu(ui.style())
+                        .show(ui, |ui| data.data_widget(ui, output, input, user_state))
+                        .inner
+                });
+            if popup.inner {
+                node_menu_responses.push(NodeResponse::ConnectionDataChanged {
+                    output,
+                    input,
+                    connection: id,
+                    data: data.clone(),
+                });
+            }
+            cursor_in_connection_popup = popup.response.rect.contains(cursor_pos);
+            if ui.input(|i| i.pointer.any_click()) && !cursor_in_connection_popup {
+                self.connection_popup = None;
+            }
+        } else {
+            self.connection_popup = None;
+        }
+
         /* Draw connections */
+        // The port may have been removed by the application while dragging
+        if let Some((_, locator)) = self.connection_in_progress {
+            if !port_locations.contains_key(&locator) {
+                self.connection_in_progress = None;
+            }
+        }
         if let Some((_, ref locator)) = self.connection_in_progress {
             let port_type = self.graph.any_param_type(*locator).unwrap();
-            let connection_color = port_type.data_type_color(user_state);
-            let start_pos = port_locations[locator];
-
+            if let Some(color) = self.style.compatible_port_highlight {
+                let compatible = compatible_ports(
+                    &self.graph,
+                    self.connection_in_progress.unwrap(),
+                    &port_locations,
+                    &self.settings,
+                    user_state,
+                );
+                for (.., port_pos) in compatible {
+                    ui.painter().circle_stroke(
+                        port_pos,
+                        2.0 * self.style.port_radius * self.pan_zoom.zoom,
+                        Stroke::new(2.0 * self.pan_zoom.zoom, color),
+                    );
+                }
+            }
             // Find a port to connect to
-            fn snap_to_ports<
-                NodeData,
-                UserState,
-                DataType: DataTypeTrait<UserState>,
-                ValueType,
-                Key: slotmap::Key + Into<AnyParameterId>,
-                Value,
-            >(
-                graph: &Graph<NodeData, DataType, ValueType>,
-                port_type: &DataType,
-                ports: &SlotMap<Key, Value>,
-                port_locations: &PortLocations,
-                cursor_pos: Pos2,
-            ) -> Pos2 {
-                ports
-                    .iter()
-                    .find_map(|(port_id, _)| {
-                        let compatible_ports = graph
-                            .any_param_type(port_id.into())
-                            .map(|other| other == port_type)
-                            .unwrap_or(false);
-
-                        if compatible_ports {
-                            port_locations.get(&port_id.into()).and_then(|port_pos| {
-                                if port_pos.distance(cursor_pos) < DISTANCE_TO_CONNECT {
-                                    Some(*port_pos)
-                                } else {
-                                    None
-                                }
-                            })
-                        } else {
-                            None
-                        }
-                    })
-                    .unwrap_or(cursor_pos)
-            }
-
-            let (src_pos, dst_pos) = match locator {
-                AnyParameterId::Output(_) => (
-                    start_pos,
-                    snap_to_ports(
-                        &self.graph,
-                        port_type,
-                        &self.graph.inputs,
-                        &port_locations,
-                        cursor_pos,
-                    ),
-                ),
-                AnyParameterId::Input(_) => (
-                    snap_to_ports(
-                        &self.graph,
-                        port_type,
-                        &self.graph.outputs,
-                        &port_locations,
-                        cursor_pos,
-                    ),
-                    start_pos,
-                ),
+            let target = port_to_connect(
+                &self.graph,
+                self.connection_in_progress.unwrap(),
+                &port_locations,
+                cursor_pos,
+                (self.style.port_hit_radius + self.style.port_drop_padding) * self.pan_zoom.zoom,
+                &self.settings,
+                user_state,
+            );
+            if let Some((_, output, input)) = target {
+                if ui.input(|i| i.pointer.any_released()) {
+                    delayed_responses.push(NodeResponse::ConnectEventEnded { input, output });
+                }
+            }
+
+            // A bidirectional port acts as an input when dropped on an output
+            let start = match target {
+                Some((_, output, input)) if AnyParameterId::Output(output) != *locator => {
+                    AnyParameterId::Input(input)
+                }
+                _ => *locator,
+            };
+            let start_pos = port_locations.get(&start).copied().unwrap_or(cursor_pos);
+            let start_dir = port_direction(&self.graph, start, self.settings.layout_direction);
+            // The loose end keeps the direction of the port it was dragged from
+            let (end_pos, end_dir) = target.map_or((cursor_pos, start_dir), |(target, _, _)| {
+                (
+                    port_locations.get(&target).copied().unwrap_or(cursor_pos),
+                    port_direction(&self.graph, target, self.settings.layout_direction),
+                )
+            });
+            let ((src_pos, src_dir), (dst_pos, dst_dir)) = match start {
+                AnyParameterId::Output(_) => ((start_pos, start_dir), (end_pos, end_dir)),
+                AnyParameterId::Input(_) => ((end_pos, end_dir), (start_pos, start_dir)),
+            };
+            let info = ConnectionDrawInfo {
+                connection: None,
+                src_pos,
+                src_dir,
+                dst_pos,
+                dst_dir,
+                hovered: false,
+                opacity: 1.0,
+                width: self.style.connection.width * self.pan_zoom.zoom,
+                feathering: self.style.connection.feathering * self.pan_zoom.zoom,
+                curvature: self.style.connection.curvature,
+                color: self.style.connection.color,
+                zoom: self.pan_zoom.zoom,
+            };
+            port_type.draw_connection(ui.painter(), &info, user_state);
+        }
+
+        // Find the connection under the mouse, if any. Nodes are on top of
+        // connections, so the mouse can't hover connections behind them.
+        let hovered_connection = if self.connection_in_progress.is_none()
+            && self.ongoing_box_selection.is_none()
+            && self.ongoing_zoom_region.is_none()
+            && cursor_in_editor
+            && !cursor_in_finder
+            && !node_rects.values().any(|rect| rect.contains(cursor_pos))
+            && !matches!(&minimap, Some(minimap) if minimap.hovered())
+        {
+            self.connection_at(cursor_pos, &port_locations, &node_rects, &canvas_view)
+        } else {
+            None
+        };
+        // The ports and nodes at the ends are highlighted on the next frame
+        self.hovered_connection = hovered_connection;
+
+        // Forget the selected connection once it's removed
+        if let Some(id) = self.selected_connection {
+            if !self.graph.connections.contains_key(id) {
+                self.selected_connection = None;
+            }
+        }
+
+        // The weight labels, to drag them below
+        let mut weight_labels = Vec::new();
+        // The middle of the selected connection, where its delete button is
+        let mut delete_connection_button = None;
+        for (id, input, output, lane) in connection_lanes(&self.graph) {
+            let port_type = match self.graph.any_param_type(AnyParameterId::Output(output)) {
+                Ok(port_type) => port_type,
+                Err(_) => continue,
+            };
+            let (input_node, output_node) = match (
+                self.graph.try_get_input(input),
+                self.graph.try_get_output(output),
+            ) {
+                (Some(input), Some(output)) => (input.node, output.node),
+                _ => continue,
+            };
+            let path = match self.connection_path(
+                input,
+                output,
+                lane,
+                &port_locations,
+                &node_rects,
+                &canvas_view,
+            ) {
+                Some(path) => path,
+                None => continue,
+            };
+            let mut info = ConnectionDrawInfo {
+                connection: Some((output, input)),
+                src_pos: path[0].0,
+                src_dir: path[0].1,
+                dst_pos: path[1].0,
+                dst_dir: path[1].1,
+                hovered: hovered_connection == Some(id),
+                width: if hovered_connection == Some(id) || self.selected_connection == Some(id) {
+                    self.style.connection.hover_width
+                } else {
+                    self.style.connection.width
+                } * self.pan_zoom.zoom,
+                feathering: self.style.connection.feathering * self.pan_zoom.zoom,
+                curvature: self.style.connection.curvature,
+                color: self.style.connection.color,
+                opacity: if disabled_nodes.contains(&input_node)
+                    || disabled_nodes.contains(&output_node)
+                {
+                    self.style.disabled_opacity
+                } else {
+                    1.0
+                },
+                zoom: self.pan_zoom.zoom,
             };
-            draw_connection(ui.painter(), src_pos, dst_pos, connection_color);
+            for segment in path.windows(2) {
+                (info.src_pos, info.src_dir) = segment[0];
+                (info.dst_pos, info.dst_dir) = segment[1];
+                port_type.draw_connection(ui.painter(), &info, user_state);
+            }
+            let reroute_color = info
+                .color
+                .unwrap_or_else(|| port_type.data_type_color(user_state))
+                .linear_multiply(info.opacity);
+            for point in self.reroute_points.get(input).into_iter().flatten() {
+                ui.painter().circle_filled(
+                    canvas_view.graph_to_screen(*point),
+                    self.style.port_radius * self.pan_zoom.zoom,
+                    reroute_color,
+                );
+            }
+            let middle = path.len() / 2;
+            let center = connection_midpoint(path[middle - 1], path[middle], info.curvature);
+            if let Some(weight) = self.graph[id].data.weight() {
+                let rect = draw_weight_label(ui, center, weight, &self.style, self.pan_zoom.zoom);
+                weight_labels.push((id, rect));
+            } else if self.selected_connection == Some(id) && !self.settings.lock_layout {
+                // The weight label sits at the middle already, so the button
+                // is only drawn for connections without one.
+                let radius = DELETE_CONNECTION_BUTTON_RADIUS * self.pan_zoom.zoom;
+                draw_delete_button(ui, center, radius);
+                delete_connection_button = Some((id, center));
+            }
         }
 
-        for (input, output) in self.graph.iter_connections() {
-            let port_type = self
-                .graph
-                .any_param_type(AnyParameterId::Output(output))
-                .unwrap();
-            let connection_color = port_type.data_type_color(user_state);
-            let src_pos = port_locations[&AnyParameterId::Output(output)];
-            let dst_pos = port_locations[&AnyParameterId::Input(input)];
-            draw_connection(ui.painter(), src_pos, dst_pos, connection_color);
+        // The arrow keys nudge the selected nodes, unless a widget has the
+        // keyboard focus.
+        let nudge_node = self.selected_nodes.first().copied().filter(|_| {
+            cursor_in_editor
+                && !self.settings.lock_layout
+                && self.node_finder.is_none()
+                && ui.memory(|mem| mem.focus().is_none())
+        });
+        if let Some(node) = nudge_node {
+            let (nudge, shift) = ui.input(|i| {
+                let key = |key, dir: Vec2| if i.key_pressed(key) { dir } else { Vec2::ZERO };
+                let nudge = key(Key::ArrowLeft, -Vec2::X)
+                    + key(Key::ArrowRight, Vec2::X)
+                    + key(Key::ArrowUp, -Vec2::Y)
+                    + key(Key::ArrowDown, Vec2::Y);
+                (nudge, i.modifiers.shift)
+            });
+            if nudge != Vec2::ZERO {
+                let step = if shift {
+                    self.settings.nudge_large_step
+                } else {
+                    self.settings.nudge_step
+                };
+                delayed_responses.push(NodeResponse::MoveNode {
+                    node,
+                    drag_delta: nudge * step,
+                });
+            }
         }
 
         /* Handle responses from drawing nodes */
@@ -301,29 +1169,70 @@ where
         // are stored here to report them back to the user.
         let mut extra_responses: Vec<NodeResponse<UserResponse, NodeData>> = Vec::new();
 
+        // Connections that are duplicates or rejected by the policy aren't
+        // made, nor reported.
+        let (graph, settings) = (&self.graph, &self.settings);
+        delayed_responses.retain(|response| match *response {
+            NodeResponse::ConnectEventEnded { input, output } => {
+                accepts_connection(graph, settings, output, input)
+            }
+            _ => true,
+        });
+
         for response in delayed_responses.iter() {
             match response {
                 NodeResponse::ConnectEventStarted(node_id, port) => {
                     self.connection_in_progress = Some((*node_id, *port));
                 }
                 NodeResponse::ConnectEventEnded { input, output } => {
-                    self.graph.add_connection(*output, *input)
+                    let graph = &self.graph;
+                    let parallel = graph
+                        .connection_ids(*input)
+                        .iter()
+                        .any(|id| graph[*id].output == *output);
+                    if parallel || self.settings.occupied_input == OccupiedInputPolicy::Append {
+                        let _ = self.graph.append_connection(*output, *input);
+                        continue;
+                    }
+                    for &previous in self.graph.connection_ids(*input) {
+                        extra_responses.push(NodeResponse::DisconnectEvent {
+                            input: *input,
+                            output: self.graph[previous].output,
+                            connection: previous,
+                        });
+                    }
+                    let _ = self.graph.add_connection(*output, *input);
                 }
-                NodeResponse::CreatedNode(_) => {
+                NodeResponse::CreatedNode(node_id) => {
+                    if self.style.creation_highlight_duration > 0.0 {
+                        self.created_nodes.insert(*node_id, None);
+                    }
+                }
+                NodeResponse::ReplacedNode { .. }
+                | NodeResponse::ConnectionDataChanged { .. }
+                | NodeResponse::SelectionChanged(_)
+                | NodeResponse::CopiedNodes(_)
+                | NodeResponse::PastedNodes(_)
+                | NodeResponse::InconsistentState(_) => {
                     //Convenience NodeResponse for users
                 }
                 NodeResponse::SelectNode(node_id) => {
                     self.selected_nodes = Vec::from([*node_id]);
                 }
                 NodeResponse::DeleteNodeUi(node_id) => {
-                    let (node, disc_events) = self.graph.remove_node(*node_id);
+                    let (node, disc_events) = match self.graph.remove_node(*node_id) {
+                        Ok(removed) => removed,
+                        Err(_) => continue,
+                    };
                     // Pass the disconnection responses first so user code can perform cleanup
                     // before node removal response.
-                    extra_responses.extend(
-                        disc_events
-                            .into_iter()
-                            .map(|(input, output)| NodeResponse::DisconnectEvent { input, output }),
-                    );
+                    extra_responses.extend(disc_events.into_iter().map(|(id, connection)| {
+                        NodeResponse::DisconnectEvent {
+                            input: connection.input,
+                            output: connection.output,
+                            connection: id,
+                        }
+                    }));
                     // Pass the full node as a response so library users can
                     // listen for it and get their user data.
                     extra_responses.push(NodeResponse::DeleteNodeFull {
@@ -331,34 +1240,74 @@ where
                         node,
                     });
                     self.node_positions.remove(*node_id);
+                    self.node_widths.remove(*node_id);
+                    self.pinned_nodes.remove(node_id);
                     // Make sure to not leave references to old nodes hanging
                     self.selected_nodes.retain(|id| *id != *node_id);
                     self.node_order.retain(|id| *id != *node_id);
                 }
-                NodeResponse::DisconnectEvent { input, output } => {
-                    let other_node = self.graph.get_output(*output).node;
-                    self.graph.remove_connection(*input);
-                    self.connection_in_progress =
-                        Some((other_node, AnyParameterId::Output(*output)));
+                NodeResponse::DisconnectEvent {
+                    output, connection, ..
+                } => {
+                    self.graph.disconnect(*connection);
+                    if let Some(other) = self.graph.try_get_output(*output) {
+                        self.connection_in_progress =
+                            Some((other.node, AnyParameterId::Output(*output)));
+                    }
                 }
                 NodeResponse::RaiseNode(node_id) => {
-                    let old_pos = self
-                        .node_order
-                        .iter()
-                        .position(|id| *id == *node_id)
-                        .expect("Node to be raised should be in `node_order`");
-                    self.node_order.remove(old_pos);
-                    self.node_order.push(*node_id);
+                    self.bring_to_front(*node_id);
                 }
                 NodeResponse::MoveNode { node, drag_delta } => {
-                    self.node_positions[*node] += *drag_delta;
                     // Handle multi-node selection movement
-                    if self.selected_nodes.contains(node) && self.selected_nodes.len() > 1 {
-                        for n in self.selected_nodes.iter().copied() {
-                            if n != *node {
-                                self.node_positions[n] += *drag_delta;
-                            }
+                    let moved_nodes =
+                        if self.selected_nodes.contains(node) && self.selected_nodes.len() > 1 {
+                            self.selected_nodes.clone()
+                        } else {
+                            vec![*node]
+                        };
+                    let drag_delta = match self.settings.canvas_bounds {
+                        Some(bounds) => clamp_delta_to_bounds(
+                            *drag_delta,
+                            bounds,
+                            moved_nodes
+                                .iter()
+                                .filter_map(|n| node_rects.get(n))
+                                .map(|rect| canvas_view.screen_to_graph_rect(*rect)),
+                        ),
+                        None => *drag_delta,
+                    };
+                    for n in moved_nodes {
+                        if self.pinned_nodes.contains(&n) {
+                            continue;
                         }
+                        if let Some(pos) = self.node_positions.get_mut(n) {
+                            *pos += drag_delta;
+                        }
+                    }
+                }
+                NodeResponse::RenameNode {
+                    node, new_label, ..
+                } => {
+                    if let Some(node) = self.graph.nodes.get_mut(*node) {
+                        node.label = new_label.clone();
+                    }
+                }
+                NodeResponse::ResizeNode { node, width } => {
+                    self.node_widths.insert(*node, *width);
+                }
+                NodeResponse::CollapseParamGroup {
+                    node,
+                    group,
+                    collapsed,
+                } => {
+                    if let Some(group) = self
+                        .graph
+                        .nodes
+                        .get_mut(*node)
+                        .and_then(|node| node.groups.get_mut(*group))
+                    {
+                        group.collapsed = *collapsed;
                     }
                 }
                 NodeResponse::User(_) => {
@@ -373,50 +1322,129 @@ where
         // Handle box selection
         if let Some(box_start) = self.ongoing_box_selection {
             let selection_rect = Rect::from_two_pos(cursor_pos, box_start);
-            let bg_color = Color32::from_rgba_unmultiplied(200, 200, 200, 20);
-            let stroke_color = Color32::from_rgba_unmultiplied(200, 200, 200, 180);
             ui.painter().rect(
                 selection_rect,
                 2.0,
-                bg_color,
-                Stroke::new(3.0, stroke_color),
+                self.style.selection_box_fill,
+                self.style.selection_box_stroke,
             );
 
             self.selected_nodes = node_rects
-                .into_iter()
+                .iter()
                 .filter_map(|(node_id, rect)| {
-                    if selection_rect.intersects(rect) {
-                        Some(node_id)
+                    if selection_rect.intersects(*rect) {
+                        Some(*node_id)
                     } else {
                         None
                     }
                 })
                 .collect();
         }
+        if let Some(region_start) = self.ongoing_zoom_region {
+            let region = Rect::from_two_pos(cursor_pos, region_start);
+            let stroke = Stroke::new(1.0, self.style.selection_box_stroke.color);
+            ui.painter().rect_stroke(region, 2.0, stroke);
+        }
 
         // Push any responses that were generated during response handling.
         // These are only informative for the end-user and need no special
         // treatment here.
         delayed_responses.extend(extra_responses);
+        delayed_responses.extend(node_menu_responses);
 
         /* Mouse input handling */
 
         // This locks the context, so don't hold on to it for too long.
         let mouse = &ui.ctx().input(|i| i.pointer.clone());
 
-        if mouse.any_released() && self.connection_in_progress.is_some() {
-            self.connection_in_progress = None;
+        if mouse.any_released() {
+            if let Some((_, source)) = self.connection_in_progress.take() {
+                // When a connection is dropped on empty canvas, offer to
+                // create a node that can be connected to it.
+                let connected = delayed_responses
+                    .iter()
+                    .any(|response| matches!(response, NodeResponse::ConnectEventEnded { .. }));
+                let over_node = node_rects.values().any(|rect| rect.contains(cursor_pos));
+                if self.settings.finder_on_connection_drop
+                    && !self.settings.lock_layout
+                    && !connected
+                    && !over_node
+                    && cursor_in_editor
+                    && !cursor_in_finder
+                {
+                    self.node_finder = Some(NodeFinder::new_for_port(cursor_pos, source));
+                }
+            }
         }
 
-        if mouse.secondary_released() && cursor_in_editor && !cursor_in_finder {
-            self.node_finder = Some(NodeFinder::new_at(cursor_pos));
+        if mouse.secondary_released()
+            && !self.settings.lock_layout
+            && cursor_in_editor
+            && !cursor_in_finder
+            && !cursor_in_node_menu
+            && !cursor_in_connection_popup
+            && !radial_menu_closed
+        {
+            // Secondary clicking a port opens the node finder for it, and the
+            // new node gets connected to the port. Secondary clicking a node
+            // opens its context menu.
+            let hit_size = Vec2::splat(2.0 * self.style.port_hit_radius * self.pan_zoom.zoom);
+            let port_under_cursor = port_locations
+                .iter()
+                .find(|(_, pos)| Rect::from_center_size(**pos, hit_size).contains(cursor_pos))
+                .map(|(port, _)| *port);
+            match (port_under_cursor, self.hovered_node) {
+                (Some(port), _) => {
+                    self.node_finder = Some(NodeFinder::new_for_port(cursor_pos, port));
+                    self.node_menu = None;
+                }
+                (None, Some(node_id)) => {
+                    self.node_menu = Some((node_id, cursor_pos));
+                    self.node_finder = None;
+                }
+                (None, None) => self.node_finder = Some(NodeFinder::new_at(cursor_pos)),
+            }
         }
         if ui.ctx().input(|i| i.key_pressed(Key::Escape)) {
             self.node_finder = None;
+            self.radial_menu = None;
+            self.node_menu = None;
         }
 
         if r.dragged() && ui.ctx().input(|i| i.pointer.middle_down()) {
+            self.viewport_animation = None;
             self.pan_zoom.pan += ui.ctx().input(|i| i.pointer.delta());
+            self.pan_velocity = ui.ctx().input(|i| i.pointer.velocity());
+        } else if let Some(friction) = self.settings.kinetic_pan_friction {
+            // Keep gliding after the pan ends, until the momentum runs out or
+            // the user presses any button.
+            if self.pan_velocity.length() > 1.0 && !mouse.any_down() {
+                let dt = ui.ctx().input(|i| i.stable_dt).min(0.1);
+                self.pan_zoom.pan += self.pan_velocity * dt;
+                self.pan_velocity *= (-friction * dt).exp();
+                ui.ctx().request_repaint();
+            } else {
+                self.pan_velocity = Vec2::ZERO;
+            }
+        } else {
+            self.pan_velocity = Vec2::ZERO;
+        }
+
+        if self.settings.show_scrollbars {
+            self.scrollbars(ui, editor_rect, &node_rects);
+        }
+        if let Some(minimap) = &minimap {
+            self.minimap(ui, minimap, editor_rect, &node_rects);
+        }
+
+        if let Some(bounds) = self.settings.canvas_bounds {
+            let zoom = self.pan_zoom.zoom;
+            let scaled_bounds = Rect::from_min_max(
+                (bounds.min.to_vec2() * zoom).to_pos2(),
+                (bounds.max.to_vec2() * zoom).to_pos2(),
+            );
+            self.pan_zoom.pan =
+                clamp_pan_to_bounds(self.pan_zoom.pan, editor_rect.size(), scaled_bounds);
         }
 
         // Deselect and deactivate finder if the editor backround is clicked,
@@ -425,502 +1453,2344 @@ where
             self.selected_nodes = Vec::new();
             self.node_finder = None;
         }
-
-        if drag_started_on_background && mouse.primary_down() {
-            self.ongoing_box_selection = Some(cursor_pos);
+        // Clicking the button of the selected connection removes it, and
+        // clicking another connection selects it. The delete key removes the
+        // selected connection too.
+        let delete_connection = match delete_connection_button {
+            Some((id, center))
+                if click_on_background
+                    && center.distance(cursor_pos)
+                        < DELETE_CONNECTION_BUTTON_RADIUS * self.pan_zoom.zoom =>
+            {
+                Some(id)
+            }
+            _ => self.selected_connection.filter(|_| {
+                cursor_in_editor
+                    && !self.settings.lock_layout
+                    && ui.memory(|mem| mem.focus().is_none())
+                    && ui.input(|i| i.key_pressed(Key::Delete) || i.key_pressed(Key::Backspace))
+            }),
+        };
+        if let Some(id) = delete_connection {
+            if let Some(Connection { output, input, .. }) = self.graph.disconnect(id) {
+                delayed_responses.push(NodeResponse::DisconnectEvent {
+                    output,
+                    input,
+                    connection: id,
+                });
+            }
+            self.selected_connection = None;
+        } else if click_on_background || (mouse.any_click() && !cursor_in_editor) {
+            self.selected_connection = hovered_connection
+                .filter(|_| click_on_background && self.settings.select_connections);
         }
-        if mouse.primary_released() || drag_released_on_background {
-            self.ongoing_box_selection = None;
+        // Clicking a connection opens the popup to edit its data
+        if let (true, Some(id), None) = (click_on_background, hovered_connection, delete_connection)
+        {
+            if let Some(connection) = self.graph.connections.get(id) {
+                if connection.data.has_widget(user_state) {
+                    self.connection_popup = Some((id, cursor_pos));
+                }
+            }
         }
 
-        GraphResponse {
-            node_responses: delayed_responses,
-            cursor_in_editor,
-            cursor_in_finder,
-        }
-    }
+        // Dragging the weight label of a connection changes the weight
+        if let Some(id) = self.dragged_weight {
+            match self.graph.connections.get_mut(id) {
+                Some(connection) if mouse.primary_down() => {
+                    let delta = mouse.delta().x * self.settings.weight_drag_speed;
+                    if let (Some(weight), true) = (connection.data.weight(), delta != 0.0) {
+                        connection.data.set_weight(weight + delta);
+                        delayed_responses.push(NodeResponse::ConnectionDataChanged {
+                            output: connection.output,
+                            input: connection.input,
+                            connection: id,
+                            data: connection.data.clone(),
+                        });
+                    }
+                }
+                _ => self.dragged_weight = None,
+            }
+        } else if drag_started_on_background && mouse.primary_down() && !self.settings.lock_layout {
+            let press_pos = mouse.press_origin().unwrap_or(cursor_pos);
+            self.dragged_weight = weight_labels
+                .iter()
+                .find(|(_, rect)| rect.contains(press_pos))
+                .map(|(id, _)| *id);
+        }
+
+        // Dragging a reroute point, or the midpoint of a connection segment
+        // to create a new one, moves it instead of starting a box selection.
+        if let Some((input, idx)) = self.dragged_reroute_point {
+            match self
+                .reroute_points
+                .get_mut(input)
+                .and_then(|p| p.get_mut(idx))
+            {
+                Some(point) if mouse.primary_down() => {
+                    *point = canvas_view.screen_to_graph(cursor_pos);
+                }
+                _ => self.dragged_reroute_point = None,
+            }
+        } else if drag_started_on_background
+            && mouse.primary_down()
+            && !self.settings.lock_layout
+            && self.dragged_weight.is_none()
+        {
+            let press_pos = mouse.press_origin().unwrap_or(cursor_pos);
+            self.dragged_reroute_point =
+                self.grab_reroute_point(press_pos, &port_locations, &node_rects, &canvas_view);
+            // Dragging a connection near one of its ends picks up that end
+            if self.dragged_reroute_point.is_none() {
+                let grabbed =
+                    self.grab_connection_end(press_pos, &port_locations, &node_rects, &canvas_view);
+                if let Some((connection, Connection { output, input, .. })) = grabbed {
+                    delayed_responses.push(NodeResponse::DisconnectEvent {
+                        output,
+                        input,
+                        connection,
+                    });
+                }
+            }
+        }
+        // Double clicking a reroute point removes it. Otherwise, double
+        // clicking a connection removes the connection.
+        let mut double_click_on_canvas = false;
+        if r.double_clicked() && !self.settings.lock_layout {
+            if let Some((input, idx)) = self.reroute_point_at(cursor_pos, &canvas_view) {
+                self.reroute_points[input].remove(idx);
+            } else if let Some(id) = hovered_connection {
+                if self.settings.delete_connection_on_double_click {
+                    if let Some(Connection { output, input, .. }) = self.graph.disconnect(id) {
+                        delayed_responses.push(NodeResponse::DisconnectEvent {
+                            output,
+                            input,
+                            connection: id,
+                        });
+                    }
+                }
+            } else {
+                double_click_on_canvas = true;
+            }
+        }
+        if double_click_on_canvas && self.settings.finder_on_double_click {
+            self.node_finder = Some(NodeFinder::new_at(cursor_pos));
+        }
+
+        if drag_started_on_background
+            && mouse.primary_down()
+            && self.dragged_reroute_point.is_none()
+            && self.dragged_weight.is_none()
+            && self.connection_in_progress.is_none()
+        {
+            let zoom_region_held = match self.settings.zoom_region_modifiers {
+                Some(modifiers) => ui.input(|i| i.modifiers.matches(modifiers)),
+                None => false,
+            };
+            if zoom_region_held {
+                self.ongoing_zoom_region = Some(cursor_pos);
+            } else {
+                self.ongoing_box_selection = Some(cursor_pos);
+            }
+        }
+        if mouse.primary_released() || drag_released_on_background {
+            self.ongoing_box_selection = None;
+            // The region is zoomed into so it fills the editor. Tiny regions
+            // are taken as accidental drags.
+            let region = self
+                .ongoing_zoom_region
+                .take()
+                .map(|start| Rect::from_two_pos(start, cursor_pos))
+                .filter(|region| region.width().min(region.height()) > 4.0);
+            if let Some(region) = region {
+                let region = canvas_view.screen_to_graph_rect(region);
+                let zoom = (editor_rect.size() / region.size())
+                    .min_elem()
+                    .clamp(self.settings.min_zoom, self.settings.max_zoom);
+                self.animate_viewport_to(region.center(), zoom);
+            }
+        }
+
+        // Holding the overview key shows the whole graph. The new viewport
+        // is fitted to the nodes drawn this frame, and shown on the next one.
+        let overview_held = match self.settings.overview_key {
+            Some(key) => {
+                ui.input(|i| i.key_down(key) && i.modifiers.is_none())
+                    && ui.memory(|mem| mem.focus().is_none())
+            }
+            None => false,
+        };
+        let half_size = editor_rect.size() / 2.0;
+        match self.overview {
+            None if overview_held && cursor_in_editor && self.node_finder.is_none() => {
+                let bounds = node_rects
+                    .values()
+                    .map(|rect| canvas_view.screen_to_graph_rect(*rect))
+                    .fold(Rect::NOTHING, |bounds, rect| bounds.union(rect));
+                if bounds.is_positive() {
+                    // The overview never zooms in
+                    let bounds = bounds.expand(OVERVIEW_MARGIN);
+                    let zoom = (editor_rect.size() / bounds.size())
+                        .min_elem()
+                        .min(self.pan_zoom.zoom);
+                    self.overview = Some(self.pan_zoom);
+                    self.viewport_animation = None;
+                    self.pan_zoom = PanZoom {
+                        pan: half_size - bounds.center().to_vec2() * zoom,
+                        zoom,
+                    };
+                    ui.ctx().request_repaint();
+                }
+            }
+            Some(previous) => {
+                let previous_rect = CanvasView::new(editor_rect, &previous).visible_rect;
+                ui.painter().rect_stroke(
+                    canvas_view.graph_to_screen_rect(previous_rect),
+                    2.0,
+                    ui.visuals().selection.stroke,
+                );
+                if mouse.primary_clicked() && cursor_in_editor {
+                    let target = canvas_view.screen_to_graph(cursor_pos);
+                    self.overview = None;
+                    self.pan_zoom = PanZoom {
+                        pan: half_size - target.to_vec2() * previous.zoom,
+                        zoom: previous.zoom,
+                    };
+                } else if !overview_held {
+                    self.overview = None;
+                    self.pan_zoom = previous;
+                }
+            }
+            None => {}
+        }
+
+        // The frame key fits the selected nodes, or all of them, in the
+        // editor from the next frame on.
+        let frame_pressed = match self.settings.frame_key {
+            Some(key) => {
+                ui.input(|i| i.key_pressed(key) && i.modifiers.is_none())
+                    && ui.memory(|mem| mem.focus().is_none())
+            }
+            None => false,
+        };
+        if frame_pressed
+            && cursor_in_editor
+            && self.overview.is_none()
+            && self.node_finder.is_none()
+        {
+            if self.selected_nodes.is_empty() {
+                self.zoom_to_fit();
+            } else {
+                let nodes = self.selected_nodes.clone();
+                self.frame_nodes(&nodes);
+            }
+            ui.ctx().request_repaint();
+        }
+
+        if self.selected_nodes != previous_selection {
+            delayed_responses.push(NodeResponse::SelectionChanged(self.selected_nodes.clone()));
+        }
+
+        GraphResponse {
+            node_responses: delayed_responses,
+            cursor_in_editor,
+            cursor_in_finder,
+            background: Some(r),
+            editor_rect,
+        }
+    }
+
+    /// Draws a horizontal and a vertical scrollbar, showing the visible part
+    /// of the graph relative to the extents of all its nodes. Dragging the
+    /// scrollbars pans the graph.
+    fn scrollbars(&mut self, ui: &mut Ui, editor_rect: Rect, node_rects: &NodeRects) {
+        const THICKNESS: f32 = 8.0;
+
+        let canvas_view = CanvasView::new(editor_rect, &self.pan_zoom);
+        let visible_rect = canvas_view.visible_rect;
+        let mut extents = node_rects.values().fold(visible_rect, |extents, rect| {
+            extents.union(canvas_view.screen_to_graph_rect(*rect))
+        });
+        if let Some(bounds) = self.settings.canvas_bounds {
+            extents = extents.union(bounds);
+        }
+
+        let tracks = [
+            Rect::from_min_max(
+                pos2(editor_rect.left(), editor_rect.bottom() - THICKNESS),
+                pos2(editor_rect.right() - THICKNESS, editor_rect.bottom()),
+            ),
+            Rect::from_min_max(
+                pos2(editor_rect.right() - THICKNESS, editor_rect.top()),
+                pos2(editor_rect.right(), editor_rect.bottom() - THICKNESS),
+            ),
+        ];
+        for (axis, track) in tracks.into_iter().enumerate() {
+            let extent_len = extents.max[axis] - extents.min[axis];
+            let track_len = track.max[axis] - track.min[axis];
+            let scale = track_len / extent_len;
+
+            let mut thumb = track;
+            thumb.min[axis] =
+                track.min[axis] + (visible_rect.min[axis] - extents.min[axis]) * scale;
+            thumb.max[axis] =
+                track.min[axis] + (visible_rect.max[axis] - extents.min[axis]) * scale;
+
+            let resp = ui.interact(thumb, ui.id().with(("scrollbar", axis)), Sense::drag());
+            if resp.dragged() {
+                self.pan_zoom.pan[axis] -= resp.drag_delta()[axis] / scale * self.pan_zoom.zoom;
+            }
+
+            let visuals = ui.style().interact(&resp);
+            ui.painter()
+                .rect_filled(track, 0.0, ui.visuals().extreme_bg_color);
+            ui.painter()
+                .rect_filled(thumb.shrink(1.0), THICKNESS / 2.0, visuals.bg_fill);
+        }
+    }
+
+    /// Draws the minimap in `response.rect`, with the nodes and the visible
+    /// part of the graph. Pressing or dragging on it centers the viewport on
+    /// the position under the mouse.
+    fn minimap(&mut self, ui: &Ui, response: &Response, editor_rect: Rect, node_rects: &NodeRects) {
+        let style = self.style.minimap;
+        let canvas_view = CanvasView::new(editor_rect, &self.pan_zoom);
+        let graph_rects: Vec<(NodeId, Rect)> = node_rects
+            .iter()
+            .map(|(node_id, rect)| (*node_id, canvas_view.screen_to_graph_rect(*rect)))
+            .collect();
+        // The map only fits the nodes, so it doesn't move along with the
+        // viewport while dragging on it.
+        let mut extents = graph_rects
+            .iter()
+            .fold(Rect::NOTHING, |extents, (_, rect)| extents.union(*rect));
+        if !extents.is_positive() {
+            extents = canvas_view.visible_rect;
+        }
+        let extents = extents.expand(OVERVIEW_MARGIN);
+        let map_rect = response.rect;
+        let scale = (map_rect.size() / extents.size()).min_elem();
+        let to_map = |pos: Pos2| map_rect.center() + (pos - extents.center()) * scale;
+        let to_map_rect = |rect: Rect| Rect::from_min_max(to_map(rect.min), to_map(rect.max));
+
+        if response.is_pointer_button_down_on() {
+            if let Some(pos) = response.interact_pointer_pos() {
+                let target = extents.center() + (pos - map_rect.center()) / scale;
+                self.viewport_animation = None;
+                self.pan_zoom.pan =
+                    editor_rect.size() / 2.0 - target.to_vec2() * self.pan_zoom.zoom;
+            }
+        }
+
+        let painter = ui.painter().with_clip_rect(map_rect);
+        painter.rect(
+            map_rect,
+            2.0,
+            style.background,
+            ui.visuals().widgets.noninteractive.bg_stroke,
+        );
+        for (node_id, rect) in graph_rects {
+            let color = if self.selected_nodes.contains(&node_id) {
+                ui.visuals().selection.bg_fill
+            } else {
+                style.node_color
+            };
+            painter.rect_filled(to_map_rect(rect), 1.0, color);
+        }
+        let viewport = to_map_rect(CanvasView::new(editor_rect, &self.pan_zoom).visible_rect);
+        painter.rect_stroke(viewport, 0.0, style.viewport_stroke);
+    }
+
+    /// Returns the points a connection passes through, in screen
+    /// coordinates: its ends, and the reroute points between them. Each point
+    /// comes with the direction the connection has when passing through it.
+    /// Connections from a node to itself without reroute points pass over the
+    /// top of the node. Parallel connections in other `lane`s than zero leave
+    /// and enter their ports at an angle.
+    fn connection_path(
+        &self,
+        input: InputId,
+        output: OutputId,
+        lane: f32,
+        port_locations: &PortLocations,
+        node_rects: &NodeRects,
+        canvas_view: &CanvasView,
+    ) -> Option<Vec<(Pos2, Vec2)>> {
+        let node = self.graph.inputs.get(input)?.node;
+        let reroute_points = self.reroute_points.get(input).into_iter().flatten();
+        let self_loop = if node == self.graph.outputs.get(output)?.node
+            && reroute_points.clone().next().is_none()
+        {
+            node_rects.get(&node)
+        } else {
+            None
+        };
+        let output = AnyParameterId::Output(output);
+        // Connections pass through reroute points from outputs to inputs
+        let forward = match self.settings.layout_direction {
+            LayoutDirection::LeftToRight => Vec2::X,
+            LayoutDirection::RightToLeft => -Vec2::X,
+        };
+        let input = AnyParameterId::Input(input);
+        let fan_out = emath::Rot2::from_angle(lane * PARALLEL_CONNECTION_ANGLE);
+        let src = (
+            *port_locations.get(&output)?,
+            fan_out * port_direction(&self.graph, output, self.settings.layout_direction),
+        );
+        let dst = (
+            *port_locations.get(&input)?,
+            fan_out.inverse() * port_direction(&self.graph, input, self.settings.layout_direction),
+        );
+        if let Some(node_rect) = self_loop {
+            // Leave the output, cross above the node and come back into the
+            // input from outside
+            let gap = SELF_LOOP_GAP * self.pan_zoom.zoom;
+            let top = node_rect.top() - gap;
+            let start = pos2(src.0.x + src.1.x * gap, top);
+            let end = pos2(dst.0.x - dst.1.x * gap, top);
+            let across = vec2((end.x - start.x).signum(), 0.0);
+            return Some(vec![src, (start, across), (end, across), dst]);
+        }
+        let path = std::iter::once(src)
+            .chain(reroute_points.map(|point| (canvas_view.graph_to_screen(*point), forward)))
+            .chain(std::iter::once(dst))
+            .collect();
+        Some(path)
+    }
+
+    /// Returns the connection closest to the screen position `pos`, if any is
+    /// close enough to hover it.
+    fn connection_at(
+        &self,
+        pos: Pos2,
+        port_locations: &PortLocations,
+        node_rects: &NodeRects,
+        canvas_view: &CanvasView,
+    ) -> Option<ConnectionId> {
+        connection_lanes(&self.graph)
+            .filter_map(|(id, input, output, lane)| {
+                let distance = self
+                    .connection_path(input, output, lane, port_locations, node_rects, canvas_view)?
+                    .windows(2)
+                    .map(|segment| {
+                        connection_distance(
+                            segment[0],
+                            segment[1],
+                            self.style.connection.curvature,
+                            pos,
+                        )
+                    })
+                    .fold(f32::INFINITY, f32::min);
+                Some((id, distance))
+            })
+            .filter(|(_, distance)| *distance < DISTANCE_TO_HOVER_CONNECTION)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(connection, _)| connection)
+    }
+
+    /// Detaches the end of a connection near the screen position `pos`, so
+    /// it can be dragged to another port. Returns the detached connection.
+    fn grab_connection_end(
+        &mut self,
+        pos: Pos2,
+        port_locations: &PortLocations,
+        node_rects: &NodeRects,
+        canvas_view: &CanvasView,
+    ) -> Option<(ConnectionId, Connection<ConnectionData>)> {
+        let id = self.connection_at(pos, port_locations, node_rects, canvas_view)?;
+        let (output, input) = (self.graph[id].output, self.graph[id].input);
+        let output_distance = pos.distance(*port_locations.get(&AnyParameterId::Output(output))?);
+        let input_distance = pos.distance(*port_locations.get(&AnyParameterId::Input(input))?);
+        let grab_distance = DISTANCE_TO_GRAB_CONNECTION_END * self.pan_zoom.zoom;
+        // The end that is picked up moves, and the other end stays attached
+        let fixed_end = if output_distance < input_distance.min(grab_distance) {
+            (self.graph[input].node, AnyParameterId::Input(input))
+        } else if input_distance < grab_distance {
+            (self.graph[output].node, AnyParameterId::Output(output))
+        } else {
+            return None;
+        };
+        let connection = self.graph.disconnect(id)?;
+        self.connection_in_progress = Some(fixed_end);
+        Some((id, connection))
+    }
+
+    /// Returns the reroute point at the screen position `pos`, if any.
+    fn reroute_point_at(&self, pos: Pos2, canvas_view: &CanvasView) -> Option<(InputId, usize)> {
+        let grab_distance = DISTANCE_TO_GRAB_REROUTE_POINT * self.pan_zoom.zoom;
+        self.reroute_points.iter().find_map(|(input, points)| {
+            points
+                .iter()
+                .position(|point| canvas_view.graph_to_screen(*point).distance(pos) < grab_distance)
+                .map(|idx| (input, idx))
+        })
+    }
+
+    /// Returns the reroute point to drag when a drag starts at the screen
+    /// position `pos`. When `pos` is at the midpoint of a connection segment,
+    /// a new reroute point is inserted there.
+    fn grab_reroute_point(
+        &mut self,
+        pos: Pos2,
+        port_locations: &PortLocations,
+        node_rects: &NodeRects,
+        canvas_view: &CanvasView,
+    ) -> Option<(InputId, usize)> {
+        if let Some(existing) = self.reroute_point_at(pos, canvas_view) {
+            return Some(existing);
+        }
+
+        let grab_distance = DISTANCE_TO_GRAB_REROUTE_POINT * self.pan_zoom.zoom;
+        let (input, idx) = connection_lanes(&self.graph).find_map(|(_, input, output, lane)| {
+            self.connection_path(input, output, lane, port_locations, node_rects, canvas_view)?
+                .windows(2)
+                .position(|segment| {
+                    connection_midpoint(segment[0], segment[1], self.style.connection.curvature)
+                        .distance(pos)
+                        < grab_distance
+                })
+                .map(|idx| (input, idx))
+        })?;
+        let points = self.reroute_points.entry(input)?.or_default();
+        // The corners of a connection looping around its node aren't reroute
+        // points, and are replaced by the new one.
+        let idx = idx.min(points.len());
+        points.insert(idx, canvas_view.screen_to_graph(pos));
+        Some((input, idx))
+    }
+
+    /// Whether a node created for the port `partner` goes to the right of the
+    /// node of the port. Ports on the top and bottom edges put new nodes on
+    /// the side of the connection, as if they were on the left or right.
+    fn partner_on_right(&self, partner: AnyParameterId) -> bool {
+        let direction = self.settings.layout_direction;
+        match self
+            .graph
+            .any_param_side(partner)
+            .map(|s| direction.port_side(s))
+        {
+            Ok(PortSide::Right) => true,
+            Ok(PortSide::Left) => false,
+            _ => {
+                let output_side = direction.port_side(PortSide::Right);
+                matches!(partner, AnyParameterId::Output(_)) == (output_side == PortSide::Right)
+            }
+        }
+    }
+
+    /// Moves a node that was created for the port `partner`, so it sits
+    /// [`PARTNER_GAP`] away from the node of the partner, on the side of the
+    /// connection, and its `port` is at the same height as the partner. This
+    /// needs the size of the node, so it happens on the first frame it's
+    /// drawn.
+    fn align_to_partner(
+        &mut self,
+        node_id: NodeId,
+        port: AnyParameterId,
+        partner: AnyParameterId,
+        port_locations: &PortLocations,
+        node_rects: &NodeRects,
+    ) {
+        let partner_node = match self.graph.any_param_node(partner) {
+            Ok(partner_node) => partner_node,
+            Err(_) => return,
+        };
+        let (port_pos, partner_pos, node_rect, partner_rect) = match (
+            port_locations.get(&port),
+            port_locations.get(&partner),
+            node_rects.get(&node_id),
+            node_rects.get(&partner_node),
+        ) {
+            (Some(a), Some(b), Some(c), Some(d)) => (a, b, c, d),
+            _ => return,
+        };
+        let gap = PARTNER_GAP * self.pan_zoom.zoom;
+        let dx = if self.partner_on_right(partner) {
+            partner_rect.right() + gap - node_rect.left()
+        } else {
+            partner_rect.left() - gap - node_rect.right()
+        };
+        let dy = partner_pos.y - port_pos.y;
+        if let Some(position) = self.node_positions.get_mut(node_id) {
+            *position += vec2(dx, dy) / self.pan_zoom.zoom;
+        }
+    }
+
+    /// Adds a new node built from `template` at the given graph-space
+    /// position, or the closest free one, and records the template usage.
+    fn add_node_from_template(
+        &mut self,
+        template: &NodeTemplate,
+        position: Pos2,
+        user_state: &mut UserState,
+    ) -> NodeId {
+        let new_node = self.graph.add_node(
+            template.node_graph_label(user_state),
+            template.user_data(user_state),
+            |graph, node_id| {
+                template.build_node(graph, user_state, node_id);
+                graph[node_id].template_version = template.version(user_state);
+            },
+        );
+        let position = self.free_position(position);
+        self.node_positions.insert(new_node, position);
+        self.node_order.push(new_node);
+        let key = template.node_finder_label(user_state).into_owned();
+        for (name, value) in self.template_defaults.get(&key).into_iter().flatten() {
+            if let Ok(input) = self.graph[new_node].get_input(name) {
+                self.graph[input].value = value.clone();
+            }
+        }
+        *self.template_usage.entry(key).or_default() += 1;
+        new_node
+    }
+
+    /// Replaces a node with one built from `template`, keeping its id,
+    /// position and size. Each old parameter passes its value and connections
+    /// to the new parameter given by [`NodeTemplateTrait::conversion_from`].
+    /// Otherwise, it goes to the new parameter with the same name and data
+    /// type or, failing that, to the first unmatched new parameter with the
+    /// same data type.
+    ///
+    /// Returns a [`NodeResponse::DisconnectEvent`] for each connection of the
+    /// old parameters, a [`NodeResponse::ConnectEventEnded`] for each
+    /// connection that was restored, and a [`NodeResponse::ReplacedNode`]
+    /// with the old node. Returns nothing if the node doesn't exist.
+    pub fn replace_node(
+        &mut self,
+        node_id: NodeId,
+        template: &NodeTemplate,
+        user_state: &mut UserState,
+    ) -> Vec<NodeResponse<UserResponse, NodeData>> {
+        if !self.graph.nodes.contains_key(node_id) {
+            return Vec::new();
+        }
+        let conversion = template.conversion_from(node_id, &self.graph, user_state);
+        let old_label = std::mem::replace(
+            &mut self.graph[node_id].label,
+            template.node_graph_label(user_state),
+        );
+        let old_user_data = std::mem::replace(
+            &mut self.graph[node_id].user_data,
+            template.user_data(user_state),
+        );
+        let old_groups = std::mem::take(&mut self.graph[node_id].groups);
+        let old_version = self.graph[node_id].template_version;
+        let old_inputs = std::mem::take(&mut self.graph[node_id].inputs);
+        let old_outputs = std::mem::take(&mut self.graph[node_id].outputs);
+        template.build_node(&mut self.graph, user_state, node_id);
+        self.graph[node_id].template_version = template.version(user_state);
+        let new_inputs = self.graph[node_id].inputs.clone();
+        let new_outputs = self.graph[node_id].outputs.clone();
+
+        // Put the old params back for now, so they're removed along with
+        // their connections below.
+        self.graph[node_id]
+            .inputs
+            .extend(old_inputs.iter().cloned());
+        self.graph[node_id]
+            .outputs
+            .extend(old_outputs.iter().cloned());
+
+        let mut responses = Vec::new();
+        let is_old_input = |input: InputId| old_inputs.iter().any(|(_, id)| *id == input);
+        let is_old_output = |output: OutputId| old_outputs.iter().any(|(_, id)| *id == output);
+        // The connections of each old input come first, in their order, so
+        // the restored connections keep it.
+        let old_connections: Vec<_> = old_inputs
+            .iter()
+            .flat_map(|(_, input)| self.graph.connection_ids(*input).iter().copied())
+            .chain(
+                self.graph
+                    .connections
+                    .iter()
+                    .filter(|(_, c)| is_old_output(c.output) && !is_old_input(c.input))
+                    .map(|(id, _)| id),
+            )
+            .map(|id| (id, self.graph[id].input, self.graph[id].output))
+            .collect();
+        responses.extend(old_connections.iter().map(|&(connection, input, output)| {
+            NodeResponse::DisconnectEvent {
+                input,
+                output,
+                connection,
+            }
+        }));
+
+        let graph = &mut self.graph;
+        let input_pairs = match_params(&old_inputs, &new_inputs, &conversion.inputs, |old, new| {
+            graph[old].typ == graph[new].typ
+        });
+        let output_pairs = match_params(
+            &old_outputs,
+            &new_outputs,
+            &conversion.outputs,
+            |old, new| graph[old].typ == graph[new].typ,
+        );
+        let mut input_map = HashMap::new();
+        for (old, new) in input_pairs {
+            graph[new].value = std::mem::take(&mut graph[old].value);
+            if let Some(points) = self.reroute_points.remove(old) {
+                self.reroute_points.insert(new, points);
+            }
+            input_map.insert(old, new);
+        }
+        for (name, value) in conversion.values {
+            if let Some((_, input)) = new_inputs.iter().find(|(n, _)| *n == name) {
+                graph[*input].value = value;
+            }
+        }
+        let output_map: HashMap<_, _> = output_pairs.into_iter().collect();
+
+        // Both ends of each connection are remapped at once, so the
+        // connections from the node to itself are restored too. The
+        // connections of old params without a new one are dropped.
+        let new_connections: Vec<_> = old_connections
+            .iter()
+            .filter_map(|&(id, input, output)| {
+                let input = if is_old_input(input) {
+                    *input_map.get(&input)?
+                } else {
+                    input
+                };
+                let output = if is_old_output(output) {
+                    *output_map.get(&output)?
+                } else {
+                    output
+                };
+                Some((id, input, output))
+            })
+            .collect();
+        // The restored connections keep their data.
+        let mut old_data: HashMap<ConnectionId, ConnectionData> = old_connections
+            .iter()
+            .filter_map(|&(id, _, _)| Some((id, graph.disconnect(id)?.data)))
+            .collect();
+        for (_, input) in &old_inputs {
+            let _ = graph.remove_input_param(*input);
+        }
+        for (_, output) in &old_outputs {
+            let _ = graph.remove_output_param(*output);
+        }
+        for (id, input, output) in new_connections {
+            // The other connections of the inputs this node is connected to
+            // are kept.
+            let data = match old_data.remove(&id) {
+                Some(data) => data,
+                None => continue,
+            };
+            if graph
+                .append_connection_with_data(output, input, data)
+                .is_ok()
+            {
+                responses.push(NodeResponse::ConnectEventEnded { input, output });
+            }
+        }
+
+        responses.push(NodeResponse::ReplacedNode {
+            node_id,
+            old_node: Node {
+                id: node_id,
+                label: old_label,
+                inputs: old_inputs,
+                outputs: old_outputs,
+                groups: old_groups,
+                template_version: old_version,
+                user_data: old_user_data,
+            },
+        });
+        responses
+    }
 }
 
-fn draw_connection(painter: &Painter, src_pos: Pos2, dst_pos: Pos2, color: Color32) {
-    let connection_stroke = egui::Stroke { width: 5.0, color };
+impl<NodeData, DataType, ValueType, NodeTemplate, UserState, ConnectionData>
+    GraphEditorState<NodeData, DataType, ValueType, NodeTemplate, UserState, ConnectionData>
+where
+    DataType: PartialEq,
+    ConnectionData: Default,
+{
+    /// Removes a node from the chain it's in: all its connections are
+    /// removed, and each node that received a value through it gets connected
+    /// directly to the node upstream, when their data types match. This is the
+    /// opposite of inserting a node in the middle of a connection.
+    ///
+    /// The new connections are added next to the other connections of the
+    /// downstream inputs, and only when the [`EditorSettings`] and
+    /// [`NodeDataTrait::can_connect`] allow them.
+    ///
+    /// Returns a [`NodeResponse::DisconnectEvent`] for each removed connection,
+    /// and a [`NodeResponse::ConnectEventEnded`] for each new one. Returns
+    /// nothing if the node doesn't exist.
+    pub fn extract_node<UserResponse: UserResponseTrait>(
+        &mut self,
+        node_id: NodeId,
+        user_state: &mut UserState,
+    ) -> Vec<NodeResponse<UserResponse, NodeData>>
+    where
+        NodeData: NodeDataTrait<
+            UserState = UserState,
+            DataType = DataType,
+            ValueType = ValueType,
+            ConnectionData = ConnectionData,
+        >,
+    {
+        let graph = &self.graph;
+        let node = match graph.nodes.get(node_id) {
+            Some(node) => node,
+            None => return Vec::new(),
+        };
+        let upstream: Vec<_> = node
+            .input_ids()
+            .flat_map(|input| graph.connection_ids(input))
+            .map(|&id| (id, graph[id].input, graph[id].output))
+            .collect();
+        let downstream: Vec<_> = graph
+            .connections
+            .iter()
+            .filter(|(_, c)| {
+                // Loops on the node itself are already in `upstream`
+                let output_node = graph.try_get_output(c.output).map(|output| output.node);
+                let input_node = graph.try_get_input(c.input).map(|input| input.node);
+                output_node == Some(node_id) && input_node.is_some() && input_node != output_node
+            })
+            .map(|(id, c)| (id, c.input, c.output))
+            .collect();
 
-    let control_scale = ((dst_pos.x - src_pos.x) / 2.0).max(30.0);
-    let src_control = src_pos + Vec2::X * control_scale;
-    let dst_control = dst_pos - Vec2::X * control_scale;
+        // Each downstream input takes the first upstream output of its type
+        let healed: Vec<_> = downstream
+            .iter()
+            .filter_map(|(_, input, _)| {
+                let typ = &graph.try_get_input(*input)?.typ;
+                let (.., output) =
+                    upstream
+                        .iter()
+                        .find(|(.., output)| match graph.try_get_output(*output) {
+                            Some(output) => output.typ == *typ,
+                            None => false,
+                        })?;
+                Some((*input, *output))
+            })
+            .collect();
+
+        let mut responses = Vec::new();
+        for &(connection, input, output) in upstream.iter().chain(&downstream) {
+            self.graph.disconnect(connection);
+            responses.push(NodeResponse::DisconnectEvent {
+                input,
+                output,
+                connection,
+            });
+        }
+        for (input, output) in healed {
+            let graph = &self.graph;
+            let input_node = graph[input].node;
+            let allowed = accepts_connection(graph, &self.settings, output, input)
+                && graph[input_node]
+                    .user_data
+                    .can_connect(input_node, output, input, graph, user_state);
+            if allowed && self.graph.append_connection(output, input).is_ok() {
+                responses.push(NodeResponse::ConnectEventEnded { input, output });
+            }
+        }
+        responses
+    }
+}
+
+/// Whether the `settings` allow connecting `output` to `input`, given the
+/// current connections of `input`.
+fn accepts_connection<NodeData, DataType, ValueType, ConnectionData>(
+    graph: &Graph<NodeData, DataType, ValueType, ConnectionData>,
+    settings: &EditorSettings,
+    output: OutputId,
+    input: InputId,
+) -> bool {
+    let previous = graph.connection_ids(input);
+    if previous.iter().any(|id| graph[*id].output == output) {
+        settings.allow_parallel_connections
+    } else {
+        previous.is_empty() || settings.occupied_input != OccupiedInputPolicy::Reject
+    }
+}
+
+/// Iterates over the connections of `graph`, along with the lane of each:
+/// parallel connections are numbered around zero, and single connections
+/// are in lane zero.
+fn connection_lanes<NodeData, DataType, ValueType, ConnectionData>(
+    graph: &Graph<NodeData, DataType, ValueType, ConnectionData>,
+) -> impl Iterator<Item = (ConnectionId, InputId, OutputId, f32)> + '_ {
+    graph
+        .input_connections
+        .iter()
+        .flat_map(move |(input, ids)| {
+            ids.iter().map(move |&id| {
+                let output = graph[id].output;
+                let mut parallel = ids.iter().filter(|other| graph[**other].output == output);
+                let idx = parallel.clone().position(|other| *other == id).unwrap_or(0);
+                let middle = parallel.by_ref().count().saturating_sub(1) as f32 / 2.0;
+                (id, input, output, idx as f32 - middle)
+            })
+        })
+}
 
+/// Pairs each of the `old` parameters with one of the `new` parameters. The
+/// names in `mapping` are paired first. The others are paired with a
+/// parameter of a compatible type, preferring the ones with the same name.
+/// Each new parameter is used at most once.
+fn match_params<Id: Copy + PartialEq>(
+    old: &[(String, Id)],
+    new: &[(String, Id)],
+    mapping: &HashMap<String, String>,
+    compatible: impl Fn(Id, Id) -> bool,
+) -> Vec<(Id, Id)> {
+    let mut used = vec![false; new.len()];
+    let mut pairs: Vec<(Id, Id)> = Vec::new();
+    for (old_name, old_id) in old {
+        let found = mapping.get(old_name).and_then(|new_name| {
+            new.iter()
+                .enumerate()
+                .position(|(idx, (name, _))| !used[idx] && name == new_name)
+        });
+        if let Some(idx) = found {
+            used[idx] = true;
+            pairs.push((*old_id, new[idx].1));
+        }
+    }
+    for same_name in [true, false] {
+        for (old_name, old_id) in old {
+            if pairs.iter().any(|(paired, _)| paired == old_id) {
+                continue;
+            }
+            let found = new.iter().enumerate().position(|(idx, (name, id))| {
+                !used[idx] && (!same_name || name == old_name) && compatible(*old_id, *id)
+            });
+            if let Some(idx) = found {
+                used[idx] = true;
+                pairs.push((*old_id, new[idx].1));
+            }
+        }
+    }
+    pairs
+}
+
+/// Restricts the `delta` applied to the given rects so they stay inside the
+/// `bounds`, as long as they were already inside.
+fn clamp_delta_to_bounds(delta: Vec2, bounds: Rect, rects: impl Iterator<Item = Rect>) -> Vec2 {
+    let mut min = Vec2::splat(f32::NEG_INFINITY);
+    let mut max = Vec2::splat(f32::INFINITY);
+    for rect in rects {
+        min = min.max((bounds.min - rect.min).min(Vec2::ZERO));
+        max = max.min((bounds.max - rect.max).max(Vec2::ZERO));
+    }
+    delta.max(min).min(max)
+}
+
+/// Restricts the `pan` so the visible part of the graph stays inside the
+/// `bounds`. When the bounds are smaller than the editor along an axis, they
+/// are centered along it instead.
+fn clamp_pan_to_bounds(pan: Vec2, editor_size: Vec2, bounds: Rect) -> Vec2 {
+    let clamp_axis = |pan: f32, size: f32, min: f32, max: f32| {
+        if max - min >= size {
+            pan.clamp(size - max, -min)
+        } else {
+            size / 2.0 - (min + max) / 2.0
+        }
+    };
+    vec2(
+        clamp_axis(pan.x, editor_size.x, bounds.min.x, bounds.max.x),
+        clamp_axis(pan.y, editor_size.y, bounds.min.y, bounds.max.y),
+    )
+}
+
+/// Returns the first port of `node_id` that can be connected to `source`,
+/// which has the data type `source_type`. This is an input when `source` is an
+/// output, and vice versa.
+fn first_compatible_port<NodeData, DataType: PartialEq, ValueType, ConnectionData>(
+    graph: &Graph<NodeData, DataType, ValueType, ConnectionData>,
+    node_id: NodeId,
+    source: AnyParameterId,
+    source_type: &DataType,
+) -> Option<AnyParameterId> {
+    let node = &graph[node_id];
+    match source {
+        AnyParameterId::Output(_) => node
+            .inputs(graph)
+            .find(|input| {
+                input.typ == *source_type && !matches!(input.kind, InputParamKind::ConstantOnly)
+            })
+            .map(|input| AnyParameterId::Input(input.id)),
+        AnyParameterId::Input(_) => node
+            .outputs(graph)
+            .find(|output| output.typ == *source_type)
+            .map(|output| AnyParameterId::Output(output.id)),
+    }
+}
+
+/// Returns the port closest to `pos` where the connection being dragged from
+/// `source` can be dropped, if any, along with the connection it would make.
+/// Ports accept drops within `hit_radius` of their center, measured as a
+/// square.
+fn port_to_connect<NodeData>(
+    graph: &Graph<NodeData, NodeData::DataType, NodeData::ValueType, NodeData::ConnectionData>,
+    source: (NodeId, AnyParameterId),
+    port_locations: &PortLocations,
+    pos: Pos2,
+    hit_radius: f32,
+    settings: &EditorSettings,
+    user_state: &mut NodeData::UserState,
+) -> Option<(AnyParameterId, OutputId, InputId)>
+where
+    NodeData: NodeDataTrait,
+    NodeData::DataType: PartialEq,
+{
+    compatible_ports(graph, source, port_locations, settings, user_state)
+        .into_iter()
+        .filter(|(_, _, _, port_pos)| {
+            Rect::from_center_size(*port_pos, Vec2::splat(2.0 * hit_radius)).contains(pos)
+        })
+        .min_by(|(.., a), (.., b)| a.distance(pos).total_cmp(&b.distance(pos)))
+        .map(|(port, output, input, _)| (port, output, input))
+}
+
+/// Returns the ports where the connection being dragged from `source` can be
+/// dropped, along with the connection each would make and its location.
+/// These are the ports with the same data type, when the `settings` and
+/// [`NodeDataTrait::can_connect`] allow the connection.
+fn compatible_ports<NodeData>(
+    graph: &Graph<NodeData, NodeData::DataType, NodeData::ValueType, NodeData::ConnectionData>,
+    (source_node, source): (NodeId, AnyParameterId),
+    port_locations: &PortLocations,
+    settings: &EditorSettings,
+    user_state: &mut NodeData::UserState,
+) -> Vec<(AnyParameterId, OutputId, InputId, Pos2)>
+where
+    NodeData: NodeDataTrait,
+    NodeData::DataType: PartialEq,
+{
+    let source_type = match graph.any_param_type(source) {
+        Ok(source_type) => source_type,
+        Err(_) => return Vec::new(),
+    };
+    let candidates: Vec<_> = port_locations
+        .iter()
+        .filter_map(|(port, port_pos)| {
+            let (output, input) = match (source, *port) {
+                (AnyParameterId::Output(output), AnyParameterId::Input(input))
+                | (AnyParameterId::Input(input), AnyParameterId::Output(output)) => (output, input),
+                // Bidirectional ports can also receive from outputs. Other
+                // bidirectional ports are reached through their input.
+                (AnyParameterId::Output(source), AnyParameterId::Output(output)) => {
+                    let input = graph.outputs.get(source)?.paired_input?;
+                    if graph.outputs.get(output)?.paired_input.is_some() {
+                        return None;
+                    }
+                    (output, input)
+                }
+                _ => return None,
+            };
+            let node = match *port {
+                AnyParameterId::Input(input) => graph.inputs.get(input)?.node,
+                AnyParameterId::Output(output) => graph.outputs.get(output)?.node,
+            };
+            let typ = graph.any_param_type(*port).ok()?;
+            let allowed_node = node != source_node || settings.allow_self_connections;
+            (allowed_node && typ == source_type).then_some((*port, output, input, *port_pos))
+        })
+        .collect();
+
+    candidates
+        .into_iter()
+        .filter(|(_, output, input, _)| accepts_connection(graph, settings, *output, *input))
+        .filter(|(_, output, input, _)| {
+            let node_id = graph[*input].node;
+            graph[node_id]
+                .user_data
+                .can_connect(node_id, *output, *input, graph, user_state)
+        })
+        .collect()
+}
+
+/// Adds a single line label with the given text style, truncated with an
+/// ellipsis to fit in `max_width`. When truncated, the full text is shown in a
+/// tooltip on hover.
+pub fn truncated_label(ui: &mut Ui, text: &str, text_style: TextStyle, max_width: f32) -> Response {
+    let font_id = text_style.resolve(ui.style());
+    let width_of = |text: String| {
+        ui.fonts(|fonts| {
+            fonts
+                .layout_no_wrap(text, font_id.clone(), Color32::TEMPORARY_COLOR)
+                .size()
+                .x
+        })
+    };
+
+    if width_of(text.to_owned()) <= max_width {
+        return ui.add(Label::new(RichText::new(text).text_style(text_style)).wrap(false));
+    }
+
+    // Find the longest prefix that fits next to the ellipsis
+    let chars: Vec<char> = text.chars().collect();
+    let truncated = |len: usize| chars[..len].iter().collect::<String>() + "…";
+    let (mut fits, mut doesnt_fit) = (0, chars.len());
+    while doesnt_fit - fits > 1 {
+        let mid = (fits + doesnt_fit) / 2;
+        if width_of(truncated(mid)) <= max_width {
+            fits = mid;
+        } else {
+            doesnt_fit = mid;
+        }
+    }
+
+    ui.add(Label::new(RichText::new(truncated(fits)).text_style(text_style)).wrap(false))
+        .on_hover_text(text)
+}
+
+/// Returns the direction in which connections pass through the port of
+/// `param`: away from its edge for outputs, and towards it for inputs.
+fn port_direction<NodeData, DataType, ValueType, ConnectionData>(
+    graph: &Graph<NodeData, DataType, ValueType, ConnectionData>,
+    param: AnyParameterId,
+    direction: LayoutDirection,
+) -> Vec2 {
+    let side = graph.any_param_side(param).unwrap_or(match param {
+        AnyParameterId::Input(_) => PortSide::Left,
+        AnyParameterId::Output(_) => PortSide::Right,
+    });
+    let outwards = match direction.port_side(side) {
+        PortSide::Left => -Vec2::X,
+        PortSide::Right => Vec2::X,
+        PortSide::Top => -Vec2::Y,
+        PortSide::Bottom => Vec2::Y,
+    };
+    match param {
+        AnyParameterId::Output(_) => outwards,
+        AnyParameterId::Input(_) => -outwards,
+    }
+}
+
+/// Returns the control points of the bezier curve used to draw a connection
+/// that leaves `src_pos` in the direction `src_dir`, and arrives at `dst_pos`
+/// in the direction `dst_dir`, see [`ConnectionStyle::curvature`].
+fn connection_bezier_points(
+    (src_pos, src_dir): (Pos2, Vec2),
+    (dst_pos, dst_dir): (Pos2, Vec2),
+    curvature: f32,
+) -> [Pos2; 4] {
+    // Ports facing away from each other still get a visible curve
+    const MIN_DISTANCE: f32 = 60.0;
+    let delta = dst_pos - src_pos;
+    let src_control = src_pos + src_dir * delta.dot(src_dir).max(MIN_DISTANCE) * curvature;
+    let dst_control = dst_pos - dst_dir * delta.dot(dst_dir).max(MIN_DISTANCE) * curvature;
+    [src_pos, src_control, dst_control, dst_pos]
+}
+
+/// Returns the point halfway along the curve of a connection.
+fn connection_midpoint(src: (Pos2, Vec2), dst: (Pos2, Vec2), curvature: f32) -> Pos2 {
+    CubicBezierShape::from_points_stroke(
+        connection_bezier_points(src, dst, curvature),
+        false,
+        Color32::TRANSPARENT,
+        Stroke::NONE,
+    )
+    .sample(0.5)
+}
+
+/// Draws the weight of a connection as a label centered at `center`, and
+/// returns the rect of the label.
+fn draw_weight_label(ui: &Ui, center: Pos2, weight: f32, style: &EditorStyle, zoom: f32) -> Rect {
+    let mut font_id = style.connection_weight_text_style.resolve(ui.style());
+    font_id.size *= zoom;
+    let galley =
+        ui.painter()
+            .layout_no_wrap(format!("{weight:.2}"), font_id, ui.visuals().text_color());
+    let rect = Rect::from_center_size(center, galley.size()).expand2(vec2(4.0, 1.0) * zoom);
+    ui.painter().rect(
+        rect,
+        2.0 * zoom,
+        ui.visuals().extreme_bg_color,
+        ui.visuals().widgets.noninteractive.bg_stroke,
+    );
+    ui.painter()
+        .galley(rect.shrink2(vec2(4.0, 1.0) * zoom).min, galley);
+    rect
+}
+
+/// Draws the round button with a cross that removes the selected connection.
+fn draw_delete_button(ui: &Ui, center: Pos2, radius: f32) {
+    let visuals = ui.visuals();
+    ui.painter().circle(
+        center,
+        radius,
+        visuals.extreme_bg_color,
+        visuals.widgets.noninteractive.bg_stroke,
+    );
+    let arm = radius * 0.4;
+    let stroke = Stroke::new(radius * 0.2, visuals.text_color());
+    for dir in [vec2(arm, arm), vec2(arm, -arm)] {
+        ui.painter()
+            .line_segment([center - dir, center + dir], stroke);
+    }
+}
+
+/// Returns the distance from `pos` to the curve of a connection.
+fn connection_distance(src: (Pos2, Vec2), dst: (Pos2, Vec2), curvature: f32, pos: Pos2) -> f32 {
     let bezier = CubicBezierShape::from_points_stroke(
-        [src_pos, src_control, dst_control, dst_pos],
+        connection_bezier_points(src, dst, curvature),
         false,
         Color32::TRANSPARENT,
-        connection_stroke,
+        Stroke::NONE,
     );
+    bezier
+        .flatten(Some(1.0))
+        .windows(2)
+        .map(|segment| {
+            let (a, b) = (segment[0], segment[1]);
+            let t = ((pos - a).dot(b - a) / (b - a).length_sq().max(f32::EPSILON)).clamp(0.0, 1.0);
+            pos.distance(a + (b - a) * t)
+        })
+        .fold(f32::INFINITY, f32::min)
+}
+
+/// Draws a connection as a bezier curve between two ports, with the given
+/// color. This is the default implementation of
+/// [`DataTypeTrait::draw_connection`].
+pub fn draw_connection(painter: &Painter, info: &ConnectionDrawInfo, color: Color32) {
+    let color = color.linear_multiply(info.opacity);
+    let points = connection_bezier_points(
+        (info.src_pos, info.src_dir),
+        (info.dst_pos, info.dst_dir),
+        info.curvature,
+    );
+
+    if info.feathering > 0.0 {
+        let feathering_stroke = Stroke::new(
+            info.width + 2.0 * info.feathering,
+            color.linear_multiply(0.3),
+        );
+        painter.add(CubicBezierShape::from_points_stroke(
+            points,
+            false,
+            Color32::TRANSPARENT,
+            feathering_stroke,
+        ));
+    }
+
+    let connection_stroke = Stroke::new(info.width, color);
+    painter.add(CubicBezierShape::from_points_stroke(
+        points,
+        false,
+        Color32::TRANSPARENT,
+        connection_stroke,
+    ));
+}
+
+#[allow(clippy::too_many_arguments)]
+fn draw_port<NodeData, DataType, ValueType, UserResponse, UserState, ConnectionData>(
+    ui: &mut Ui,
+    graph: &Graph<NodeData, DataType, ValueType, ConnectionData>,
+    node_id: NodeId,
+    user_state: &mut UserState,
+    port_pos: Pos2,
+    responses: &mut Vec<NodeResponse<UserResponse, NodeData>>,
+    param_id: AnyParameterId,
+    port_locations: &mut PortLocations,
+    output_connections: &OutputConnectionCounts,
+    ongoing_drag: Option<(NodeId, AnyParameterId)>,
+    is_connected_input: bool,
+    style: &EditorStyle,
+    strings: &EditorStrings,
+    highlighted: bool,
+    opacity: f32,
+    zoom: f32,
+) where
+    DataType: DataTypeTrait<UserState>,
+    UserResponse: UserResponseTrait,
+    NodeData: NodeDataTrait,
+{
+    let port_type = match graph.any_param_type(param_id) {
+        Ok(port_type) => port_type,
+        Err(_) => return,
+    };
+
+    let port_rect =
+        Rect::from_center_size(port_pos, Vec2::splat(2.0 * style.port_hit_radius * zoom));
+
+    let sense = if ongoing_drag.is_some() {
+        Sense::hover()
+    } else {
+        Sense::click_and_drag()
+    };
+
+    let resp = ui.allocate_rect(port_rect, sense);
+
+    // Check if the mouse is close enough to drop a connection on the port
+    let close_enough = if let Some(pointer_pos) = ui.ctx().pointer_hover_pos() {
+        port_rect
+            .expand(style.port_drop_padding * zoom)
+            .contains(pointer_pos)
+    } else {
+        false
+    };
+
+    // Bidirectional ports are drawn once, for their output
+    let paired_input = match param_id {
+        AnyParameterId::Input(_) => None,
+        AnyParameterId::Output(output) => match graph.try_get_output(output) {
+            Some(output) => output.paired_input,
+            None => return,
+        },
+    };
+    let connection_count = match param_id {
+        AnyParameterId::Input(input) => graph.connection_count(input),
+        AnyParameterId::Output(output) => {
+            output_connections.get(&output).copied().unwrap_or_default()
+        }
+    } + paired_input.map_or(0, |input| graph.connection_count(input));
+    let connected = connection_count > 0;
+    let info = PortDrawInfo {
+        param_id,
+        center: port_rect.center(),
+        hovered: close_enough,
+        connected,
+        highlighted,
+        bidirectional: paired_input.is_some(),
+        opacity,
+        radius: style.port_radius * zoom,
+        zoom,
+    };
+    port_type.draw_port(ui.painter(), &info, user_state);
+
+    let resp = if connection_count > 1 {
+        if let Some(color) = style.connection_count_badge {
+            let mut font_id = TextStyle::Small.resolve(ui.style());
+            font_id.size *= zoom;
+            let galley = ui.painter().layout_no_wrap(
+                connection_count.to_string(),
+                font_id,
+                Color32::WHITE.linear_multiply(opacity),
+            );
+            let center = port_rect.center() + vec2(1.0, -1.0) * 1.5 * info.radius;
+            let radius = 0.5 * galley.size().max_elem() + 1.5 * zoom;
+            ui.painter()
+                .circle_filled(center, radius, color.linear_multiply(opacity));
+            ui.painter().galley(center - 0.5 * galley.size(), galley);
+        }
+        let count = connection_count.to_string();
+        resp.on_hover_text(str::replace(&strings.port_connections, "{count}", &count))
+    } else {
+        resp
+    };
+
+    if resp.drag_started() {
+        // Dragging a connected input picks up its last connection
+        let connection = match param_id {
+            AnyParameterId::Input(input) if is_connected_input => {
+                graph.connection_ids(input).last().copied()
+            }
+            _ => None,
+        };
+        if let Some(id) = connection {
+            responses.push(NodeResponse::DisconnectEvent {
+                input: param_id.assume_input(),
+                output: graph[id].output,
+                connection: id,
+            });
+        } else {
+            responses.push(NodeResponse::ConnectEventStarted(node_id, param_id));
+        }
+    }
 
-    painter.add(bezier);
+    port_locations.insert(param_id, port_rect.center());
+    if let Some(input) = paired_input {
+        port_locations.insert(AnyParameterId::Input(input), port_rect.center());
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
 struct OuterRectMemory(Rect);
 
-impl<'a, NodeData, DataType, ValueType, UserResponse, UserState>
-    GraphNodeWidget<'a, NodeData, DataType, ValueType>
+/// The label being edited in place, for a node being renamed.
+#[derive(Clone, Debug)]
+struct RenameMemory {
+    label: String,
+    just_started: bool,
+}
+
+/// Measurements of a node, collected while drawing it and shared between the
+/// stages of a [`NodeWidget`].
+#[derive(Clone, Debug)]
+pub struct NodeLayout {
+    /// The rect containing the node contents, including the margins.
+    pub outer_rect: Rect,
+    /// The height of the titlebar.
+    pub titlebar_height: f32,
+    /// The vertical position of the port of each input drawn in the node.
+    pub input_port_heights: Vec<(InputId, f32)>,
+    /// The vertical position of the port of each output drawn in the node.
+    pub output_port_heights: Vec<(OutputId, f32)>,
+}
+
+/// Returns the node background and text colors, from the style when they
+/// are set and from the visuals otherwise. The visuals of disabled nodes are
+/// already faded, so `opacity` only applies to the colors of the style.
+fn node_colors(visuals: &Visuals, style: &EditorStyle, opacity: f32) -> (Color32, Color32) {
+    let background = match style.node_background {
+        Some(color) => color.linear_multiply(opacity),
+        None => visuals.window_fill(),
+    };
+    let text = match style.node_text_color {
+        Some(color) => color.linear_multiply(opacity),
+        None => visuals.text_color(),
+    };
+    (background, text)
+}
+
+impl<'a, NodeData, DataType, ValueType, UserResponse, UserState, ConnectionData>
+    GraphNodeWidget<'a, NodeData, DataType, ValueType, ConnectionData>
 where
     NodeData: NodeDataTrait<
         Response = UserResponse,
         UserState = UserState,
         DataType = DataType,
         ValueType = ValueType,
+        ConnectionData = ConnectionData,
     >,
     UserResponse: UserResponseTrait,
     ValueType:
         WidgetValueTrait<Response = UserResponse, UserState = UserState, NodeData = NodeData>,
     DataType: DataTypeTrait<UserState>,
 {
-    pub const MAX_NODE_SIZE: [f32; 2] = [200.0, 200.0];
+    /// The default of [`NodeDataTrait::max_size`], 200 points wide and 400
+    /// points high. Nodes still grow taller to fit their contents; the height
+    /// only limits the widgets that fill the available height.
+    pub const MAX_NODE_SIZE: [f32; 2] = DEFAULT_MAX_NODE_SIZE;
+    pub const MIN_NODE_WIDTH: f32 = 50.0;
 
     pub fn show(
         self,
         ui: &mut Ui,
         user_state: &mut UserState,
     ) -> Vec<NodeResponse<UserResponse, NodeData>> {
+        self.show_with_widget(ui, user_state, &mut DefaultNodeWidget)
+    }
+
+    /// Like [`GraphNodeWidget::show`], but draws the node using the stages of
+    /// a custom [`NodeWidget`].
+    pub fn show_with_widget(
+        self,
+        ui: &mut Ui,
+        user_state: &mut UserState,
+        node_widget: &mut impl NodeWidget<NodeData>,
+    ) -> Vec<NodeResponse<UserResponse, NodeData>> {
+        let max_size =
+            self.graph[self.node_id]
+                .user_data
+                .max_size(self.node_id, self.graph, user_state);
         let mut child_ui = ui.child_ui_with_id_source(
-            Rect::from_min_size(*self.position + self.pan, Self::MAX_NODE_SIZE.into()),
-            Layout::default(),
+            Rect::from_min_size(
+                (self.position.to_vec2() * self.zoom + self.pan).to_pos2(),
+                vec2(self.width.unwrap_or(max_size.x), max_size.y) * self.zoom,
+            ),
+            self.layout_direction.vertical(),
             self.node_id,
         );
 
-        Self::show_graph_node(self, &mut child_ui, user_state)
+        if self.disabled {
+            fade_visuals(child_ui.visuals_mut(), self.style.disabled_opacity);
+        }
+
+        Self::show_graph_node(self, &mut child_ui, user_state, node_widget)
+    }
+
+    /// Draws this node. Also fills in the list of port locations with all of its ports.
+    /// Returns responses indicating multiple events.
+    fn show_graph_node(
+        mut self,
+        ui: &mut Ui,
+        user_state: &mut UserState,
+        node_widget: &mut impl NodeWidget<NodeData>,
+    ) -> Vec<NodeResponse<UserResponse, NodeData>> {
+        let margin = self.style.node_margin * self.zoom;
+        let mut responses = Vec::<NodeResponse<UserResponse, NodeData>>::new();
+
+        let (_, text_color) = node_colors(ui.visuals(), self.style, self.opacity());
+        ui.visuals_mut().widgets.noninteractive.fg_stroke =
+            Stroke::new(2.0 * self.zoom, text_color);
+        if self.style.node_text_color.is_some() {
+            ui.visuals_mut().override_text_color = Some(text_color);
+        }
+
+        let outer_rect_bounds = ui.available_rect_before_wrap();
+
+        let mut inner_rect = outer_rect_bounds.shrink2(margin);
+
+        // Make sure we don't shrink to the negative:
+        inner_rect.max.x = inner_rect.max.x.max(inner_rect.min.x);
+        inner_rect.max.y = inner_rect.max.y.max(inner_rect.min.y);
+
+        let min_size =
+            self.graph[self.node_id]
+                .user_data
+                .min_size(self.node_id, self.graph, user_state);
+        let mut child_ui = ui.child_ui(inner_rect, *ui.layout());
+        child_ui.set_min_size((min_size * self.zoom - 2.0 * margin).max(Vec2::ZERO));
+        if self.width.is_some() {
+            child_ui.set_min_width(inner_rect.width());
+        }
+
+        // Get interaction rect from memory, it may expand after the window response on resize.
+        // The bounds can be infinitely tall, so they're not used as a fallback.
+        let interaction_rect = ui
+            .ctx()
+            .memory_mut(|mem| {
+                mem.data
+                    .get_temp::<OuterRectMemory>(child_ui.id())
+                    .map(|stored| stored.0)
+            })
+            .unwrap_or_else(|| Rect::from_min_size(outer_rect_bounds.min, Vec2::ZERO));
+        // After 0.20, layers added over others can block hover interaction. Call this first
+        // before creating the node content.
+        let window_response = ui.interact(
+            interaction_rect,
+            Id::new((self.node_id, "window")),
+            Sense::click_and_drag(),
+        );
+
+        self.graph[self.node_id].user_data.before_draw(
+            ui.painter(),
+            interaction_rect,
+            self.node_id,
+            self.graph,
+            user_state,
+        );
+
+        // Preallocate the shape to paint below contents
+        let chrome_shape = ui.painter().add(Shape::Noop);
+
+        let mut layout = NodeLayout {
+            outer_rect: Rect::NOTHING,
+            titlebar_height: 0.0,
+            input_port_heights: Vec::new(),
+            output_port_heights: Vec::new(),
+        };
+
+        child_ui.vertical(|ui| {
+            node_widget.title_ui(&mut self, ui, user_state, &mut responses);
+            // The titlebar spans the contents and the margins above and below
+            let min_height = self.style.titlebar_min_height * self.zoom - 2.0 * margin.y;
+            ui.add_space((min_height - ui.min_size().y).max(0.0) + margin.y);
+            layout.titlebar_height = ui.min_size().y + margin.y;
+
+            node_widget.params_ui(&mut self, ui, user_state, &mut layout, &mut responses);
+        });
+
+        // Ports are drawn outside the child_ui because we want them to
+        // overflow the node background.
+        let outer_rect = child_ui.min_rect().expand2(margin);
+        layout.outer_rect = outer_rect;
+
+        // Save expanded rect to memory.
+        ui.ctx().memory_mut(|mem| {
+            mem.data
+                .insert_temp(child_ui.id(), OuterRectMemory(outer_rect))
+        });
+
+        // The resize handle is added before the ports, so they are on top of it.
+        if self.resizable {
+            let handle_rect = Rect::from_min_max(
+                pos2(outer_rect.right() - 3.0 * self.zoom, outer_rect.top()),
+                pos2(outer_rect.right() + 3.0 * self.zoom, outer_rect.bottom()),
+            );
+            let handle = ui.interact(
+                handle_rect,
+                Id::new((self.node_id, "resize")),
+                Sense::drag(),
+            );
+            if handle.hovered() || handle.dragged() {
+                ui.ctx().set_cursor_icon(CursorIcon::ResizeHorizontal);
+            }
+            if handle.dragged() && handle.drag_delta().x != 0.0 {
+                let width = (outer_rect.width() + handle.drag_delta().x) / self.zoom;
+                responses.push(NodeResponse::ResizeNode {
+                    node: self.node_id,
+                    width: width.max(Self::MIN_NODE_WIDTH).max(min_size.x),
+                });
+            }
+        }
+
+        node_widget.ports_ui(&mut self, ui, user_state, &layout, &mut responses);
+        if self.locked {
+            responses.retain(|response| {
+                !matches!(
+                    response,
+                    NodeResponse::ConnectEventStarted(..) | NodeResponse::DisconnectEvent { .. }
+                )
+            });
+        }
+
+        let background = node_widget.background(&mut self, ui, user_state, &layout);
+
+        // Take note of the node rect, so the editor can use it later to compute intersections.
+        let node_rect = background.visual_bounding_rect();
+        self.node_rects.insert(
+            self.node_id,
+            if node_rect.is_positive() {
+                node_rect
+            } else {
+                outer_rect
+            },
+        );
+
+        let mut chrome = Vec::with_capacity(2);
+        if let Some(shadow) = &self.style.node_shadow {
+            let shadow = NodeShadow {
+                color: shadow.color.linear_multiply(self.opacity()),
+                ..*shadow
+            };
+            chrome.push(shadow.shape(
+                self.node_rects[&self.node_id],
+                self.style.node_rounding * self.zoom,
+                self.zoom,
+                self.selected,
+            ));
+        }
+        chrome.push(background);
+        let chrome = CachedNodeShapes::tessellate(self.node_shapes, self.node_id, ui.ctx(), chrome);
+        ui.painter().set(chrome_shape, chrome);
+
+        // --- Interaction ---
+
+        // Titlebar buttons
+        let can_delete = !self.locked
+            && self.graph.nodes[self.node_id].user_data.can_delete(
+                self.node_id,
+                self.graph,
+                user_state,
+            );
+
+        if can_delete
+            && Self::close_button(ui, outer_rect, self.zoom, self.layout_direction).clicked()
+        {
+            responses.push(NodeResponse::DeleteNodeUi(self.node_id));
+        };
+
+        // Movement
+        if self.pinned {
+            Self::pin_indicator(ui, outer_rect, self.zoom, can_delete, self.layout_direction);
+        }
+
+        let drag_delta = window_response.drag_delta() / self.zoom;
+        if drag_delta.length_sq() > 0.0 && !self.pinned && !self.locked {
+            responses.push(NodeResponse::MoveNode {
+                node: self.node_id,
+                drag_delta,
+            });
+            responses.push(NodeResponse::RaiseNode(self.node_id));
+        }
+
+        // Node selection
+        //
+        // HACK: Only set the select response when no other response is active.
+        // This prevents some issues.
+        if responses.is_empty() && window_response.clicked_by(PointerButton::Primary) {
+            responses.push(NodeResponse::SelectNode(self.node_id));
+            responses.push(NodeResponse::RaiseNode(self.node_id));
+        }
+
+        self.graph[self.node_id].user_data.after_draw(
+            ui.painter(),
+            self.node_rects[&self.node_id],
+            self.node_id,
+            self.graph,
+            user_state,
+        );
+
+        responses
+    }
+
+    /// Draws the contents of the titlebar: the node label, followed by the
+    /// [`NodeDataTrait::top_bar_ui`]. This is the default implementation of
+    /// [`NodeWidget::title_ui`].
+    pub fn title_ui(
+        &mut self,
+        ui: &mut Ui,
+        user_state: &mut UserState,
+        responses: &mut Vec<NodeResponse<UserResponse, NodeData>>,
+    ) {
+        ui.with_layout(self.layout_direction.horizontal(), |ui| {
+            self.label_ui(ui, responses);
+            responses.extend(self.graph[self.node_id].user_data.top_bar_ui(
+                ui,
+                self.node_id,
+                self.graph,
+                user_state,
+            ));
+            ui.add_space(8.0 * self.zoom); // The size of the little cross icon
+        });
+    }
+
+    /// Draws the node label. Double clicking it starts editing the label in
+    /// place, with the text selected. Pressing enter or clicking elsewhere
+    /// emits a [`NodeResponse::RenameNode`], and escape cancels.
+    fn label_ui(&mut self, ui: &mut Ui, responses: &mut Vec<NodeResponse<UserResponse, NodeData>>) {
+        let rename_id = Id::new((self.node_id, "rename"));
+        let renaming = ui
+            .ctx()
+            .memory_mut(|mem| mem.data.get_temp::<RenameMemory>(rename_id));
+
+        let mut renaming = match renaming {
+            Some(renaming) => renaming,
+            None => {
+                let label = &self.graph[self.node_id].label;
+                // Leave room for the close button
+                let max_width = ui.available_width() - 8.0 * self.zoom;
+                let text_style = self.style.title_text_style.clone();
+                let resp = truncated_label(ui, label, text_style, max_width);
+                // NOTE: A clickable label would cover the node window and block
+                // dragging the node by its title. Check for a double click over
+                // the label instead.
+                if resp.hovered()
+                    && ui.input(|i| i.pointer.button_double_clicked(PointerButton::Primary))
+                {
+                    let memory = RenameMemory {
+                        label: label.clone(),
+                        just_started: true,
+                    };
+                    ui.ctx()
+                        .memory_mut(|mem| mem.data.insert_temp(rename_id, memory));
+                }
+                return;
+            }
+        };
+
+        let mut output = TextEdit::singleline(&mut renaming.label)
+            .font(self.style.title_text_style.clone())
+            .text_color(ui.visuals().text_color())
+            .desired_width(ui.available_width() - 8.0 * self.zoom)
+            .show(ui);
+        if renaming.just_started {
+            renaming.just_started = false;
+            output.response.request_focus();
+            let end = text::CCursor::new(renaming.label.chars().count());
+            output
+                .state
+                .set_ccursor_range(Some(text::CCursorRange::two(text::CCursor::new(0), end)));
+            output.state.store(ui.ctx(), output.response.id);
+        }
+
+        if output.response.lost_focus() {
+            ui.ctx()
+                .memory_mut(|mem| mem.data.remove::<RenameMemory>(rename_id));
+            let old_label = &self.graph[self.node_id].label;
+            if !ui.input(|i| i.key_pressed(Key::Escape)) && *old_label != renaming.label {
+                responses.push(NodeResponse::RenameNode {
+                    node: self.node_id,
+                    old_label: old_label.clone(),
+                    new_label: renaming.label,
+                });
+            }
+        } else {
+            ui.ctx()
+                .memory_mut(|mem| mem.data.insert_temp(rename_id, renaming));
+        }
     }
 
-    /// Draws this node. Also fills in the list of port locations with all of its ports.
-    /// Returns responses indicating multiple events.
-    fn show_graph_node(
-        self,
+    /// Draws the inline widgets of the inputs, the output labels, the
+    /// [`NodeDataTrait::preview_texture`] and the [`NodeDataTrait::bottom_ui`],
+    /// recording the port heights in `layout`. Grouped parameters come after
+    /// the others, under the heading of their [`ParamGroup`]. This is the
+    /// default implementation of [`NodeWidget::params_ui`].
+    pub fn params_ui(
+        &mut self,
         ui: &mut Ui,
         user_state: &mut UserState,
-    ) -> Vec<NodeResponse<UserResponse, NodeData>> {
-        let margin = egui::vec2(15.0, 5.0);
-        let mut responses = Vec::<NodeResponse<UserResponse, NodeData>>::new();
-
-        let background_color;
-        let text_color;
-        if ui.visuals().dark_mode {
-            background_color = color_from_hex("#3f3f3f").unwrap();
-            text_color = color_from_hex("#fefefe").unwrap();
-        } else {
-            background_color = color_from_hex("#ffffff").unwrap();
-            text_color = color_from_hex("#505050").unwrap();
+        layout: &mut NodeLayout,
+        responses: &mut Vec<NodeResponse<UserResponse, NodeData>>,
+    ) {
+        let node = &self.graph[self.node_id];
+        let inputs = node.inputs.clone();
+        let outputs = node.outputs.clone();
+        let groups = node.groups.clone();
+
+        for (param_name, param_id) in &inputs {
+            if !self.graph[self.node_id].is_grouped(AnyParameterId::Input(*param_id)) {
+                self.input_row(ui, user_state, layout, responses, param_name, *param_id);
+            }
+        }
+        for (param_name, param_id) in &outputs {
+            if !self.graph[self.node_id].is_grouped(AnyParameterId::Output(*param_id)) {
+                self.output_row(ui, user_state, layout, responses, param_name, *param_id);
+            }
         }
 
-        ui.visuals_mut().widgets.noninteractive.fg_stroke = Stroke::new(2.0, text_color);
-
-        // Preallocate shapes to paint below contents
-        let outline_shape = ui.painter().add(Shape::Noop);
-        let background_shape = ui.painter().add(Shape::Noop);
-
-        let outer_rect_bounds = ui.available_rect_before_wrap();
-
-        let mut inner_rect = outer_rect_bounds.shrink2(margin);
+        for (idx, group) in groups.into_iter().enumerate() {
+            ui.separator();
+            let icon = if group.collapsed { "⏵" } else { "⏷" };
+            let heading = ui.add(
+                Label::new(RichText::new(format!("{} {}", icon, group.name)).strong())
+                    .sense(Sense::click()),
+            );
+            if heading.clicked() {
+                responses.push(NodeResponse::CollapseParamGroup {
+                    node: self.node_id,
+                    group: idx,
+                    collapsed: !group.collapsed,
+                });
+            }
 
-        // Make sure we don't shrink to the negative:
-        inner_rect.max.x = inner_rect.max.x.max(inner_rect.min.x);
-        inner_rect.max.y = inner_rect.max.y.max(inner_rect.min.y);
+            if group.collapsed {
+                // Keep the connected ports visible, next to the heading
+                let height = heading.rect.center().y;
+                for param in group.params {
+                    match param {
+                        AnyParameterId::Input(input) => {
+                            if self.graph.connection(input).is_some() {
+                                layout.input_port_heights.push((input, height));
+                            }
+                        }
+                        AnyParameterId::Output(output) => {
+                            if self.output_connections.contains_key(&output) {
+                                layout.output_port_heights.push((output, height));
+                            }
+                        }
+                    }
+                }
+                continue;
+            }
 
-        let mut child_ui = ui.child_ui(inner_rect, *ui.layout());
+            for param in group.params {
+                match param {
+                    AnyParameterId::Input(input) => {
+                        if let Some((param_name, _)) = inputs.iter().find(|(_, id)| *id == input) {
+                            self.input_row(ui, user_state, layout, responses, param_name, input);
+                        }
+                    }
+                    AnyParameterId::Output(output) => {
+                        if let Some((param_name, _)) = outputs.iter().find(|(_, id)| *id == output)
+                        {
+                            self.output_row(ui, user_state, layout, responses, param_name, output);
+                        }
+                    }
+                }
+            }
+        }
 
-        // Get interaction rect from memory, it may expand after the window response on resize.
-        let interaction_rect = ui
-            .ctx()
-            .memory_mut(|mem| {
-                mem.data
-                    .get_temp::<OuterRectMemory>(child_ui.id())
-                    .map(|stored| stored.0)
-            })
-            .unwrap_or(outer_rect_bounds);
-        // After 0.20, layers added over others can block hover interaction. Call this first
-        // before creating the node content.
-        let window_response = ui.interact(
-            interaction_rect,
-            Id::new((self.node_id, "window")),
-            Sense::click_and_drag(),
+        let preview = self.graph[self.node_id].user_data.preview_texture(
+            self.node_id,
+            self.graph,
+            user_state,
         );
+        if let Some((texture, size)) = preview {
+            self.preview_ui(ui, texture, size);
+        }
 
-        let mut title_height = 0.0;
-
-        let mut input_port_heights = vec![];
-        let mut output_port_heights = vec![];
-
-        child_ui.vertical(|ui| {
-            ui.horizontal(|ui| {
-                ui.add(Label::new(
-                    RichText::new(&self.graph[self.node_id].label)
-                        .text_style(TextStyle::Button)
-                        .color(text_color),
-                ));
-                responses.extend(
-                    self.graph[self.node_id]
-                        .user_data
-                        .top_bar_ui(ui, self.node_id, self.graph, user_state)
-                        .into_iter(),
-                );
-                ui.add_space(8.0); // The size of the little cross icon
-            });
-            ui.add_space(margin.y);
-            title_height = ui.min_size().y;
-
-            // First pass: Draw the inner fields. Compute port heights
-            let inputs = self.graph[self.node_id].inputs.clone();
-            for (param_name, param_id) in inputs {
-                if self.graph[param_id].shown_inline {
-                    let height_before = ui.min_rect().bottom();
-                    // NOTE: We want to pass the `user_data` to
-                    // `value_widget`, but we can't since that would require
-                    // borrowing the graph twice. Here, we make the
-                    // assumption that the value is cheaply replaced, and
-                    // use `std::mem::take` to temporarily replace it with a
-                    // dummy value. This requires `ValueType` to implement
-                    // Default, but results in a totally safe alternative.
-                    let mut value = std::mem::take(&mut self.graph[param_id].value);
-
-                    if self.graph.connection(param_id).is_some() {
-                        let node_responses = value.value_widget_connected(
-                            &param_name,
-                            self.node_id,
-                            ui,
-                            user_state,
-                            &self.graph[self.node_id].user_data,
-                        );
-
-                        responses.extend(node_responses.into_iter().map(NodeResponse::User));
-                    } else {
-                        let node_responses = value.value_widget(
-                            &param_name,
-                            self.node_id,
-                            ui,
-                            user_state,
-                            &self.graph[self.node_id].user_data,
-                        );
-
-                        responses.extend(node_responses.into_iter().map(NodeResponse::User));
-                    }
+        responses.extend(self.graph[self.node_id].user_data.bottom_ui(
+            ui,
+            self.node_id,
+            self.graph,
+            user_state,
+        ));
+    }
 
-                    self.graph[self.node_id].user_data.separator(
-                        ui,
-                        self.node_id,
-                        AnyParameterId::Input(param_id),
-                        self.graph,
-                        user_state,
-                    );
+    /// Draws the preview image of the node, fitted in a square as wide as the
+    /// node. Clicking it toggles a window with a larger view.
+    fn preview_ui(&self, ui: &mut Ui, texture: TextureId, size: Vec2) {
+        const EXPANDED_SIZE: f32 = 512.0;
 
-                    self.graph[param_id].value = value;
+        if size.min_elem() <= 0.0 {
+            return;
+        }
+        let width = ui.available_width();
+        let response = ui
+            .add(Image::new(texture, size * (width / size.max_elem())).sense(Sense::click()))
+            .on_hover_cursor(CursorIcon::ZoomIn);
 
-                    let height_after = ui.min_rect().bottom();
-                    input_port_heights.push((height_before + height_after) / 2.0);
-                }
-            }
+        let expanded_id = Id::new((self.node_id, "preview_expanded"));
+        let mut expanded = ui
+            .ctx()
+            .data_mut(|data| data.get_temp::<bool>(expanded_id))
+            .unwrap_or(false);
+        if response.clicked() {
+            expanded = !expanded;
+        }
+        if expanded {
+            Window::new(&self.graph[self.node_id].label)
+                .id(expanded_id)
+                .open(&mut expanded)
+                .collapsible(false)
+                .resizable(false)
+                .show(ui.ctx(), |ui| {
+                    ui.image(texture, size * (EXPANDED_SIZE / size.max_elem()));
+                });
+        }
+        ui.ctx()
+            .data_mut(|data| data.insert_temp(expanded_id, expanded));
+    }
 
-            let outputs = self.graph[self.node_id].outputs.clone();
-            for (param_name, param_id) in outputs {
-                let height_before = ui.min_rect().bottom();
-                responses.extend(
-                    self.graph[self.node_id]
-                        .user_data
-                        .output_ui(ui, self.node_id, self.graph, user_state, &param_name)
-                        .into_iter(),
+    /// Draws the inline widget of an input, if shown, as part of
+    /// [`Self::params_ui`].
+    fn input_row(
+        &mut self,
+        ui: &mut Ui,
+        user_state: &mut UserState,
+        layout: &mut NodeLayout,
+        responses: &mut Vec<NodeResponse<UserResponse, NodeData>>,
+        param_name: &str,
+        param_id: InputId,
+    ) {
+        if self.graph[param_id].shown_inline {
+            let height_before = ui.min_rect().bottom();
+            let text_style = ui
+                .style_mut()
+                .override_text_style
+                .replace(self.style.input_text_style.clone());
+            // NOTE: We want to pass the `user_data` to
+            // `value_widget`, but we can't since that would require
+            // borrowing the graph twice. Here, we make the
+            // assumption that the value is cheaply replaced, and
+            // use `std::mem::take` to temporarily replace it with a
+            // dummy value. This requires `ValueType` to implement
+            // Default, but results in a totally safe alternative.
+            let mut value = std::mem::take(&mut self.graph[param_id].value);
+
+            if self.graph.connection(param_id).is_some() {
+                let node_responses = value.value_widget_connected(
+                    param_name,
+                    self.node_id,
+                    ui,
+                    user_state,
+                    &self.graph[self.node_id].user_data,
                 );
 
-                self.graph[self.node_id].user_data.separator(
-                    ui,
+                responses.extend(node_responses.into_iter().map(NodeResponse::User));
+            } else {
+                let node_responses = value.value_widget(
+                    param_name,
                     self.node_id,
-                    AnyParameterId::Output(param_id),
-                    self.graph,
+                    ui,
                     user_state,
+                    &self.graph[self.node_id].user_data,
                 );
 
-                let height_after = ui.min_rect().bottom();
-                output_port_heights.push((height_before + height_after) / 2.0);
+                responses.extend(node_responses.into_iter().map(NodeResponse::User));
             }
+            ui.style_mut().override_text_style = text_style;
 
-            responses.extend(
-                self.graph[self.node_id]
-                    .user_data
-                    .bottom_ui(ui, self.node_id, self.graph, user_state)
-                    .into_iter(),
+            self.graph[self.node_id].user_data.separator(
+                ui,
+                self.node_id,
+                AnyParameterId::Input(param_id),
+                self.graph,
+                user_state,
             );
-        });
-
-        // Second pass, iterate again to draw the ports. This happens outside
-        // the child_ui because we want ports to overflow the node background.
-
-        let outer_rect = child_ui.min_rect().expand2(margin);
-        let port_left = outer_rect.left();
-        let port_right = outer_rect.right();
-
-        // Save expanded rect to memory.
-        ui.ctx().memory_mut(|mem| {
-            mem.data
-                .insert_temp(child_ui.id(), OuterRectMemory(outer_rect))
-        });
-
-        #[allow(clippy::too_many_arguments)]
-        fn draw_port<NodeData, DataType, ValueType, UserResponse, UserState>(
-            ui: &mut Ui,
-            graph: &Graph<NodeData, DataType, ValueType>,
-            node_id: NodeId,
-            user_state: &mut UserState,
-            port_pos: Pos2,
-            responses: &mut Vec<NodeResponse<UserResponse, NodeData>>,
-            param_id: AnyParameterId,
-            port_locations: &mut PortLocations,
-            ongoing_drag: Option<(NodeId, AnyParameterId)>,
-            is_connected_input: bool,
-        ) where
-            DataType: DataTypeTrait<UserState>,
-            UserResponse: UserResponseTrait,
-            NodeData: NodeDataTrait,
-        {
-            let port_type = graph.any_param_type(param_id).unwrap();
-
-            let port_rect = Rect::from_center_size(port_pos, egui::vec2(10.0, 10.0));
-
-            let sense = if ongoing_drag.is_some() {
-                Sense::hover()
-            } else {
-                Sense::click_and_drag()
-            };
 
-            let resp = ui.allocate_rect(port_rect, sense);
+            self.graph[param_id].value = value;
 
-            // Check if the distance between the port and the mouse is the distance to connect
-            let close_enough = if let Some(pointer_pos) = ui.ctx().pointer_hover_pos() {
-                port_rect.center().distance(pointer_pos) < DISTANCE_TO_CONNECT
-            } else {
-                false
-            };
+            let height_after = ui.min_rect().bottom();
+            layout
+                .input_port_heights
+                .push((param_id, (height_before + height_after) / 2.0));
+        }
+    }
 
-            let port_color = if close_enough {
-                Color32::WHITE
-            } else {
-                port_type.data_type_color(user_state)
-            };
-            ui.painter()
-                .circle(port_rect.center(), 5.0, port_color, Stroke::NONE);
-
-            if resp.drag_started() {
-                if is_connected_input {
-                    let input = param_id.assume_input();
-                    let corresp_output = graph
-                        .connection(input)
-                        .expect("Connection data should be valid");
-                    responses.push(NodeResponse::DisconnectEvent {
-                        input: param_id.assume_input(),
-                        output: corresp_output,
+    /// Draws the label of an output as part of [`Self::params_ui`].
+    fn output_row(
+        &mut self,
+        ui: &mut Ui,
+        user_state: &mut UserState,
+        layout: &mut NodeLayout,
+        responses: &mut Vec<NodeResponse<UserResponse, NodeData>>,
+        param_name: &str,
+        param_id: OutputId,
+    ) {
+        // Bidirectional ports are shown in the row of their input
+        if self.graph[param_id].paired_input.is_some() {
+            return;
+        }
+        let height_before = ui.min_rect().bottom();
+        let text_style = ui
+            .style_mut()
+            .override_text_style
+            .replace(self.style.output_text_style.clone());
+        let value = self.graph[self.node_id].user_data.output_value(
+            self.node_id,
+            param_id,
+            self.graph,
+            user_state,
+        );
+        match value {
+            Some(value) => {
+                ui.with_layout(self.layout_direction.horizontal(), |ui| {
+                    // Leave room for the value after the label
+                    let value = RichText::new(value).monospace().weak();
+                    let value_width = WidgetText::from(value.clone())
+                        .into_galley(ui, Some(false), f32::INFINITY, TextStyle::Monospace)
+                        .size()
+                        .x;
+                    let label_width =
+                        ui.available_width() - value_width - ui.spacing().item_spacing.x;
+                    let label_size = vec2(label_width.max(0.0), ui.spacing().interact_size.y);
+                    ui.allocate_ui(label_size, |ui| {
+                        responses.extend(self.graph[self.node_id].user_data.output_ui(
+                            ui,
+                            self.node_id,
+                            self.graph,
+                            user_state,
+                            param_name,
+                        ));
                     });
-                } else {
-                    responses.push(NodeResponse::ConnectEventStarted(node_id, param_id));
-                }
+                    ui.label(value);
+                });
             }
-
-            if let Some((origin_node, origin_param)) = ongoing_drag {
-                if origin_node != node_id {
-                    // Don't allow self-loops
-                    if graph.any_param_type(origin_param).unwrap() == port_type
-                        && close_enough
-                        && ui.input(|i| i.pointer.any_released())
-                    {
-                        match (param_id, origin_param) {
-                            (AnyParameterId::Input(input), AnyParameterId::Output(output))
-                            | (AnyParameterId::Output(output), AnyParameterId::Input(input)) => {
-                                responses.push(NodeResponse::ConnectEventEnded { input, output });
-                            }
-                            _ => { /* Ignore in-in or out-out connections */ }
-                        }
-                    }
-                }
+            None => {
+                responses.extend(self.graph[self.node_id].user_data.output_ui(
+                    ui,
+                    self.node_id,
+                    self.graph,
+                    user_state,
+                    param_name,
+                ));
             }
-
-            port_locations.insert(param_id, port_rect.center());
         }
+        ui.style_mut().override_text_style = text_style;
 
-        // Input ports
-        for ((_, param), port_height) in self.graph[self.node_id]
-            .inputs
+        self.graph[self.node_id].user_data.separator(
+            ui,
+            self.node_id,
+            AnyParameterId::Output(param_id),
+            self.graph,
+            user_state,
+        );
+
+        let height_after = ui.min_rect().bottom();
+        layout
+            .output_port_heights
+            .push((param_id, (height_before + height_after) / 2.0));
+    }
+
+    /// Draws the ports on the left and right edges of the node, and handles
+    /// the interaction with them. This is the default implementation of
+    /// [`NodeWidget::ports_ui`].
+    pub fn ports_ui(
+        &mut self,
+        ui: &mut Ui,
+        user_state: &mut UserState,
+        layout: &NodeLayout,
+        responses: &mut Vec<NodeResponse<UserResponse, NodeData>>,
+    ) {
+        // Ports are placed next to their row on the left and right edges, and
+        // spread evenly along the top and bottom edges.
+        let inputs = layout
+            .input_port_heights
             .iter()
-            .zip(input_port_heights.into_iter())
-        {
-            let should_draw = match self.graph[*param].kind() {
+            .copied()
+            .filter(|(param, _)| match self.graph[*param].kind() {
                 InputParamKind::ConnectionOnly => true,
                 InputParamKind::ConstantOnly => false,
                 InputParamKind::ConnectionOrConstant => true,
-            };
-
-            if should_draw {
-                let pos_left = pos2(port_left, port_height);
-                draw_port(
-                    ui,
-                    self.graph,
-                    self.node_id,
-                    user_state,
-                    pos_left,
-                    &mut responses,
-                    AnyParameterId::Input(*param),
-                    self.port_locations,
-                    self.ongoing_drag,
-                    self.graph.connection(*param).is_some(),
-                );
-            }
-        }
-
-        // Output ports
-        for ((_, param), port_height) in self.graph[self.node_id]
-            .outputs
+            })
+            .map(|(param, height)| match self.graph[param].paired_output {
+                Some(output) => (AnyParameterId::Output(output), height),
+                None => (AnyParameterId::Input(param), height),
+            });
+        let outputs = layout
+            .output_port_heights
             .iter()
-            .zip(output_port_heights.into_iter())
-        {
-            let pos_right = pos2(port_right, port_height);
+            .copied()
+            .map(|(param, height)| (AnyParameterId::Output(param), height));
+        let ports: Vec<_> = inputs
+            .chain(outputs)
+            .filter_map(|(param, height)| {
+                // Ports whose param or paired input is gone aren't drawn
+                let side = self.graph.any_param_side(param).ok()?;
+                Some((param, self.layout_direction.port_side(side), height))
+            })
+            .collect();
+
+        let rect = layout.outer_rect;
+        let edge_ports = |side: PortSide| {
+            let count = ports.iter().filter(|(_, s, _)| *s == side).count();
+            (0..count)
+                .map(move |idx| rect.left() + rect.width() * (idx + 1) as f32 / (count + 1) as f32)
+        };
+        let mut top_ports = edge_ports(PortSide::Top);
+        let mut bottom_ports = edge_ports(PortSide::Bottom);
+
+        for (param, side, height) in ports.iter().copied() {
+            let pos = match side {
+                PortSide::Left => pos2(rect.left(), height),
+                PortSide::Right => pos2(rect.right(), height),
+                PortSide::Top => pos2(top_ports.next().unwrap(), rect.top()),
+                PortSide::Bottom => pos2(bottom_ports.next().unwrap(), rect.bottom()),
+            };
+            let (connected, highlighted) = match param {
+                AnyParameterId::Input(input) => (
+                    self.graph.connection(input).is_some(),
+                    matches!(self.hovered_connection, Some((_, hovered)) if hovered == input),
+                ),
+                AnyParameterId::Output(output) => (
+                    false,
+                    matches!(self.hovered_connection, Some((hovered_output, hovered_input))
+                        if hovered_output == output
+                            || matches!(self.graph.try_get_output(output),
+                                Some(output) if output.paired_input == Some(hovered_input))),
+                ),
+            };
             draw_port(
                 ui,
                 self.graph,
                 self.node_id,
                 user_state,
-                pos_right,
-                &mut responses,
-                AnyParameterId::Output(*param),
+                pos,
+                responses,
+                param,
                 self.port_locations,
+                self.output_connections,
                 self.ongoing_drag,
-                false,
+                connected,
+                self.style,
+                self.strings,
+                highlighted,
+                self.opacity(),
+                self.zoom,
             );
         }
+    }
 
-        // Draw the background shape.
+    /// Returns the shape painted behind the node contents: the titlebar, the
+    /// body and the selection outline. This is the default implementation of
+    /// [`NodeWidget::background`].
+    pub fn background(
+        &mut self,
+        ui: &mut Ui,
+        user_state: &mut UserState,
+        layout: &NodeLayout,
+    ) -> Shape {
         // NOTE: This code is a bit more involved than it needs to be because egui
         // does not support drawing rectangles with asymmetrical round corners.
+        let (mut background_color, _) = node_colors(ui.visuals(), self.style, self.opacity());
+        if let Some(color) = self.graph[self.node_id].user_data.background_color(
+            ui,
+            self.node_id,
+            self.graph,
+            user_state,
+        ) {
+            background_color = color.linear_multiply(self.opacity());
+        }
+        let heatmap_value =
+            self.graph[self.node_id]
+                .user_data
+                .heatmap_value(self.node_id, self.graph, user_state);
+        if let Some(value) = heatmap_value {
+            background_color = self.style.heatmap.tint(background_color, value);
+        }
+        let outer_rect = layout.outer_rect;
+
+        let rounding_radius = self.style.node_rounding * self.zoom;
+        let rounding = Rounding::same(rounding_radius);
+
+        let titlebar_height = layout.titlebar_height;
+        let titlebar_rect =
+            Rect::from_min_size(outer_rect.min, vec2(outer_rect.width(), titlebar_height));
+        let titlebar = Shape::Rect(RectShape {
+            rect: titlebar_rect,
+            rounding,
+            fill: self.graph[self.node_id]
+                .user_data
+                .titlebar_color(ui, self.node_id, self.graph, user_state)
+                .or(self.style.titlebar_color)
+                .map(|color| color.linear_multiply(self.opacity()))
+                .unwrap_or_else(|| background_color.lighten(0.8)),
+            stroke: Stroke::NONE,
+        });
 
-        let (shape, outline) = {
-            let rounding_radius = 4.0;
-            let rounding = Rounding::same(rounding_radius);
-
-            let titlebar_height = title_height + margin.y;
-            let titlebar_rect =
-                Rect::from_min_size(outer_rect.min, vec2(outer_rect.width(), titlebar_height));
-            let titlebar = Shape::Rect(RectShape {
-                rect: titlebar_rect,
-                rounding,
-                fill: self.graph[self.node_id]
-                    .user_data
-                    .titlebar_color(ui, self.node_id, self.graph, user_state)
-                    .unwrap_or_else(|| background_color.lighten(0.8)),
-                stroke: Stroke::NONE,
-            });
+        let body_rect = Rect::from_min_size(
+            outer_rect.min + vec2(0.0, titlebar_height - rounding_radius),
+            vec2(outer_rect.width(), outer_rect.height() - titlebar_height),
+        );
+        let body = Shape::Rect(RectShape {
+            rect: body_rect,
+            rounding: Rounding::none(),
+            fill: background_color,
+            stroke: Stroke::NONE,
+        });
 
-            let body_rect = Rect::from_min_size(
-                outer_rect.min + vec2(0.0, titlebar_height - rounding_radius),
-                vec2(outer_rect.width(), outer_rect.height() - titlebar_height),
-            );
-            let body = Shape::Rect(RectShape {
-                rect: body_rect,
-                rounding: Rounding::none(),
-                fill: background_color,
-                stroke: Stroke::NONE,
-            });
+        let bottom_body_rect = Rect::from_min_size(
+            body_rect.min + vec2(0.0, body_rect.height() - titlebar_height * 0.5),
+            vec2(outer_rect.width(), titlebar_height),
+        );
+        let bottom_body = Shape::Rect(RectShape {
+            rect: bottom_body_rect,
+            rounding,
+            fill: background_color,
+            stroke: Stroke::NONE,
+        });
 
-            let bottom_body_rect = Rect::from_min_size(
-                body_rect.min + vec2(0.0, body_rect.height() - titlebar_height * 0.5),
-                vec2(outer_rect.width(), titlebar_height),
-            );
-            let bottom_body = Shape::Rect(RectShape {
-                rect: bottom_body_rect,
-                rounding,
-                fill: background_color,
-                stroke: Stroke::NONE,
-            });
+        let progress =
+            self.graph[self.node_id]
+                .user_data
+                .progress(self.node_id, self.graph, user_state);
+        let progress_bar = match progress {
+            Some(progress) => {
+                // The body starts under the rounded corners of the titlebar,
+                // so the bar is placed right below the titlebar instead.
+                let track = Rect::from_min_size(
+                    titlebar_rect.left_bottom(),
+                    vec2(
+                        titlebar_rect.width(),
+                        self.style.progress_bar_height * self.zoom,
+                    ),
+                );
+                let mut bar = track;
+                bar.set_width(track.width() * progress.clamp(0.0, 1.0));
+                let color = ui.visuals().selection.bg_fill;
+                Shape::Vec(vec![
+                    Shape::rect_filled(track, Rounding::none(), color.linear_multiply(0.3)),
+                    Shape::rect_filled(bar, Rounding::none(), color),
+                ])
+            }
+            None => Shape::Noop,
+        };
 
-            let node_rect = titlebar_rect.union(body_rect).union(bottom_body_rect);
-            let outline = if self.selected {
-                Shape::Rect(RectShape {
-                    rect: node_rect.expand(1.0),
-                    rounding,
-                    fill: Color32::WHITE.lighten(0.8),
-                    stroke: Stroke::NONE,
-                })
+        let status =
+            self.graph[self.node_id]
+                .user_data
+                .status(self.node_id, self.graph, user_state);
+        let status_style = &self.style.status;
+        let status_color = status_style
+            .color(status)
+            .map(|color| color.linear_multiply(self.opacity()));
+
+        let node_rect = titlebar_rect.union(body_rect).union(bottom_body_rect);
+        let selection = &self.style.selection;
+        let outline = if self.selected {
+            let color = if self.primary_selected {
+                selection.primary_color
             } else {
-                Shape::Noop
+                selection.secondary_color
             };
+            selection.shape(node_rect, self.zoom, color)
+        } else if let (Some(color), NodeStatusIndicator::Outline) =
+            (status_color, status_style.indicator)
+        {
+            selection.shape(node_rect, self.zoom, color)
+        } else if let Some(color) = self.highlight_color() {
+            selection.shape(node_rect, self.zoom, color)
+        } else if self.creation_highlight > 0.0 {
+            let color = selection
+                .primary_color
+                .linear_multiply(self.creation_highlight);
+            selection.shape(node_rect, self.zoom, color)
+        } else {
+            Shape::Noop
+        };
 
-            // Take note of the node rect, so the editor can use it later to compute intersections.
-            self.node_rects.insert(self.node_id, node_rect);
-
-            (Shape::Vec(vec![titlebar, body, bottom_body]), outline)
+        let badge = match (status_color, status_style.indicator) {
+            (Some(color), NodeStatusIndicator::Badge) => Shape::circle_filled(
+                node_rect.left_top(),
+                status_style.badge_radius * self.zoom,
+                color,
+            ),
+            _ => Shape::Noop,
         };
 
-        ui.painter().set(background_shape, shape);
-        ui.painter().set(outline_shape, outline);
+        Shape::Vec(vec![
+            outline,
+            titlebar,
+            body,
+            bottom_body,
+            progress_bar,
+            badge,
+        ])
+    }
 
-        // --- Interaction ---
+    /// Returns the outline color for this node when it's an end of the
+    /// hovered connection, see [`EditorStyle::connection_hover_node_outline`].
+    fn highlight_color(&self) -> Option<Color32> {
+        let (output, input) = self.hovered_connection?;
+        let graph = &self.graph;
+        let attached = graph.inputs.get(input).map(|i| i.node) == Some(self.node_id)
+            || graph.outputs.get(output).map(|o| o.node) == Some(self.node_id);
+        if attached {
+            self.style.connection_hover_node_outline
+        } else {
+            None
+        }
+    }
 
-        // Titlebar buttons
-        let can_delete = self.graph.nodes[self.node_id].user_data.can_delete(
-            self.node_id,
-            self.graph,
-            user_state,
-        );
+    /// The opacity of the node, which is reduced while it's disabled.
+    fn opacity(&self) -> f32 {
+        if self.disabled {
+            self.style.disabled_opacity
+        } else {
+            1.0
+        }
+    }
 
-        if can_delete && Self::close_button(ui, outer_rect).clicked() {
-            responses.push(NodeResponse::DeleteNodeUi(self.node_id));
-        };
+    /// Draws a small pin in the titlebar of pinned nodes, next to the close
+    /// button when there is one.
+    fn pin_indicator(
+        ui: &mut Ui,
+        node_rect: Rect,
+        zoom: f32,
+        has_close_button: bool,
+        direction: LayoutDirection,
+    ) {
+        let margin = 8.0 * zoom;
+        let size = 10.0 * zoom;
+        let offs = margin
+            + size / 2.0
+            + if has_close_button {
+                size + 4.0 * zoom
+            } else {
+                0.0
+            };
 
-        // Movement
-        let drag_delta = window_response.drag_delta();
-        if drag_delta.length_sq() > 0.0 {
-            responses.push(NodeResponse::MoveNode {
-                node: self.node_id,
-                drag_delta,
-            });
-            responses.push(NodeResponse::RaiseNode(self.node_id));
-        }
+        let head = pos2(
+            Self::titlebar_end(node_rect, offs, direction),
+            node_rect.top() + margin + size * 0.3,
+        );
+        let tip = head + vec2(0.0, size * 0.7);
+        let color = ui.visuals().widgets.inactive.fg_stroke.color;
+        ui.painter()
+            .line_segment([head, tip], Stroke::new(1.5 * zoom, color));
+        ui.painter().circle_filled(head, size * 0.3, color);
+    }
 
-        // Node selection
-        //
-        // HACK: Only set the select response when no other response is active.
-        // This prevents some issues.
-        if responses.is_empty() && window_response.clicked_by(PointerButton::Primary) {
-            responses.push(NodeResponse::SelectNode(self.node_id));
-            responses.push(NodeResponse::RaiseNode(self.node_id));
+    /// The horizontal position `offs` away from the end of the titlebar,
+    /// where the close button is.
+    fn titlebar_end(node_rect: Rect, offs: f32, direction: LayoutDirection) -> f32 {
+        match direction {
+            LayoutDirection::LeftToRight => node_rect.right() - offs,
+            LayoutDirection::RightToLeft => node_rect.left() + offs,
         }
-
-        responses
     }
 
-    fn close_button(ui: &mut Ui, node_rect: Rect) -> Response {
+    fn close_button(
+        ui: &mut Ui,
+        node_rect: Rect,
+        zoom: f32,
+        direction: LayoutDirection,
+    ) -> Response {
         // Measurements
-        let margin = 8.0;
-        let size = 10.0;
-        let stroke_width = 2.0;
+        let margin = 8.0 * zoom;
+        let size = 10.0 * zoom;
+        let stroke_width = 2.0 * zoom;
         let offs = margin + size / 2.0;
 
-        let position = pos2(node_rect.right() - offs, node_rect.top() + offs);
+        let position = pos2(
+            Self::titlebar_end(node_rect, offs, direction),
+            node_rect.top() + offs,
+        );
         let rect = Rect::from_center_size(position, vec2(size, size));
         let resp = ui.allocate_rect(rect, Sense::click());
 
-        let dark_mode = ui.visuals().dark_mode;
+        let widgets = &ui.visuals().widgets;
         let color = if resp.clicked() {
-            if dark_mode {
-                color_from_hex("#ffffff").unwrap()
-            } else {
-                color_from_hex("#000000").unwrap()
-            }
+            widgets.active.fg_stroke.color
         } else if resp.hovered() {
-            if dark_mode {
-                color_from_hex("#dddddd").unwrap()
-            } else {
-                color_from_hex("#222222").unwrap()
-            }
+            widgets.hovered.fg_stroke.color
         } else {
-            #[allow(clippy::collapsible_else_if)]
-            if dark_mode {
-                color_from_hex("#aaaaaa").unwrap()
-            } else {
-                color_from_hex("#555555").unwrap()
-            }
+            widgets.inactive.fg_stroke.color
         };
         let stroke = Stroke {
             width: stroke_width,
@@ -7,4 +7,19 @@ pub enum EguiGraphError {
 
     #[error("Parameter {0:?} was not found in the graph.")]
     InvalidParameterId(AnyParameterId),
+
+    #[error("Node {0:?} was not found in the graph.")]
+    InvalidNodeId(NodeId),
+
+    #[error("Saved format version {0} is newer than the supported version {1}")]
+    UnsupportedVersion(u32, u32),
+
+    #[error("No migration was registered from format version {0}")]
+    MissingMigration(u32),
+
+    #[error("Migration from format version {0} failed: {1}")]
+    MigrationFailed(u32, String),
+
+    #[error("Line {0}: {1}")]
+    TextParse(usize, String),
 }
@@ -13,9 +13,33 @@ pub struct Node<NodeData> {
     pub label: String,
     pub inputs: Vec<(String, InputId)>,
     pub outputs: Vec<(String, OutputId)>,
+    /// Groups of parameters, shown after the ungrouped ones under a heading.
+    /// See [`Graph::add_param_group`].
+    #[cfg_attr(feature = "persistence", serde(default))]
+    pub groups: Vec<ParamGroup>,
+    /// The version of the template the node was created from, see
+    /// [`NodeTemplateTrait::version`].
+    #[cfg_attr(feature = "persistence", serde(default))]
+    pub template_version: u32,
     pub user_data: NodeData,
 }
 
+/// A group of parameters inside a node, shown under a heading with a separator
+/// line. Used to organize nodes with many parameters.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "persistence", derive(Serialize, Deserialize))]
+pub struct ParamGroup {
+    /// The heading shown above the parameters.
+    pub name: String,
+    /// The parameters in the group, in the order they are shown. Inputs and
+    /// outputs can be mixed.
+    pub params: Vec<AnyParameterId>,
+    /// When collapsed, only the heading is shown. The ports of the connected
+    /// parameters stay visible next to it.
+    #[cfg_attr(feature = "persistence", serde(default))]
+    pub collapsed: bool,
+}
+
 /// The three kinds of input params. These describe how the graph must behave
 /// with respect to inline widgets and connections for this parameter.
 #[derive(Debug, Clone, Copy)]
@@ -30,11 +54,32 @@ pub enum InputParamKind {
     ConnectionOrConstant,
 }
 
+/// The edge of a node where the port of a parameter is drawn. Connections
+/// leave and enter the ports perpendicular to their edge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "persistence", derive(Serialize, Deserialize))]
+pub enum PortSide {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
 #[cfg(feature = "persistence")]
 fn shown_inline_default() -> bool {
     true
 }
 
+#[cfg(feature = "persistence")]
+fn input_side_default() -> PortSide {
+    PortSide::Left
+}
+
+#[cfg(feature = "persistence")]
+fn output_side_default() -> PortSide {
+    PortSide::Right
+}
+
 /// An input parameter. Input parameters are inside a node, and represent data
 /// that this node receives. Unlike their [`OutputParam`] counterparts, input
 /// parameters also display an inline widget which allows setting its "value".
@@ -58,6 +103,13 @@ pub struct InputParam<DataType, ValueType> {
     /// When true, the node is shown inline inside the node graph.
     #[cfg_attr(feature = "persistence", serde(default = "shown_inline_default"))]
     pub shown_inline: bool,
+    /// The edge of the node where the port is drawn. Defaults to the left.
+    #[cfg_attr(feature = "persistence", serde(default = "input_side_default"))]
+    pub side: PortSide,
+    /// For bidirectional ports, the output that shares the port with this
+    /// input. See [`Graph::add_bidirectional_param`].
+    #[cfg_attr(feature = "persistence", serde(default))]
+    pub paired_output: Option<OutputId>,
 }
 
 /// An output parameter. Output parameters are inside a node, and represent the
@@ -71,6 +123,24 @@ pub struct OutputParam<DataType> {
     /// Back-reference to the node containing this parameter.
     pub node: NodeId,
     pub typ: DataType,
+    /// The edge of the node where the port is drawn. Defaults to the right.
+    /// Bidirectional ports use the side of their input instead.
+    #[cfg_attr(feature = "persistence", serde(default = "output_side_default"))]
+    pub side: PortSide,
+    /// For bidirectional ports, the input that shares the port with this
+    /// output. See [`Graph::add_bidirectional_param`].
+    #[cfg_attr(feature = "persistence", serde(default))]
+    pub paired_input: Option<InputId>,
+}
+
+/// A connection from an output to an input, see [`Graph::connections`].
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "persistence", derive(Serialize, Deserialize))]
+pub struct Connection<ConnectionData> {
+    pub output: OutputId,
+    pub input: InputId,
+    /// The data carried by the connection, like a weight or a delay.
+    pub data: ConnectionData,
 }
 
 /// The graph, containing nodes, input parameters and output parameters. Because
@@ -78,14 +148,18 @@ pub struct OutputParam<DataType> {
 /// crate to represent all the inner references in the data.
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "persistence", derive(Serialize, Deserialize))]
-pub struct Graph<NodeData, DataType, ValueType> {
+pub struct Graph<NodeData, DataType, ValueType, ConnectionData = ()> {
     /// The [`Node`]s of the graph
     pub nodes: SlotMap<NodeId, Node<NodeData>>,
     /// The [`InputParam`]s of the graph
     pub inputs: SlotMap<InputId, InputParam<DataType, ValueType>>,
     /// The [`OutputParam`]s of the graph
     pub outputs: SlotMap<OutputId, OutputParam<DataType>>,
-    // Connects the input of a node, to the output of its predecessor that
-    // produces it
-    pub connections: SecondaryMap<InputId, OutputId>,
+    /// The [`Connection`]s of the graph. Each connects the input of a node to
+    /// the output of its predecessor that produces it.
+    pub connections: SlotMap<ConnectionId, Connection<ConnectionData>>,
+    /// The connections to each input, in the order they were made. Inputs have
+    /// more than one when they take parallel connections, or the values of
+    /// several outputs.
+    pub input_connections: SecondaryMap<InputId, Vec<ConnectionId>>,
 }
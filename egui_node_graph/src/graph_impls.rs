@@ -1,12 +1,15 @@
 use super::*;
 
-impl<NodeData, DataType, ValueType> Graph<NodeData, DataType, ValueType> {
+impl<NodeData, DataType, ValueType, ConnectionData>
+    Graph<NodeData, DataType, ValueType, ConnectionData>
+{
     pub fn new() -> Self {
         Self {
             nodes: SlotMap::default(),
             inputs: SlotMap::default(),
             outputs: SlotMap::default(),
-            connections: SecondaryMap::default(),
+            connections: SlotMap::default(),
+            input_connections: SecondaryMap::default(),
         }
     }
 
@@ -14,7 +17,7 @@ impl<NodeData, DataType, ValueType> Graph<NodeData, DataType, ValueType> {
         &mut self,
         label: String,
         user_data: NodeData,
-        f: impl FnOnce(&mut Graph<NodeData, DataType, ValueType>, NodeId),
+        f: impl FnOnce(&mut Graph<NodeData, DataType, ValueType, ConnectionData>, NodeId),
     ) -> NodeId {
         let node_id = self.nodes.insert_with_key(|node_id| {
             Node {
@@ -23,6 +26,8 @@ impl<NodeData, DataType, ValueType> Graph<NodeData, DataType, ValueType> {
                 // These get filled in later by the user function
                 inputs: Vec::default(),
                 outputs: Vec::default(),
+                groups: Vec::default(),
+                template_version: 0,
                 user_data,
             }
         });
@@ -48,23 +53,107 @@ impl<NodeData, DataType, ValueType> Graph<NodeData, DataType, ValueType> {
             kind,
             node: node_id,
             shown_inline,
+            side: PortSide::Left,
+            paired_output: None,
         });
         self.nodes[node_id].inputs.push((name, input_id));
         input_id
     }
 
-    pub fn remove_input_param(&mut self, param: InputId) {
-        let node = self[param].node;
-        self[node].inputs.retain(|(_, id)| *id != param);
-        self.inputs.remove(param);
-        self.connections.retain(|i, _| i != param);
+    /// Adds a bidirectional parameter, made of an input and an output that
+    /// share a single port. The port can receive a connection like an input,
+    /// and send connections like an output. Only the input is shown in the
+    /// node, with its inline widget, and the output has no separate row.
+    pub fn add_bidirectional_param(
+        &mut self,
+        node_id: NodeId,
+        name: String,
+        typ: DataType,
+        value: ValueType,
+        kind: InputParamKind,
+        shown_inline: bool,
+    ) -> (InputId, OutputId)
+    where
+        DataType: Clone,
+    {
+        let input_id = self.add_input_param(
+            node_id,
+            name.clone(),
+            typ.clone(),
+            value,
+            kind,
+            shown_inline,
+        );
+        let output_id = self.add_output_param(node_id, name, typ);
+        self.inputs[input_id].paired_output = Some(output_id);
+        self.outputs[output_id].paired_input = Some(input_id);
+        (input_id, output_id)
+    }
+
+    /// Adds a group of parameters to a node, shown under the heading `name`
+    /// with a separator line. Typically called from
+    /// [`NodeTemplateTrait::build_node`], after adding the parameters. A
+    /// parameter should be in one group at most. Groups can be collapsed by
+    /// clicking their heading.
+    pub fn add_param_group(
+        &mut self,
+        node_id: NodeId,
+        name: String,
+        params: impl IntoIterator<Item = AnyParameterId>,
+    ) {
+        self.nodes[node_id].groups.push(ParamGroup {
+            name,
+            params: params.into_iter().collect(),
+            collapsed: false,
+        });
+    }
+
+    /// Removes an input parameter, along with its connections. Fails if the
+    /// parameter doesn't exist.
+    pub fn remove_input_param(&mut self, param: InputId) -> Result<(), EguiGraphError> {
+        let removed = self
+            .inputs
+            .remove(param)
+            .ok_or(EguiGraphError::InvalidParameterId(AnyParameterId::Input(
+                param,
+            )))?;
+        if let Some(output) = removed.paired_output.and_then(|o| self.outputs.get_mut(o)) {
+            output.paired_input = None;
+        }
+        if let Some(node) = self.nodes.get_mut(removed.node) {
+            node.inputs.retain(|(_, id)| *id != param);
+            node.remove_from_groups(AnyParameterId::Input(param));
+        }
+        self.remove_connection(param);
+        Ok(())
     }
 
-    pub fn remove_output_param(&mut self, param: OutputId) {
-        let node = self[param].node;
-        self[node].outputs.retain(|(_, id)| *id != param);
-        self.outputs.remove(param);
-        self.connections.retain(|_, o| *o != param);
+    /// Removes an output parameter, along with its connections. Fails if the
+    /// parameter doesn't exist.
+    pub fn remove_output_param(&mut self, param: OutputId) -> Result<(), EguiGraphError> {
+        let removed = self
+            .outputs
+            .remove(param)
+            .ok_or(EguiGraphError::InvalidParameterId(AnyParameterId::Output(
+                param,
+            )))?;
+        if let Some(input) = removed.paired_input.and_then(|i| self.inputs.get_mut(i)) {
+            input.paired_output = None;
+        }
+        if let Some(node) = self.nodes.get_mut(removed.node) {
+            node.outputs.retain(|(_, id)| *id != param);
+            node.remove_from_groups(AnyParameterId::Output(param));
+        }
+        let removed: Vec<_> = self
+            .connections
+            .iter()
+            .filter(|(_, connection)| connection.output == param)
+            .map(|(id, _)| id)
+            .collect();
+        for id in removed {
+            self.disconnect(id);
+        }
+        Ok(())
     }
 
     pub fn add_output_param(&mut self, node_id: NodeId, name: String, typ: DataType) -> OutputId {
@@ -72,6 +161,8 @@ impl<NodeData, DataType, ValueType> Graph<NodeData, DataType, ValueType> {
             id: output_id,
             node: node_id,
             typ,
+            side: PortSide::Right,
+            paired_input: None,
         });
         self.nodes[node_id].outputs.push((name, output_id));
         output_id
@@ -81,52 +172,213 @@ impl<NodeData, DataType, ValueType> Graph<NodeData, DataType, ValueType> {
     /// any incoming or outgoing connections from that node
     ///
     /// This function returns the list of connections that has been removed
-    /// after deleting this node as input-output pairs. Note that one of the two
-    /// ids in the pair (the one on `node_id`'s end) will be invalid after
-    /// calling this function.
-    pub fn remove_node(&mut self, node_id: NodeId) -> (Node<NodeData>, Vec<(InputId, OutputId)>) {
-        let mut disconnect_events = vec![];
-
-        self.connections.retain(|i, o| {
-            if self.outputs[*o].node == node_id || self.inputs[i].node == node_id {
-                disconnect_events.push((i, *o));
-                false
-            } else {
-                true
-            }
-        });
-
-        // NOTE: Collect is needed because we can't borrow the input ids while
-        // we remove them inside the loop.
-        for input in self[node_id].input_ids().collect::<SVec<_>>() {
+    /// after deleting this node, along with their ids. Note that one of the
+    /// two parameters of each connection (the one on `node_id`'s end) will be
+    /// invalid after calling this function. Fails if the node doesn't exist.
+    #[allow(clippy::type_complexity)]
+    pub fn remove_node(
+        &mut self,
+        node_id: NodeId,
+    ) -> Result<
+        (
+            Node<NodeData>,
+            Vec<(ConnectionId, Connection<ConnectionData>)>,
+        ),
+        EguiGraphError,
+    > {
+        let removed_node = self
+            .nodes
+            .remove(node_id)
+            .ok_or(EguiGraphError::InvalidNodeId(node_id))?;
+        let (inputs, outputs) = (&self.inputs, &self.outputs);
+        // Connections to missing parameters are dropped as well
+        let on_node = |node: Option<NodeId>| node.unwrap_or(node_id) == node_id;
+        let removed: Vec<_> = self
+            .connections
+            .iter()
+            .filter(|(_, c)| {
+                on_node(inputs.get(c.input).map(|i| i.node))
+                    || on_node(outputs.get(c.output).map(|o| o.node))
+            })
+            .map(|(id, _)| id)
+            .collect();
+        let disconnect_events = removed
+            .into_iter()
+            .filter_map(|id| Some((id, self.disconnect(id)?)))
+            .collect();
+
+        for input in removed_node.input_ids() {
             self.inputs.remove(input);
         }
-        for output in self[node_id].output_ids().collect::<SVec<_>>() {
+        for output in removed_node.output_ids() {
             self.outputs.remove(output);
         }
-        let removed_node = self.nodes.remove(node_id).expect("Node should exist");
 
-        (removed_node, disconnect_events)
+        Ok((removed_node, disconnect_events))
     }
 
+    /// Removes the connections to an input, and returns the output of the
+    /// first one.
     pub fn remove_connection(&mut self, input_id: InputId) -> Option<OutputId> {
-        self.connections.remove(input_id)
+        let ids = self.input_connections.remove(input_id)?;
+        let mut removed = ids.into_iter().filter_map(|id| self.connections.remove(id));
+        let first = removed.next().map(|connection| connection.output);
+        removed.for_each(drop);
+        first
+    }
+
+    /// Removes the last connection made to an input, and returns the output
+    /// it came from. The other connections of the input are kept.
+    pub fn remove_parallel_connection(&mut self, input_id: InputId) -> Option<OutputId> {
+        let id = *self.input_connections.get(input_id)?.last()?;
+        self.disconnect(id).map(|connection| connection.output)
+    }
+
+    /// Removes a connection, and returns it. Returns `None` if the connection
+    /// doesn't exist.
+    pub fn disconnect(&mut self, id: ConnectionId) -> Option<Connection<ConnectionData>> {
+        let connection = self.connections.remove(id)?;
+        if let Some(ids) = self.input_connections.get_mut(connection.input) {
+            ids.retain(|other| *other != id);
+            if ids.is_empty() {
+                self.input_connections.remove(connection.input);
+            }
+        }
+        Some(connection)
     }
 
     pub fn iter_nodes(&self) -> impl Iterator<Item = NodeId> + '_ {
         self.nodes.iter().map(|(id, _)| id)
     }
 
-    pub fn add_connection(&mut self, output: OutputId, input: InputId) {
-        self.connections.insert(input, output);
+    /// Connects `output` to `input`, replacing the previous connections of
+    /// `input`. The connection starts with the default data. Fails if either
+    /// parameter doesn't exist.
+    pub fn add_connection(
+        &mut self,
+        output: OutputId,
+        input: InputId,
+    ) -> Result<ConnectionId, EguiGraphError>
+    where
+        ConnectionData: Default,
+    {
+        self.add_connection_with_data(output, input, ConnectionData::default())
+    }
+
+    /// Connects `output` to `input` with the given data, replacing the
+    /// previous connections of `input`. Fails if either parameter doesn't
+    /// exist.
+    pub fn add_connection_with_data(
+        &mut self,
+        output: OutputId,
+        input: InputId,
+        data: ConnectionData,
+    ) -> Result<ConnectionId, EguiGraphError> {
+        self.check_connection(output, input)?;
+        self.remove_connection(input);
+        Ok(self.insert_connection(output, input, data))
+    }
+
+    /// Adds a connection from `output` to `input` next to the ones that
+    /// already connect them, for graphs with parallel connections. Other
+    /// connections of `input` are replaced. Fails if either parameter doesn't
+    /// exist.
+    pub fn add_parallel_connection(
+        &mut self,
+        output: OutputId,
+        input: InputId,
+    ) -> Result<ConnectionId, EguiGraphError>
+    where
+        ConnectionData: Default,
+    {
+        self.check_connection(output, input)?;
+        if self.connection(input) != Some(output) {
+            self.remove_connection(input);
+        }
+        Ok(self.insert_connection(output, input, ConnectionData::default()))
+    }
+
+    /// Adds a connection from `output` to `input`, keeping the other
+    /// connections of `input`, for inputs that take the values of several
+    /// outputs. Fails if either parameter doesn't exist.
+    pub fn append_connection(
+        &mut self,
+        output: OutputId,
+        input: InputId,
+    ) -> Result<ConnectionId, EguiGraphError>
+    where
+        ConnectionData: Default,
+    {
+        self.append_connection_with_data(output, input, ConnectionData::default())
+    }
+
+    /// Like [`Graph::append_connection`], with the given data.
+    pub fn append_connection_with_data(
+        &mut self,
+        output: OutputId,
+        input: InputId,
+        data: ConnectionData,
+    ) -> Result<ConnectionId, EguiGraphError> {
+        self.check_connection(output, input)?;
+        Ok(self.insert_connection(output, input, data))
+    }
+
+    pub(crate) fn insert_connection(
+        &mut self,
+        output: OutputId,
+        input: InputId,
+        data: ConnectionData,
+    ) -> ConnectionId {
+        let id = self.connections.insert(Connection {
+            output,
+            input,
+            data,
+        });
+        if let Some(ids) = self.input_connections.entry(input) {
+            ids.or_default().push(id);
+        }
+        id
+    }
+
+    fn check_connection(&self, output: OutputId, input: InputId) -> Result<(), EguiGraphError> {
+        if !self.outputs.contains_key(output) {
+            return Err(EguiGraphError::InvalidParameterId(AnyParameterId::Output(
+                output,
+            )));
+        }
+        if !self.inputs.contains_key(input) {
+            return Err(EguiGraphError::InvalidParameterId(AnyParameterId::Input(
+                input,
+            )));
+        }
+        Ok(())
     }
 
+    /// Iterates over the connections of the graph. Parallel connections are
+    /// returned once each.
     pub fn iter_connections(&self) -> impl Iterator<Item = (InputId, OutputId)> + '_ {
-        self.connections.iter().map(|(o, i)| (o, *i))
+        self.connections.values().map(|c| (c.input, c.output))
     }
 
+    /// Returns the output connected to `input`. When the input has several
+    /// connections, this is the output of the first one.
     pub fn connection(&self, input: InputId) -> Option<OutputId> {
-        self.connections.get(input).copied()
+        let id = self.connection_ids(input).first()?;
+        self.connections
+            .get(*id)
+            .map(|connection| connection.output)
+    }
+
+    /// The connections to `input`, in the order they were made.
+    pub fn connection_ids(&self, input: InputId) -> &[ConnectionId] {
+        self.input_connections
+            .get(input)
+            .map_or(&[], |ids| ids.as_slice())
+    }
+
+    /// The number of connections to an input, counting parallel ones.
+    pub fn connection_count(&self, input: InputId) -> usize {
+        self.connection_ids(input).len()
     }
 
     pub fn any_param_type(&self, param: AnyParameterId) -> Result<&DataType, EguiGraphError> {
@@ -137,6 +389,30 @@ impl<NodeData, DataType, ValueType> Graph<NodeData, DataType, ValueType> {
         .ok_or(EguiGraphError::InvalidParameterId(param))
     }
 
+    /// Returns the node `param` belongs to.
+    pub fn any_param_node(&self, param: AnyParameterId) -> Result<NodeId, EguiGraphError> {
+        match param {
+            AnyParameterId::Input(input) => self.inputs.get(input).map(|x| x.node),
+            AnyParameterId::Output(output) => self.outputs.get(output).map(|x| x.node),
+        }
+        .ok_or(EguiGraphError::InvalidParameterId(param))
+    }
+
+    /// Returns the edge of its node where the port of `param` is drawn. For
+    /// bidirectional ports, this is the side of the input.
+    pub fn any_param_side(&self, param: AnyParameterId) -> Result<PortSide, EguiGraphError> {
+        match param {
+            AnyParameterId::Input(input) => self.inputs.get(input).map(|x| x.side),
+            AnyParameterId::Output(output) => {
+                self.outputs.get(output).and_then(|x| match x.paired_input {
+                    Some(input) => self.inputs.get(input).map(|x| x.side),
+                    None => Some(x.side),
+                })
+            }
+        }
+        .ok_or(EguiGraphError::InvalidParameterId(param))
+    }
+
     pub fn try_get_input(&self, input: InputId) -> Option<&InputParam<DataType, ValueType>> {
         self.inputs.get(input)
     }
@@ -154,24 +430,26 @@ impl<NodeData, DataType, ValueType> Graph<NodeData, DataType, ValueType> {
     }
 }
 
-impl<NodeData, DataType, ValueType> Default for Graph<NodeData, DataType, ValueType> {
+impl<NodeData, DataType, ValueType, ConnectionData> Default
+    for Graph<NodeData, DataType, ValueType, ConnectionData>
+{
     fn default() -> Self {
         Self::new()
     }
 }
 
 impl<NodeData> Node<NodeData> {
-    pub fn inputs<'a, DataType, DataValue>(
+    pub fn inputs<'a, DataType, DataValue, ConnectionData>(
         &'a self,
-        graph: &'a Graph<NodeData, DataType, DataValue>,
-    ) -> impl Iterator<Item = &InputParam<DataType, DataValue>> + 'a {
+        graph: &'a Graph<NodeData, DataType, DataValue, ConnectionData>,
+    ) -> impl Iterator<Item = &'a InputParam<DataType, DataValue>> + 'a {
         self.input_ids().map(|id| graph.get_input(id))
     }
 
-    pub fn outputs<'a, DataType, DataValue>(
+    pub fn outputs<'a, DataType, DataValue, ConnectionData>(
         &'a self,
-        graph: &'a Graph<NodeData, DataType, DataValue>,
-    ) -> impl Iterator<Item = &OutputParam<DataType>> + 'a {
+        graph: &'a Graph<NodeData, DataType, DataValue, ConnectionData>,
+    ) -> impl Iterator<Item = &'a OutputParam<DataType>> + 'a {
         self.output_ids().map(|id| graph.get_output(id))
     }
 
@@ -183,6 +461,20 @@ impl<NodeData> Node<NodeData> {
         self.outputs.iter().map(|(_name, id)| *id)
     }
 
+    /// Returns whether `param` belongs to one of the [`ParamGroup`]s of the
+    /// node.
+    pub fn is_grouped(&self, param: AnyParameterId) -> bool {
+        self.groups
+            .iter()
+            .any(|group| group.params.contains(&param))
+    }
+
+    fn remove_from_groups(&mut self, param: AnyParameterId) {
+        for group in &mut self.groups {
+            group.params.retain(|p| *p != param);
+        }
+    }
+
     pub fn get_input(&self, name: &str) -> Result<InputId, EguiGraphError> {
         self.inputs
             .iter()
@@ -0,0 +1,264 @@
+use super::*;
+use egui::{Key, Modifiers};
+
+/// Changes of the same kind that happen within this many seconds of each
+/// other, like the frames of a drag, are undone together.
+const HISTORY_MERGE_WINDOW: f64 = 0.5;
+
+/// The parts of the editor state that are restored by
+/// [`GraphEditorState::undo`] and [`GraphEditorState::redo`].
+#[derive(Clone, Debug)]
+pub struct HistorySnapshot<NodeData, DataType, ValueType, ConnectionData> {
+    pub graph: Graph<NodeData, DataType, ValueType, ConnectionData>,
+    pub node_positions: SecondaryMap<NodeId, egui::Pos2>,
+    pub node_widths: SecondaryMap<NodeId, f32>,
+    pub node_order: Vec<NodeId>,
+}
+
+/// The undo and redo stacks of an editor, filled by
+/// [`GraphEditorState::update_history`].
+#[derive(Clone, Debug)]
+pub struct EditHistory<NodeData, DataType, ValueType, ConnectionData> {
+    pub undo_stack: Vec<HistorySnapshot<NodeData, DataType, ValueType, ConnectionData>>,
+    pub redo_stack: Vec<HistorySnapshot<NodeData, DataType, ValueType, ConnectionData>>,
+    /// The state after the last recorded change, which is pushed to the undo
+    /// stack when the next change happens.
+    pub current: Option<HistorySnapshot<NodeData, DataType, ValueType, ConnectionData>>,
+    /// The time of the last move or value edit, which the next one is merged
+    /// with when it comes soon enough.
+    last_merge: Option<f64>,
+}
+
+impl<NodeData, DataType, ValueType, ConnectionData> Default
+    for EditHistory<NodeData, DataType, ValueType, ConnectionData>
+{
+    fn default() -> Self {
+        Self {
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            current: None,
+            last_merge: None,
+        }
+    }
+}
+
+impl<NodeData, DataType, ValueType, NodeTemplate, UserState, ConnectionData>
+    GraphEditorState<NodeData, DataType, ValueType, NodeTemplate, UserState, ConnectionData>
+where
+    NodeData: Clone,
+    DataType: Clone,
+    ValueType: Clone + PartialEq,
+    ConnectionData: Clone,
+{
+    /// Records the changes made this frame in the undo history, and handles
+    /// the undo and redo shortcuts when
+    /// [`EditorSettings::history_shortcuts`] is set. Call this after drawing
+    /// the editor each frame, with its responses, and after the application
+    /// handled them, so its own changes are recorded along with them.
+    ///
+    /// Node creation and deletion, connection changes, renames and other
+    /// edits reported in `responses` are recorded one by one. Moves and value
+    /// edits in a row, like the frames of a drag, are recorded as one.
+    pub fn update_history<UserResponse>(
+        &mut self,
+        ctx: &egui::Context,
+        responses: &[NodeResponse<UserResponse, NodeData>],
+    ) where
+        UserResponse: UserResponseTrait,
+        NodeData: NodeDataTrait,
+    {
+        if self.settings.history_shortcuts && ctx.memory(|mem| mem.focus().is_none()) {
+            let (undo, redo) = ctx.input(|i| {
+                let command = i.modifiers.matches(Modifiers::COMMAND);
+                let shift_command = i.modifiers.matches(Modifiers::COMMAND | Modifiers::SHIFT);
+                (
+                    command && i.key_pressed(Key::Z),
+                    (shift_command && i.key_pressed(Key::Z)) || (command && i.key_pressed(Key::Y)),
+                )
+            });
+            if undo {
+                self.undo();
+                return;
+            } else if redo {
+                self.redo();
+                return;
+            }
+        }
+
+        let current = match &self.history.current {
+            Some(current) => current,
+            None => {
+                self.history.current = Some(self.snapshot());
+                return;
+            }
+        };
+        let edited = responses.iter().any(|response| {
+            !matches!(
+                response,
+                NodeResponse::ConnectEventStarted(..)
+                    | NodeResponse::RaiseNode(_)
+                    | NodeResponse::MoveNode { .. }
+                    | NodeResponse::SelectNode(_)
+                    | NodeResponse::SelectionChanged(_)
+                    | NodeResponse::CopiedNodes(_)
+                    | NodeResponse::InconsistentState(_)
+                    | NodeResponse::User(_)
+            )
+        }) || self.graph.nodes.len() != current.graph.nodes.len()
+            || self.graph.connections.len() != current.graph.connections.len();
+        let moved = self
+            .node_positions
+            .iter()
+            .any(|(node_id, pos)| current.node_positions.get(node_id) != Some(pos));
+        let values_changed = self.graph.inputs.iter().any(|(input_id, input)| {
+            match current.graph.inputs.get(input_id) {
+                Some(old) => old.value != input.value,
+                None => true,
+            }
+        });
+        if !edited && !moved && !values_changed {
+            return;
+        }
+
+        let now = ctx.input(|i| i.time);
+        let merge =
+            !edited && matches!(self.history.last_merge, Some(t) if now - t < HISTORY_MERGE_WINDOW);
+        if !merge {
+            let previous = self.history.current.take();
+            self.history.undo_stack.extend(previous);
+            let excess = self
+                .history
+                .undo_stack
+                .len()
+                .saturating_sub(self.settings.history_limit);
+            self.history.undo_stack.drain(..excess);
+            self.history.redo_stack.clear();
+        }
+        self.history.current = Some(self.snapshot());
+        self.history.last_merge = if edited { None } else { Some(now) };
+    }
+
+    /// Reverts the last change recorded by
+    /// [`GraphEditorState::update_history`]. Returns false if there was
+    /// nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        match self.history.undo_stack.pop() {
+            Some(snapshot) => {
+                let current = self.snapshot();
+                self.history.redo_stack.push(current);
+                self.restore(snapshot);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Applies again the last change reverted by [`GraphEditorState::undo`].
+    /// Returns false if there was nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        match self.history.redo_stack.pop() {
+            Some(snapshot) => {
+                let current = self.snapshot();
+                self.history.undo_stack.push(current);
+                self.restore(snapshot);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.history.undo_stack.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.history.redo_stack.is_empty()
+    }
+
+    /// Forgets the recorded changes, e.g. after loading another document.
+    pub fn clear_history(&mut self) {
+        self.history = EditHistory::default();
+    }
+
+    fn snapshot(&self) -> HistorySnapshot<NodeData, DataType, ValueType, ConnectionData> {
+        HistorySnapshot {
+            graph: self.graph.clone(),
+            node_positions: self.node_positions.clone(),
+            node_widths: self.node_widths.clone(),
+            node_order: self.node_order.clone(),
+        }
+    }
+
+    fn restore(
+        &mut self,
+        snapshot: HistorySnapshot<NodeData, DataType, ValueType, ConnectionData>,
+    ) {
+        self.history.current = Some(snapshot.clone());
+        self.history.last_merge = None;
+        self.graph = snapshot.graph;
+        self.node_positions = snapshot.node_positions;
+        self.node_widths = snapshot.node_widths;
+        self.node_order = snapshot.node_order;
+
+        // Drop the editor state that refers to nodes or connections that
+        // don't exist anymore.
+        let graph = &self.graph;
+        self.selected_nodes
+            .retain(|node_id| graph.nodes.contains_key(*node_id));
+        self.pinned_nodes
+            .retain(|node_id| graph.nodes.contains_key(*node_id));
+        self.connection_in_progress = None;
+        self.connection_popup = None;
+        self.dragged_weight = None;
+        self.selected_connection = None;
+        self.dragged_reroute_point = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    type TestState = GraphEditorState<TestNode, u32, u32, (), (), u32>;
+
+    fn value(state: &TestState) -> Option<u32> {
+        let (_, input) = state.graph.inputs.iter().next()?;
+        Some(input.value)
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let ctx = egui::Context::default();
+        let mut state = TestState::default();
+        state.update_history::<TestResponse>(&ctx, &[]);
+        assert!(!state.can_undo());
+
+        let node_id = add_editor_node(&mut state, "a", egui::pos2(10.0, 20.0));
+        state.update_history::<TestResponse>(&ctx, &[NodeResponse::CreatedNode(node_id)]);
+
+        let input = state.graph[node_id].get_input("in").unwrap();
+        state.graph[input].value = 5;
+        state.update_history::<TestResponse>(&ctx, &[]);
+
+        assert!(state.undo());
+        assert_eq!(value(&state), Some(0));
+        assert!(state.undo());
+        assert!(state.graph.nodes.is_empty());
+        assert!(state.node_order.is_empty());
+        assert!(!state.undo());
+
+        assert!(state.redo());
+        assert_eq!(state.node_order, vec![node_id]);
+        assert_eq!(state.node_positions[node_id], egui::pos2(10.0, 20.0));
+        assert!(state.redo());
+        assert_eq!(value(&state), Some(5));
+        assert!(!state.can_redo());
+
+        // A new change discards the changes that were undone.
+        assert!(state.undo());
+        state.graph[input].value = 7;
+        state.update_history::<TestResponse>(&ctx, &[]);
+        assert!(!state.can_redo());
+    }
+}
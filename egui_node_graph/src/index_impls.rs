@@ -2,7 +2,7 @@ use super::*;
 
 macro_rules! impl_index_traits {
     ($id_type:ty, $output_type:ty, $arena:ident) => {
-        impl<A, B, C> std::ops::Index<$id_type> for Graph<A, B, C> {
+        impl<A, B, C, D> std::ops::Index<$id_type> for Graph<A, B, C, D> {
             type Output = $output_type;
 
             fn index(&self, index: $id_type) -> &Self::Output {
@@ -16,7 +16,7 @@ macro_rules! impl_index_traits {
             }
         }
 
-        impl<A, B, C> std::ops::IndexMut<$id_type> for Graph<A, B, C> {
+        impl<A, B, C, D> std::ops::IndexMut<$id_type> for Graph<A, B, C, D> {
             fn index_mut(&mut self, index: $id_type) -> &mut Self::Output {
                 self.$arena.get_mut(index).unwrap_or_else(|| {
                     panic!(
@@ -33,3 +33,4 @@ macro_rules! impl_index_traits {
 impl_index_traits!(NodeId, Node<A>, nodes);
 impl_index_traits!(InputId, InputParam<B, C>, inputs);
 impl_index_traits!(OutputId, OutputParam<B>, outputs);
+impl_index_traits!(ConnectionId, Connection<D>, connections);
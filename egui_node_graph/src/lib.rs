@@ -19,6 +19,56 @@ pub mod index_impls;
 /// Implementing the main methods for the `Graph`
 pub mod graph_impls;
 
+/// Summary figures about a graph, like its size and depth
+pub mod stats;
+pub use stats::*;
+
+/// Comparison of two versions of a graph
+pub mod diff;
+pub use diff::*;
+
+/// Three-way merge of graph documents
+pub mod merge;
+pub use merge::*;
+
+/// Replicated graph operations, for collaborative editing
+pub mod ops;
+pub use ops::*;
+
+/// Mirroring of the editor state between instances
+pub mod sync;
+pub use sync::*;
+
+/// Recording and replay of editing sessions
+pub mod recording;
+pub use recording::*;
+
+/// Undo and redo of the edits made in the editor
+pub mod history;
+pub use history::*;
+
+/// Copy, paste and duplication of nodes
+pub mod clipboard;
+pub use clipboard::*;
+
+/// Upgrades of saved documents between format versions
+pub mod migration;
+pub use migration::*;
+
+/// Consistency checks for loaded documents
+pub mod validation;
+pub use validation::*;
+
+/// Importer for node trees exported from Blender
+#[cfg(feature = "blender_import")]
+pub mod blender;
+#[cfg(feature = "blender_import")]
+pub use blender::*;
+
+/// A human-readable text format for graphs
+pub mod text_format;
+pub use text_format::*;
+
 /// Custom error types, crate-wide
 pub mod error;
 pub use error::*;
@@ -32,6 +82,26 @@ pub use ui_state::*;
 pub mod node_finder;
 pub use node_finder::*;
 
+/// A circular quick menu to create the most used node types
+pub mod radial_menu;
+pub use radial_menu::*;
+
+/// Behavior settings for the editor, configured by the application
+pub mod settings;
+pub use settings::*;
+
+/// The text of the built-in UI, for localization
+pub mod strings;
+pub use strings::*;
+
+/// Visual settings for the editor, configured by the application
+pub mod style;
+pub use style::*;
+
+/// Scaling of egui styles, used to draw the nodes at the current zoom level
+pub mod scale;
+pub use scale::*;
+
 /// The inner details of the egui implementation. Most egui code lives here.
 pub mod editor_ui;
 pub use editor_ui::*;
@@ -43,4 +113,9 @@ pub use traits::*;
 
 mod utils;
 
-mod color_hex_utils;
+/// Helpers to write colors as hex strings, e.g. for the editor style
+pub mod color_hex_utils;
+
+/// Fixtures shared by the tests of several modules
+#[cfg(test)]
+mod test_utils;
@@ -0,0 +1,257 @@
+use std::collections::HashMap;
+
+use super::*;
+
+/// A change made on both sides of a [`Graph::merge`] that couldn't be
+/// reconciled. In all cases, the merged graph keeps "our" version.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MergeConflict {
+    /// Both sides changed the label of the node to different values.
+    Label(NodeId),
+    /// Both sides changed the value of the input to different values.
+    Value(InputId),
+    /// One side removed the node, and the other changed its label or values.
+    RemovedChangedNode(NodeId),
+    /// Both sides connected an input that takes a single connection to
+    /// different outputs.
+    Connection(InputId),
+}
+
+/// The result of a [`Graph::merge`].
+#[derive(Clone, Debug)]
+pub struct MergeResult<NodeData, DataType, ValueType, ConnectionData = ()> {
+    /// The merged graph.
+    pub graph: Graph<NodeData, DataType, ValueType, ConnectionData>,
+    /// The changes that couldn't be merged.
+    pub conflicts: Vec<MergeConflict>,
+    /// The nodes added on "their" side get new ids in the merged graph, so
+    /// they don't clash with the nodes added on "our" side. This maps their
+    /// ids to the new ones, e.g. to carry over the node positions.
+    pub their_nodes: HashMap<NodeId, NodeId>,
+}
+
+impl<NodeData, DataType, ValueType, ConnectionData>
+    Graph<NodeData, DataType, ValueType, ConnectionData>
+where
+    NodeData: Clone,
+    DataType: Clone + PartialEq,
+    ValueType: Clone + PartialEq,
+    ConnectionData: Clone + Default,
+{
+    /// Merges the changes made in two versions of the same document, `ours`
+    /// and `theirs`, since their common ancestor `base`. Changes made on one
+    /// side only are combined, and changes made on both sides are reported as
+    /// [`MergeConflict`]s.
+    ///
+    /// Node labels, input values, connections and added or removed nodes are
+    /// merged. Changes to the parameters of existing nodes, and the user data
+    /// of existing nodes, are taken from `ours`.
+    pub fn merge(
+        base: &Self,
+        ours: &Self,
+        theirs: &Self,
+    ) -> MergeResult<NodeData, DataType, ValueType, ConnectionData> {
+        let our_diff = base.diff(ours);
+        let their_diff = base.diff(theirs);
+        let mut graph = ours.clone();
+        let mut conflicts = Vec::new();
+
+        let changed_by = |diff: &GraphDiff, node_id: NodeId| {
+            diff.changed_nodes.contains(&node_id)
+                || diff
+                    .changed_values
+                    .iter()
+                    .any(|input| base[*input].node == node_id)
+        };
+
+        for &node_id in &their_diff.removed_nodes {
+            if our_diff.removed_nodes.contains(&node_id) {
+                continue;
+            }
+            if changed_by(&our_diff, node_id) {
+                conflicts.push(MergeConflict::RemovedChangedNode(node_id));
+            } else {
+                let _ = graph.remove_node(node_id);
+            }
+        }
+        for &node_id in &our_diff.removed_nodes {
+            if !their_diff.removed_nodes.contains(&node_id) && changed_by(&their_diff, node_id) {
+                conflicts.push(MergeConflict::RemovedChangedNode(node_id));
+            }
+        }
+
+        for &node_id in &their_diff.changed_nodes {
+            if !graph.nodes.contains_key(node_id) {
+                continue;
+            }
+            let (base_label, our_label) = (&base[node_id].label, &ours[node_id].label);
+            let their_label = &theirs[node_id].label;
+            if their_label == base_label || their_label == our_label {
+                continue;
+            }
+            if our_label == base_label {
+                graph[node_id].label = their_label.clone();
+            } else {
+                conflicts.push(MergeConflict::Label(node_id));
+            }
+        }
+
+        for &input in &their_diff.changed_values {
+            if !graph.inputs.contains_key(input) {
+                continue;
+            }
+            if !our_diff.changed_values.contains(&input) {
+                graph[input].value = theirs[input].value.clone();
+            } else if ours[input].value != theirs[input].value {
+                conflicts.push(MergeConflict::Value(input));
+            }
+        }
+
+        let mut their_nodes = HashMap::new();
+        let mut their_inputs = HashMap::new();
+        let mut their_outputs = HashMap::new();
+        for &node_id in &their_diff.added_nodes {
+            let new_id =
+                graph.copy_node_from(theirs, node_id, &mut their_inputs, &mut their_outputs);
+            their_nodes.insert(node_id, new_id);
+        }
+
+        // The removed connections have their id in the base, which is the
+        // same in ours unless we removed them too.
+        for &id in &their_diff.removed_connections {
+            graph.disconnect(id);
+        }
+        // The connections we added, which they may have added as well.
+        let mut our_added: Vec<(InputId, OutputId)> = our_diff
+            .added_connections
+            .iter()
+            .map(|id| (ours[*id].input, ours[*id].output))
+            .collect();
+        for &id in &their_diff.added_connections {
+            let connection = &theirs[id];
+            // Params that are neither in the base nor added by them belong to
+            // another node, and connections to removed params are dropped.
+            let input = match their_inputs.get(&connection.input) {
+                Some(input) => *input,
+                None if base.inputs.contains_key(connection.input) => connection.input,
+                None => continue,
+            };
+            let output = match their_outputs.get(&connection.output) {
+                Some(output) => *output,
+                None if base.outputs.contains_key(connection.output) => connection.output,
+                None => continue,
+            };
+            if !graph.inputs.contains_key(input) || !graph.outputs.contains_key(output) {
+                continue;
+            }
+            if let Some(idx) = our_added.iter().position(|added| *added == (input, output)) {
+                our_added.swap_remove(idx);
+                continue;
+            }
+            // When both sides connected an input that takes a single
+            // connection to different outputs, ours is kept.
+            let we_connected = our_diff
+                .added_connections
+                .iter()
+                .any(|id| ours[*id].input == input);
+            if we_connected
+                && ours.connection_count(input) == 1
+                && theirs.connection_count(connection.input) == 1
+            {
+                conflicts.push(MergeConflict::Connection(input));
+                continue;
+            }
+            let _ = graph.append_connection_with_data(output, input, connection.data.clone());
+        }
+
+        MergeResult {
+            graph,
+            conflicts,
+            their_nodes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    type TestGraph = Graph<(), u32, u32, u32>;
+
+    #[test]
+    fn test_merge() {
+        let mut base = TestGraph::new();
+        let a = add_node(&mut base, "a");
+        let b = add_node(&mut base, "b");
+        let a_in = base[a].get_input("in").unwrap();
+        let b_in = base[b].get_input("in").unwrap();
+
+        let mut ours = base.clone();
+        ours[a_in].value = 1;
+        ours[b_in].value = 2;
+        let our_node = add_node(&mut ours, "ours");
+
+        let mut theirs = base.clone();
+        theirs[a].label = "renamed".into();
+        theirs[b_in].value = 3;
+        let their_node = add_node(&mut theirs, "theirs");
+        let their_out = theirs[their_node].get_output("out").unwrap();
+        theirs.add_connection(their_out, a_in).unwrap();
+
+        let merged = TestGraph::merge(&base, &ours, &theirs);
+        let graph = &merged.graph;
+        assert_eq!(merged.conflicts, vec![MergeConflict::Value(b_in)]);
+        assert_eq!(graph[a].label, "renamed");
+        assert_eq!(graph[a_in].value, 1);
+        assert_eq!(graph[b_in].value, 2);
+        assert_eq!(graph[our_node].label, "ours");
+        let new_node = merged.their_nodes[&their_node];
+        assert_ne!(new_node, our_node);
+        assert_eq!(graph[new_node].label, "theirs");
+        let new_out = graph[new_node].get_output("out").unwrap();
+        assert_eq!(graph.connection(a_in), Some(new_out));
+    }
+
+    #[test]
+    fn test_merge_multi_input() {
+        let mut base = TestGraph::new();
+        let a = add_node(&mut base, "a");
+        let b = add_node(&mut base, "b");
+        let sum = add_node(&mut base, "sum");
+        let a_out = base[a].get_output("out").unwrap();
+        let b_out = base[b].get_output("out").unwrap();
+        let sum_in = base[sum].get_input("in").unwrap();
+        let a_edge = base.append_connection_with_data(a_out, sum_in, 1).unwrap();
+        base.append_connection_with_data(b_out, sum_in, 2).unwrap();
+
+        // We remove one connection and add another, they add a third one
+        let mut ours = base.clone();
+        ours.disconnect(a_edge);
+        let c = add_node(&mut ours, "c");
+        let c_out = ours[c].get_output("out").unwrap();
+        ours.append_connection_with_data(c_out, sum_in, 3).unwrap();
+
+        let mut theirs = base.clone();
+        let d = add_node(&mut theirs, "d");
+        let d_out = theirs[d].get_output("out").unwrap();
+        theirs
+            .append_connection_with_data(d_out, sum_in, 4)
+            .unwrap();
+
+        assert!(base.diff(&base.clone()).is_empty());
+        assert_eq!(base.diff(&theirs).added_connections.len(), 1);
+        assert!(base.diff(&theirs).removed_connections.is_empty());
+
+        let merged = TestGraph::merge(&base, &ours, &theirs);
+        let graph = &merged.graph;
+        assert!(merged.conflicts.is_empty());
+        let new_d_out = graph[merged.their_nodes[&d]].get_output("out").unwrap();
+        let connections: Vec<(OutputId, u32)> = graph
+            .connection_ids(sum_in)
+            .iter()
+            .map(|id| (graph[*id].output, graph[*id].data))
+            .collect();
+        assert_eq!(connections, vec![(b_out, 2), (c_out, 3), (new_d_out, 4)]);
+    }
+}
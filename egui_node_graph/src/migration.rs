@@ -0,0 +1,157 @@
+use std::collections::BTreeMap;
+
+use super::*;
+
+#[cfg(feature = "persistence")]
+use serde::{Deserialize, Serialize};
+
+/// A saved document, tagged with the version of its format. See
+/// [`Migrations`].
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "persistence", derive(Serialize, Deserialize))]
+pub struct Versioned<T> {
+    pub version: u32,
+    pub data: T,
+}
+
+type UpgradeFn<Value> = Box<dyn Fn(Value) -> Result<Value, String>>;
+
+/// Upgrades saved documents to the current format, so old saves keep opening
+/// as the serialized layout of the graph evolves.
+///
+/// The migrations work on `Value`, the untyped representation of the
+/// serialization format, e.g. `serde_json::Value` or `ron::Value`. Documents
+/// are saved as a [`Versioned`] with the current version, and loaded as a
+/// `Versioned<Value>`, which [`Migrations::load`] upgrades before it's turned
+/// into the editor state.
+///
+/// ```ignore
+/// let migrations = Migrations::new()
+///     // Version 0 stored the zoom at the top level.
+///     .register(0, |mut doc: serde_json::Value| {
+///         let zoom = doc["zoom"].take();
+///         doc["pan_zoom"]["zoom"] = zoom;
+///         Ok(doc)
+///     });
+///
+/// let saved = serde_json::to_string(&migrations.versioned(&state))?;
+/// let loaded: Versioned<serde_json::Value> = serde_json::from_str(&saved)?;
+/// let state: MyEditorState = serde_json::from_value(migrations.load(loaded)?)?;
+/// ```
+pub struct Migrations<Value> {
+    upgrades: BTreeMap<u32, UpgradeFn<Value>>,
+}
+
+impl<Value> Default for Migrations<Value> {
+    fn default() -> Self {
+        Self {
+            upgrades: BTreeMap::new(),
+        }
+    }
+}
+
+impl<Value> Migrations<Value> {
+    /// Creates a registry without migrations, where the current version is 0.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the function that upgrades documents from `version` to
+    /// `version + 1`. The current version is the one after the last upgrade.
+    pub fn register(
+        mut self,
+        version: u32,
+        upgrade: impl Fn(Value) -> Result<Value, String> + 'static,
+    ) -> Self {
+        self.upgrades.insert(version, Box::new(upgrade));
+        self
+    }
+
+    /// The version documents are saved with.
+    pub fn current_version(&self) -> u32 {
+        self.upgrades
+            .keys()
+            .next_back()
+            .map_or(0, |version| version + 1)
+    }
+
+    /// Tags `data` with the current version, to be saved.
+    pub fn versioned<T>(&self, data: T) -> Versioned<T> {
+        Versioned {
+            version: self.current_version(),
+            data,
+        }
+    }
+
+    /// Runs the upgrades from the version of the document to the current
+    /// one, in order.
+    pub fn load(&self, document: Versioned<Value>) -> Result<Value, EguiGraphError> {
+        let current = self.current_version();
+        if document.version > current {
+            return Err(EguiGraphError::UnsupportedVersion(
+                document.version,
+                current,
+            ));
+        }
+        let mut value = document.data;
+        for version in document.version..current {
+            let upgrade = self
+                .upgrades
+                .get(&version)
+                .ok_or(EguiGraphError::MissingMigration(version))?;
+            value = upgrade(value).map_err(|err| EguiGraphError::MigrationFailed(version, err))?;
+        }
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Each upgrade appends its version, so the result shows which upgrades
+    /// ran, and in which order.
+    fn migrations() -> Migrations<Vec<u32>> {
+        // Registered out of order on purpose.
+        Migrations::new()
+            .register(1, |mut doc: Vec<u32>| {
+                doc.push(1);
+                Ok(doc)
+            })
+            .register(0, |mut doc: Vec<u32>| {
+                doc.push(0);
+                Ok(doc)
+            })
+            .register(2, |doc: Vec<u32>| match doc.is_empty() {
+                true => Err("empty document".into()),
+                false => Ok([doc, vec![2]].concat()),
+            })
+    }
+
+    #[test]
+    fn test_load() {
+        let migrations = migrations();
+        assert_eq!(migrations.current_version(), 3);
+        assert_eq!(migrations.versioned(()).version, 3);
+
+        let load = |version, data| migrations.load(Versioned { version, data });
+        assert_eq!(load(0, vec![]).unwrap(), vec![0, 1, 2]);
+        assert_eq!(load(2, vec![9]).unwrap(), vec![9, 2]);
+        assert_eq!(load(3, vec![9]).unwrap(), vec![9]);
+        assert!(matches!(
+            load(4, vec![]),
+            Err(EguiGraphError::UnsupportedVersion(4, 3))
+        ));
+        assert!(matches!(
+            load(2, vec![]),
+            Err(EguiGraphError::MigrationFailed(2, _))
+        ));
+        assert!(matches!(
+            Migrations::new().register(1, Ok).load(Versioned {
+                version: 0,
+                data: ()
+            }),
+            Err(EguiGraphError::MissingMigration(0))
+        ));
+    }
+}
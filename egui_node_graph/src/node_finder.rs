@@ -1,6 +1,6 @@
 use std::{collections::BTreeMap, marker::PhantomData};
 
-use crate::{color_hex_utils::*, CategoryTrait, NodeTemplateIter, NodeTemplateTrait};
+use crate::{AnyParameterId, CategoryTrait, LayoutDirection, NodeTemplateIter, NodeTemplateTrait};
 
 use egui::*;
 
@@ -11,9 +11,42 @@ pub struct NodeFinder<NodeTemplate> {
     /// Reset every frame. When set, the node finder will be moved at that position
     pub position: Option<Pos2>,
     pub just_spawned: bool,
+    /// When set, the finder was opened from this port. Only the templates
+    /// with a compatible port are listed, and the created node gets connected
+    /// to it.
+    pub source_port: Option<AnyParameterId>,
+    /// The most results requested from [`NodeTemplateIter::query`].
+    #[cfg_attr(feature = "persistence", serde(default = "default_query_limit"))]
+    pub query_limit: usize,
+    /// The results of [`NodeTemplateIter::query`], and the query they are
+    /// for.
+    #[cfg_attr(feature = "persistence", serde(skip, default = "Option::default"))]
+    query_results: Option<(String, Vec<NodeTemplate>)>,
+    /// The direction of the entries, set by the editor from
+    /// [`crate::EditorSettings::layout_direction`].
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub layout_direction: LayoutDirection,
+    /// The placeholder of the search field, set by the editor from
+    /// [`crate::EditorStrings::finder_hint`].
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub hint_text: String,
+    /// The index of the entry highlighted with the arrow keys, which is
+    /// created when pressing enter.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub highlighted: usize,
+    /// The verdicts of the filter of [`NodeFinder::show_filtered`] for each
+    /// listed template, by position, and the query of the listed templates,
+    /// or `None` when they aren't search results.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    filter_verdicts: Option<(Option<String>, Vec<bool>)>,
     _phantom: PhantomData<NodeTemplate>,
 }
 
+#[cfg(feature = "persistence")]
+fn default_query_limit() -> usize {
+    100
+}
+
 impl<NodeTemplate, NodeData, UserState, CategoryType> NodeFinder<NodeTemplate>
 where
     NodeTemplate:
@@ -25,10 +58,26 @@ where
             query: "".into(),
             position: Some(pos),
             just_spawned: true,
+            source_port: None,
+            query_limit: 100,
+            query_results: None,
+            layout_direction: LayoutDirection::LeftToRight,
+            hint_text: String::new(),
+            highlighted: 0,
+            filter_verdicts: None,
             _phantom: Default::default(),
         }
     }
 
+    /// Creates a finder that only lists the templates which can be connected
+    /// to `port`. See [`NodeFinder::source_port`].
+    pub fn new_for_port(pos: Pos2, port: AnyParameterId) -> Self {
+        NodeFinder {
+            source_port: Some(port),
+            ..Self::new_at(pos)
+        }
+    }
+
     /// Shows the node selector panel with a search bar. Returns whether a node
     /// archetype was selected and, in that case, the finder should be hidden on
     /// the next frame.
@@ -38,16 +87,32 @@ where
         all_kinds: impl NodeTemplateIter<Item = NodeTemplate>,
         user_state: &mut UserState,
     ) -> Option<NodeTemplate> {
-        let background_color;
-        let text_color;
-
-        if ui.visuals().dark_mode {
-            background_color = color_from_hex("#3f3f3f").unwrap();
-            text_color = color_from_hex("#fefefe").unwrap();
-        } else {
-            background_color = color_from_hex("#fefefe").unwrap();
-            text_color = color_from_hex("#3f3f3f").unwrap();
-        }
+        self.show_filtered(ui, all_kinds, user_state, |_, _| true)
+    }
+
+    /// Like [`NodeFinder::show`], but only lists the templates for which
+    /// `filter` returns true. The filter is only called for the templates
+    /// the finder lists, once each until the listed templates change, so it
+    /// can be expensive.
+    pub fn show_filtered(
+        &mut self,
+        ui: &mut Ui,
+        all_kinds: impl NodeTemplateIter<Item = NodeTemplate>,
+        user_state: &mut UserState,
+        mut filter: impl FnMut(&NodeTemplate, &mut UserState) -> bool,
+    ) -> Option<NodeTemplate> {
+        let query_results = match &self.query_results {
+            Some((query, results)) if *query == self.query => Some(results.clone()),
+            _ => {
+                let results = all_kinds.query(&self.query, self.query_limit);
+                self.query_results = results.clone().map(|results| (self.query.clone(), results));
+                results
+            }
+        };
+        let list_key = query_results.as_ref().map(|_| self.query.clone());
+
+        let background_color = ui.visuals().window_fill();
+        let text_color = ui.visuals().text_color();
 
         ui.visuals_mut().widgets.noninteractive.fg_stroke = Stroke::new(2.0, text_color);
 
@@ -58,33 +123,83 @@ where
         // The archetype that will be returned.
         let mut submitted_archetype = None;
         frame.show(ui, |ui| {
-            ui.vertical(|ui| {
-                let resp = ui.text_edit_singleline(&mut self.query);
+            ui.with_layout(self.layout_direction.vertical(), |ui| {
+                // The arrow keys move the highlighted entry. They're consumed
+                // before the search field, so they don't move its cursor.
+                let highlight_delta = ui.input_mut(|i| {
+                    i.consume_key(Modifiers::NONE, Key::ArrowDown) as isize
+                        - i.consume_key(Modifiers::NONE, Key::ArrowUp) as isize
+                });
+                let resp = ui.add(TextEdit::singleline(&mut self.query).hint_text(&self.hint_text));
                 if self.just_spawned {
                     resp.request_focus();
                     self.just_spawned = false;
                 }
                 let update_open = resp.changed();
+                if update_open {
+                    self.highlighted = 0;
+                }
 
-                let mut query_submit = resp.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter));
+                let query_submit = resp.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter));
 
                 let max_height = ui.input(|i| i.screen_rect.height() * 0.5);
                 let scroll_area_width = resp.rect.width() - 30.0;
 
-                let all_kinds = all_kinds.all_kinds();
-                let mut categories: BTreeMap<String, Vec<&NodeTemplate>> = Default::default();
-                let mut orphan_kinds = Vec::new();
-
-                for kind in &all_kinds {
-                    let kind_categories = kind.node_finder_categories(user_state);
-
-                    if kind_categories.is_empty() {
-                        orphan_kinds.push(kind);
-                    } else {
-                        for category in kind_categories {
-                            categories.entry(category.name()).or_default().push(kind);
-                        }
+                let searched = query_results.is_some();
+                let candidates = query_results.unwrap_or_else(|| all_kinds.all_kinds());
+                let verdicts = match self.filter_verdicts.take() {
+                    Some((key, verdicts))
+                        if key == list_key && verdicts.len() == candidates.len() =>
+                    {
+                        verdicts
                     }
+                    _ => candidates
+                        .iter()
+                        .map(|kind| filter(kind, user_state))
+                        .collect(),
+                };
+                let all_kinds: Vec<NodeTemplate> = candidates
+                    .into_iter()
+                    .zip(&verdicts)
+                    .filter(|(_, keep)| **keep)
+                    .map(|(kind, _)| kind)
+                    .collect();
+                self.filter_verdicts = Some((list_key, verdicts));
+                // Search results are listed in their order, and the matches of
+                // a query are listed best first, without categories.
+                let (categories, orphan_kinds): (_, Vec<&NodeTemplate>) = if searched {
+                    (BTreeMap::new(), all_kinds.iter().collect())
+                } else if !self.query.is_empty() {
+                    let mut scored: Vec<(i32, &NodeTemplate)> = all_kinds
+                        .iter()
+                        .filter_map(|kind| {
+                            Some((query_score(kind, &self.query, user_state)?, kind))
+                        })
+                        .collect();
+                    scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+                    (
+                        BTreeMap::new(),
+                        scored.into_iter().map(|(_, kind)| kind).collect(),
+                    )
+                } else {
+                    group_by_category(&all_kinds, user_state)
+                };
+
+                // The entries in the order they are listed, which the arrow
+                // keys move through.
+                let entries: Vec<&NodeTemplate> = categories
+                    .values()
+                    .flatten()
+                    .chain(&orphan_kinds)
+                    .copied()
+                    .collect();
+                let highlighted = (self.highlighted as isize + highlight_delta)
+                    .clamp(0, entries.len().saturating_sub(1) as isize)
+                    as usize;
+                self.highlighted = highlighted;
+                let navigated = highlight_delta != 0;
+                if query_submit {
+                    submitted_archetype = entries.get(highlighted).map(|kind| (*kind).clone());
                 }
 
                 Frame::default()
@@ -94,52 +209,47 @@ where
                             .max_height(max_height)
                             .show(ui, |ui| {
                                 ui.set_width(scroll_area_width);
+                                let mut idx = 0;
                                 for (category, kinds) in categories {
-                                    let filtered_kinds: Vec<_> = kinds
-                                        .into_iter()
-                                        .map(|kind| {
+                                    let start = idx;
+                                    idx += kinds.len();
+                                    let contains_highlighted = (start..idx).contains(&highlighted);
+                                    let open = if update_open {
+                                        Some(false)
+                                    } else if navigated && contains_highlighted {
+                                        Some(true)
+                                    } else {
+                                        None
+                                    };
+
+                                    CollapsingHeader::new(&category).open(open).show(ui, |ui| {
+                                        for (i, kind) in kinds.into_iter().enumerate() {
                                             let kind_name =
                                                 kind.node_finder_label(user_state).to_string();
-                                            (kind, kind_name)
-                                        })
-                                        .filter(|(_kind, kind_name)| {
-                                            kind_name
-                                                .to_lowercase()
-                                                .contains(self.query.to_lowercase().as_str())
-                                        })
-                                        .collect();
-
-                                    if !filtered_kinds.is_empty() {
-                                        let default_open = !self.query.is_empty();
-
-                                        CollapsingHeader::new(&category)
-                                            .default_open(default_open)
-                                            .open(update_open.then_some(default_open))
-                                            .show(ui, |ui| {
-                                                for (kind, kind_name) in filtered_kinds {
-                                                    if ui
-                                                        .selectable_label(false, kind_name)
-                                                        .clicked()
-                                                    {
-                                                        submitted_archetype = Some(kind.clone());
-                                                    } else if query_submit {
-                                                        submitted_archetype = Some(kind.clone());
-                                                        query_submit = false;
-                                                    }
-                                                }
-                                            });
-                                    }
+                                            let is_highlighted = start + i == highlighted;
+                                            let resp =
+                                                ui.selectable_label(is_highlighted, kind_name);
+                                            if navigated && is_highlighted {
+                                                resp.scroll_to_me(None);
+                                            }
+                                            if resp.clicked() {
+                                                submitted_archetype = Some(kind.clone());
+                                            }
+                                        }
+                                    });
                                 }
 
                                 for kind in orphan_kinds {
                                     let kind_name = kind.node_finder_label(user_state).to_string();
-
-                                    if ui.selectable_label(false, kind_name).clicked() {
-                                        submitted_archetype = Some(kind.clone());
-                                    } else if query_submit {
+                                    let is_highlighted = idx == highlighted;
+                                    let resp = ui.selectable_label(is_highlighted, kind_name);
+                                    if navigated && is_highlighted {
+                                        resp.scroll_to_me(None);
+                                    }
+                                    if resp.clicked() {
                                         submitted_archetype = Some(kind.clone());
-                                        query_submit = false;
                                     }
+                                    idx += 1;
                                 }
                             });
                     });
@@ -149,3 +259,123 @@ where
         submitted_archetype
     }
 }
+
+/// Groups templates by their [`NodeTemplateTrait::node_finder_categories`],
+/// sorted by category name. Templates in several categories are listed in
+/// each of them. Also returns the templates without a category.
+pub fn group_by_category<'a, NodeTemplate>(
+    kinds: &'a [NodeTemplate],
+    user_state: &mut NodeTemplate::UserState,
+) -> (
+    BTreeMap<String, Vec<&'a NodeTemplate>>,
+    Vec<&'a NodeTemplate>,
+)
+where
+    NodeTemplate: NodeTemplateTrait,
+    NodeTemplate::CategoryType: CategoryTrait,
+{
+    let mut categories: BTreeMap<String, Vec<&NodeTemplate>> = BTreeMap::new();
+    let mut orphan_kinds = Vec::new();
+    for kind in kinds {
+        let kind_categories = kind.node_finder_categories(user_state);
+        if kind_categories.is_empty() {
+            orphan_kinds.push(kind);
+        }
+        for category in kind_categories {
+            categories.entry(category.name()).or_default().push(kind);
+        }
+    }
+    (categories, orphan_kinds)
+}
+
+/// Returns whether the label or one of the [`NodeTemplateTrait::tags`] of a
+/// template match the search `query`, see [`fuzzy_score`].
+pub fn matches_query<NodeTemplate: NodeTemplateTrait>(
+    kind: &NodeTemplate,
+    query: &str,
+    user_state: &mut NodeTemplate::UserState,
+) -> bool {
+    query_score(kind, query, user_state).is_some()
+}
+
+/// Returns the best [`fuzzy_score`] of the label and the
+/// [`NodeTemplateTrait::tags`] of a template for the search `query`.
+pub fn query_score<NodeTemplate: NodeTemplateTrait>(
+    kind: &NodeTemplate,
+    query: &str,
+    user_state: &mut NodeTemplate::UserState,
+) -> Option<i32> {
+    let label_score = fuzzy_score(&kind.node_finder_label(user_state), query);
+    kind.tags(user_state)
+        .iter()
+        .filter_map(|tag| fuzzy_score(tag, query))
+        .chain(label_score)
+        .max()
+}
+
+/// Scores how well `text` matches the search `query`, ignoring case and the
+/// spaces of the query. The characters of the query must appear in the text
+/// in the same order, but not necessarily next to each other, so `"vsc"`
+/// matches `"Vector times scalar"`. Runs of consecutive characters and
+/// matches at the start of words score higher. Returns `None` when the text
+/// doesn't match.
+pub fn fuzzy_score(text: &str, query: &str) -> Option<i32> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let query: Vec<char> = query
+        .to_lowercase()
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect();
+    // Matches the rest of the query greedily, after its first character is
+    // matched at `start`.
+    let score_from = |start: usize| {
+        let mut score = 0;
+        let mut next = 0;
+        for (i, c) in query.iter().enumerate() {
+            let found = if i == 0 {
+                start
+            } else {
+                next + text[next..].iter().position(|t| t == c)?
+            };
+            score += 1;
+            if found > 0 && found == next {
+                score += 5;
+            }
+            if found == 0 || !text[found - 1].is_alphanumeric() {
+                score += 3;
+            }
+            // Skipped characters cost a little, up to a point
+            score -= (found - next).min(3) as i32;
+            next = found + 1;
+        }
+        Some(score)
+    };
+    match query.first() {
+        Some(first) => (0..text.len())
+            .filter(|&start| text[start] == *first)
+            .filter_map(score_from)
+            .max(),
+        None => Some(0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_score() {
+        assert_eq!(fuzzy_score("Add scalar", ""), Some(0));
+        assert!(fuzzy_score("Add scalar", "adsc").is_some());
+        assert!(fuzzy_score("Add scalar", "Add Scalar").is_some());
+        assert_eq!(fuzzy_score("Add scalar", "ss"), None);
+        assert_eq!(fuzzy_score("Add scalar", "addx"), None);
+
+        // Prefixes beat matches in the middle, which beat scattered ones
+        let prefix = fuzzy_score("Scalar", "sca").unwrap();
+        let middle = fuzzy_score("Vector times scalar", "sca").unwrap();
+        let scattered = fuzzy_score("Sine curve amplitude", "sca").unwrap();
+        assert!(prefix > middle);
+        assert!(middle > scattered);
+    }
+}
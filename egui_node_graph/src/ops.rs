@@ -0,0 +1,534 @@
+use std::collections::{HashMap, HashSet};
+
+use super::*;
+
+#[cfg(feature = "persistence")]
+use serde::{Deserialize, Serialize};
+
+/// An id that is unique across all the replicas of a document. It identifies
+/// the nodes created through [`GraphOp`]s, and doubles as a Lamport timestamp
+/// to order concurrent changes: later counters win, and ties are broken by
+/// the replica.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "persistence", derive(Serialize, Deserialize))]
+pub struct OpId {
+    pub counter: u64,
+    pub replica: u64,
+}
+
+/// Refers to a parameter of a node by name, so it means the same on every
+/// replica, unlike the local [`InputId`]s and [`OutputId`]s.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "persistence", derive(Serialize, Deserialize))]
+pub struct ParamRef {
+    pub node: OpId,
+    pub name: String,
+}
+
+/// An input parameter of a node created by [`GraphOp::InsertNode`].
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "persistence", derive(Serialize, Deserialize))]
+pub struct InputOp<DataType, ValueType> {
+    pub name: String,
+    pub typ: DataType,
+    pub value: ValueType,
+    pub kind: InputParamKind,
+    pub shown_inline: bool,
+}
+
+/// A mutation of the graph that can be sent to the other replicas of the
+/// document. Operations commute: replicas that applied the same set of
+/// operations end up with the same graph, in whichever order they arrived.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "persistence", derive(Serialize, Deserialize))]
+pub enum GraphOp<NodeData, DataType, ValueType> {
+    /// Creates a node. Inserting the same node twice, or a node that was
+    /// already removed, does nothing.
+    InsertNode {
+        id: OpId,
+        label: String,
+        user_data: NodeData,
+        #[cfg_attr(feature = "persistence", serde(default))]
+        template_version: u32,
+        inputs: Vec<InputOp<DataType, ValueType>>,
+        outputs: Vec<(String, DataType)>,
+    },
+    /// Removes a node. Removals always win over the other operations on the
+    /// same node.
+    RemoveNode { id: OpId },
+    /// Sets the value of an input. The operation with the latest `stamp`
+    /// wins.
+    SetValue {
+        input: ParamRef,
+        value: ValueType,
+        stamp: OpId,
+    },
+    /// Connects an input to `outputs`, in order, replacing all of its
+    /// connections. An empty list disconnects it. The operation with the
+    /// latest `stamp` wins.
+    Connect {
+        input: ParamRef,
+        outputs: Vec<ParamRef>,
+        stamp: OpId,
+    },
+}
+
+/// Turns the mutations of a local [`Graph`] into [`GraphOp`]s, and applies
+/// the operations received from other replicas to it.
+///
+/// The host mutates the graph as usual, then calls the matching method here
+/// (e.g. [`OpReplica::insert_node`] after a node was added) and sends the
+/// returned operation to the other replicas, which pass it to
+/// [`OpReplica::apply`]. Operations may arrive in any order: the ones that
+/// refer to nodes that aren't known yet are kept, and take effect once the
+/// node is inserted.
+///
+/// Node positions, port sides, bidirectional params and param groups are not
+/// replicated.
+#[derive(Clone, Debug)]
+pub struct OpReplica<ValueType> {
+    replica: u64,
+    clock: u64,
+    nodes: HashMap<OpId, NodeId>,
+    op_ids: SecondaryMap<NodeId, OpId>,
+    removed: HashSet<OpId>,
+    values: HashMap<ParamRef, (OpId, ValueType)>,
+    connections: HashMap<ParamRef, (OpId, Vec<ParamRef>)>,
+    /// The last value of each local input that was sent or received, to
+    /// find the values changed by the widgets.
+    synced_values: SecondaryMap<InputId, ValueType>,
+}
+
+impl<ValueType: Clone> OpReplica<ValueType> {
+    /// Creates an empty replica. Each replica of a document needs a
+    /// different `replica` id, e.g. a random number or one assigned by the
+    /// server.
+    pub fn new(replica: u64) -> Self {
+        Self {
+            replica,
+            clock: 0,
+            nodes: HashMap::new(),
+            op_ids: SecondaryMap::new(),
+            removed: HashSet::new(),
+            values: HashMap::new(),
+            connections: HashMap::new(),
+            synced_values: SecondaryMap::new(),
+        }
+    }
+
+    /// Returns the local id of a node created through an operation.
+    pub fn node_id(&self, id: OpId) -> Option<NodeId> {
+        self.nodes.get(&id).copied()
+    }
+
+    /// Returns the id shared by all the replicas for a local node.
+    pub fn op_id(&self, node_id: NodeId) -> Option<OpId> {
+        self.op_ids.get(node_id).copied()
+    }
+
+    fn next_id(&mut self) -> OpId {
+        self.clock += 1;
+        OpId {
+            counter: self.clock,
+            replica: self.replica,
+        }
+    }
+
+    fn param_ref<NodeData, DataType, ConnectionData>(
+        &self,
+        graph: &Graph<NodeData, DataType, ValueType, ConnectionData>,
+        param: AnyParameterId,
+    ) -> Option<ParamRef> {
+        let (node_id, name) = match param {
+            AnyParameterId::Input(input) => {
+                let node_id = graph.try_get_input(input)?.node;
+                let (name, _) = graph[node_id].inputs.iter().find(|(_, id)| *id == input)?;
+                (node_id, name)
+            }
+            AnyParameterId::Output(output) => {
+                let node_id = graph.try_get_output(output)?.node;
+                let (name, _) = graph[node_id]
+                    .outputs
+                    .iter()
+                    .find(|(_, id)| *id == output)?;
+                (node_id, name)
+            }
+        };
+        Some(ParamRef {
+            node: self.op_id(node_id)?,
+            name: name.clone(),
+        })
+    }
+
+    /// Returns the operation that creates a node that was added to the local
+    /// graph. The node gets a new [`OpId`].
+    pub fn insert_node<NodeData: Clone, DataType: Clone, ConnectionData>(
+        &mut self,
+        graph: &Graph<NodeData, DataType, ValueType, ConnectionData>,
+        node_id: NodeId,
+    ) -> GraphOp<NodeData, DataType, ValueType> {
+        let id = self.next_id();
+        self.nodes.insert(id, node_id);
+        self.op_ids.insert(node_id, id);
+
+        let node = &graph[node_id];
+        GraphOp::InsertNode {
+            id,
+            label: node.label.clone(),
+            user_data: node.user_data.clone(),
+            template_version: node.template_version,
+            inputs: node
+                .inputs
+                .iter()
+                .map(|(name, input)| {
+                    let param = &graph[*input];
+                    InputOp {
+                        name: name.clone(),
+                        typ: param.typ.clone(),
+                        value: param.value.clone(),
+                        kind: param.kind,
+                        shown_inline: param.shown_inline,
+                    }
+                })
+                .collect(),
+            outputs: node
+                .outputs
+                .iter()
+                .map(|(name, output)| (name.clone(), graph[*output].typ.clone()))
+                .collect(),
+        }
+    }
+
+    /// Returns the operation that removes a node, which was either removed
+    /// from the local graph already or is about to be. Returns `None` for
+    /// nodes that weren't created through an operation.
+    pub fn remove_node<NodeData, DataType>(
+        &mut self,
+        node_id: NodeId,
+    ) -> Option<GraphOp<NodeData, DataType, ValueType>> {
+        let id = self.op_ids.remove(node_id)?;
+        self.nodes.remove(&id);
+        self.removed.insert(id);
+        Some(GraphOp::RemoveNode { id })
+    }
+
+    /// Returns the operation that sets an input to its current value in the
+    /// local graph.
+    pub fn set_value<NodeData, DataType, ConnectionData>(
+        &mut self,
+        graph: &Graph<NodeData, DataType, ValueType, ConnectionData>,
+        input: InputId,
+    ) -> Option<GraphOp<NodeData, DataType, ValueType>> {
+        let input_ref = self.param_ref(graph, AnyParameterId::Input(input))?;
+        let stamp = self.next_id();
+        let value = graph[input].value.clone();
+        self.synced_values.insert(input, value.clone());
+        self.values
+            .insert(input_ref.clone(), (stamp, value.clone()));
+        Some(GraphOp::SetValue {
+            input: input_ref,
+            value,
+            stamp,
+        })
+    }
+
+    /// Returns the operation that connects an input to its current outputs
+    /// in the local graph, or disconnects it.
+    pub fn set_connection<NodeData, DataType, ConnectionData>(
+        &mut self,
+        graph: &Graph<NodeData, DataType, ValueType, ConnectionData>,
+        input: InputId,
+    ) -> Option<GraphOp<NodeData, DataType, ValueType>> {
+        let input_ref = self.param_ref(graph, AnyParameterId::Input(input))?;
+        let output_refs = graph
+            .connection_ids(input)
+            .iter()
+            .map(|id| self.param_ref(graph, AnyParameterId::Output(graph[*id].output)))
+            .collect::<Option<Vec<_>>>()?;
+        let stamp = self.next_id();
+        self.connections
+            .insert(input_ref.clone(), (stamp, output_refs.clone()));
+        Some(GraphOp::Connect {
+            input: input_ref,
+            outputs: output_refs,
+            stamp,
+        })
+    }
+
+    /// Applies an operation received from another replica to the local
+    /// graph. Applying an operation more than once has no further effect.
+    pub fn apply<NodeData, DataType, ConnectionData>(
+        &mut self,
+        graph: &mut Graph<NodeData, DataType, ValueType, ConnectionData>,
+        op: GraphOp<NodeData, DataType, ValueType>,
+    ) where
+        ConnectionData: Default,
+    {
+        match op {
+            GraphOp::InsertNode {
+                id,
+                label,
+                user_data,
+                template_version,
+                inputs,
+                outputs,
+            } => {
+                self.clock = self.clock.max(id.counter);
+                if self.nodes.contains_key(&id) || self.removed.contains(&id) {
+                    return;
+                }
+                let node_id = graph.add_node(label, user_data, |graph, node_id| {
+                    for input in inputs {
+                        graph.add_input_param(
+                            node_id,
+                            input.name,
+                            input.typ,
+                            input.value,
+                            input.kind,
+                            input.shown_inline,
+                        );
+                    }
+                    for (name, typ) in outputs {
+                        graph.add_output_param(node_id, name, typ);
+                    }
+                    graph[node_id].template_version = template_version;
+                });
+                self.nodes.insert(id, node_id);
+                self.op_ids.insert(node_id, id);
+
+                // Catch up with the operations that arrived before the node.
+                for (input_ref, (_, value)) in &self.values {
+                    if input_ref.node == id {
+                        if let Some(input) = self.local_input(graph, input_ref) {
+                            graph[input].value = value.clone();
+                            self.synced_values.insert(input, value.clone());
+                        }
+                    }
+                }
+                let pending: Vec<ParamRef> = self
+                    .connections
+                    .iter()
+                    .filter(|(input_ref, (_, output_refs))| {
+                        input_ref.node == id || output_refs.iter().any(|output| output.node == id)
+                    })
+                    .map(|(input_ref, _)| input_ref.clone())
+                    .collect();
+                for input_ref in pending {
+                    self.sync_connection(graph, &input_ref);
+                }
+            }
+            GraphOp::RemoveNode { id } => {
+                self.removed.insert(id);
+                if let Some(node_id) = self.nodes.remove(&id) {
+                    self.op_ids.remove(node_id);
+                    let _ = graph.remove_node(node_id);
+                }
+            }
+            GraphOp::SetValue {
+                input,
+                value,
+                stamp,
+            } => {
+                self.clock = self.clock.max(stamp.counter);
+                if matches!(self.values.get(&input), Some((latest, _)) if *latest >= stamp) {
+                    return;
+                }
+                if let Some(input_id) = self.local_input(graph, &input) {
+                    graph[input_id].value = value.clone();
+                    self.synced_values.insert(input_id, value.clone());
+                }
+                self.values.insert(input, (stamp, value));
+            }
+            GraphOp::Connect {
+                input,
+                outputs,
+                stamp,
+            } => {
+                self.clock = self.clock.max(stamp.counter);
+                if matches!(self.connections.get(&input), Some((latest, _)) if *latest >= stamp) {
+                    return;
+                }
+                self.connections.insert(input.clone(), (stamp, outputs));
+                self.sync_connection(graph, &input);
+            }
+        }
+    }
+
+    fn local_input<NodeData, DataType, ConnectionData>(
+        &self,
+        graph: &Graph<NodeData, DataType, ValueType, ConnectionData>,
+        input: &ParamRef,
+    ) -> Option<InputId> {
+        graph[self.node_id(input.node)?].get_input(&input.name).ok()
+    }
+
+    fn local_output<NodeData, DataType, ConnectionData>(
+        &self,
+        graph: &Graph<NodeData, DataType, ValueType, ConnectionData>,
+        output: &ParamRef,
+    ) -> Option<OutputId> {
+        graph[self.node_id(output.node)?]
+            .get_output(&output.name)
+            .ok()
+    }
+
+    /// Makes the connections of an input in the local graph match the latest
+    /// [`GraphOp::Connect`]. Outputs of nodes that don't exist yet are
+    /// connected once they're inserted.
+    fn sync_connection<NodeData, DataType, ConnectionData>(
+        &self,
+        graph: &mut Graph<NodeData, DataType, ValueType, ConnectionData>,
+        input_ref: &ParamRef,
+    ) where
+        ConnectionData: Default,
+    {
+        let input = match self.local_input(graph, input_ref) {
+            Some(input) => input,
+            None => return,
+        };
+        graph.remove_connection(input);
+        if let Some((_, output_refs)) = self.connections.get(input_ref) {
+            for output_ref in output_refs {
+                if let Some(output) = self.local_output(graph, output_ref) {
+                    let _ = graph.append_connection(output, input);
+                }
+            }
+        }
+    }
+}
+
+impl<ValueType: Clone + PartialEq> OpReplica<ValueType> {
+    /// Returns the operations for the inputs whose value changed in the local
+    /// graph since they were last sent or received. Values are edited by the
+    /// widgets of [`WidgetValueTrait`], which don't report their changes, so
+    /// this can be called once per frame to find them.
+    pub fn value_changes<NodeData, DataType, ConnectionData>(
+        &mut self,
+        graph: &Graph<NodeData, DataType, ValueType, ConnectionData>,
+    ) -> Vec<GraphOp<NodeData, DataType, ValueType>> {
+        let mut changed = Vec::new();
+        for (input_id, input) in &graph.inputs {
+            if !self.op_ids.contains_key(input.node) {
+                continue;
+            }
+            match self.synced_values.get(input_id) {
+                Some(value) if *value == input.value => {}
+                // Inputs seen for the first time come with their node.
+                None => {
+                    self.synced_values.insert(input_id, input.value.clone());
+                }
+                Some(_) => changed.push(input_id),
+            }
+        }
+        changed
+            .into_iter()
+            .filter_map(|input| self.set_value(graph, input))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    type TestGraph = Graph<(), u32, u32>;
+    type TestOp = GraphOp<(), u32, u32>;
+
+    /// The label, input value and connected nodes of each node, in a form
+    /// that can be compared across replicas.
+    fn snapshot(graph: &TestGraph, replica: &OpReplica<u32>) -> Vec<(String, u32, Vec<String>)> {
+        let mut nodes: Vec<_> = graph
+            .iter_nodes()
+            .map(|node_id| {
+                let input = graph[node_id].get_input("in").unwrap();
+                assert!(replica.op_id(node_id).is_some());
+                (
+                    graph[node_id].label.clone(),
+                    graph[input].value,
+                    connected_labels(graph, node_id),
+                )
+            })
+            .collect();
+        nodes.sort();
+        nodes
+    }
+
+    /// Makes a few changes on a replica, and returns the operations.
+    fn edit() -> (TestGraph, OpReplica<u32>, Vec<TestOp>) {
+        let mut graph = TestGraph::new();
+        let mut replica = OpReplica::new(1);
+        let mut ops = Vec::new();
+        let a = add_node(&mut graph, "a");
+        ops.push(replica.insert_node(&graph, a));
+        let b = add_node(&mut graph, "b");
+        ops.push(replica.insert_node(&graph, b));
+        let sum = add_node(&mut graph, "sum");
+        ops.push(replica.insert_node(&graph, sum));
+
+        let sum_in = graph[sum].get_input("in").unwrap();
+        for node_id in [a, b] {
+            let out = graph[node_id].get_output("out").unwrap();
+            graph.append_connection(out, sum_in).unwrap();
+        }
+        ops.extend(replica.set_connection(&graph, sum_in));
+        let a_in = graph[a].get_input("in").unwrap();
+        graph[a_in].value = 1;
+        ops.extend(replica.set_value(&graph, a_in));
+        graph[a_in].value = 2;
+        ops.extend(replica.set_value(&graph, a_in));
+        (graph, replica, ops)
+    }
+
+    fn replay(ops: impl IntoIterator<Item = TestOp>) -> (TestGraph, OpReplica<u32>) {
+        let mut graph = TestGraph::new();
+        let mut replica = OpReplica::new(2);
+        for op in ops {
+            replica.apply(&mut graph, op);
+        }
+        (graph, replica)
+    }
+
+    #[test]
+    fn test_ops_commute() {
+        let (graph, replica, ops) = edit();
+        let expected = snapshot(&graph, &replica);
+        assert_eq!(
+            expected[2],
+            ("sum".to_string(), 0, vec!["a".to_string(), "b".to_string()])
+        );
+
+        let (in_order, in_order_replica) = replay(ops.clone());
+        assert_eq!(snapshot(&in_order, &in_order_replica), expected);
+        // The connections arrive before the nodes they refer to, and the
+        // older value arrives last.
+        let (reversed, reversed_replica) = replay(ops.into_iter().rev());
+        assert_eq!(snapshot(&reversed, &reversed_replica), expected);
+    }
+
+    #[test]
+    fn test_ops_idempotent() {
+        let (graph, replica, ops) = edit();
+        let (twice, twice_replica) = replay(ops.iter().chain(&ops).cloned());
+        assert_eq!(snapshot(&twice, &twice_replica), snapshot(&graph, &replica));
+        assert_eq!(twice.connections.len(), 2);
+    }
+
+    #[test]
+    fn test_remove_wins() {
+        let (mut graph, mut replica, mut ops) = edit();
+        let a = graph.iter_nodes().find(|n| graph[*n].label == "a").unwrap();
+        let a_in = graph[a].get_input("in").unwrap();
+        graph[a_in].value = 3;
+        let set_value = replica.set_value(&graph, a_in).unwrap();
+        graph.remove_node(a).unwrap();
+        ops.push(replica.remove_node(a).unwrap());
+        ops.push(set_value);
+
+        // The removal arrives before the node and a later change to it.
+        let (late, late_replica) = replay(ops.into_iter().rev());
+        assert_eq!(snapshot(&late, &late_replica), snapshot(&graph, &replica));
+        assert_eq!(late.nodes.len(), 2);
+        assert_eq!(late.connections.len(), 1);
+    }
+}
@@ -0,0 +1,114 @@
+use std::{cmp::Reverse, collections::HashMap, f32::consts::TAU};
+
+use crate::NodeTemplateTrait;
+
+use egui::*;
+
+/// A circular quick menu with the most used node templates. Entries are
+/// picked by flicking the mouse towards them and releasing the gesture that
+/// opened the menu, which makes it a faster alternative to the [`NodeFinder`]
+/// for common templates.
+///
+/// [`NodeFinder`]: crate::NodeFinder
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+pub struct RadialMenu {
+    /// The screen position where the menu was opened.
+    pub center: Pos2,
+}
+
+impl RadialMenu {
+    /// Distance from the center of the menu to the entry labels.
+    const RADIUS: f32 = 90.0;
+    /// No entry is highlighted while the cursor is this close to the center.
+    const DEAD_ZONE: f32 = 20.0;
+
+    pub fn new_at(pos: Pos2) -> Self {
+        RadialMenu { center: pos }
+    }
+
+    /// Picks the entries to show in the menu. The most used templates, as
+    /// recorded in `usage`, come first. The remaining slots are filled in the
+    /// order given by `all_kinds`.
+    pub fn entries<NodeTemplate, UserState>(
+        all_kinds: Vec<NodeTemplate>,
+        usage: &HashMap<String, u32>,
+        max_entries: usize,
+        user_state: &mut UserState,
+    ) -> Vec<(NodeTemplate, String)>
+    where
+        NodeTemplate: NodeTemplateTrait<UserState = UserState>,
+    {
+        let mut entries: Vec<_> = all_kinds
+            .into_iter()
+            .map(|kind| {
+                let label = kind.node_finder_label(user_state).into_owned();
+                (kind, label)
+            })
+            .collect();
+        // NOTE: The sort is stable, so unused templates keep their order.
+        entries.sort_by_key(|(_, label)| Reverse(usage.get(label).copied().unwrap_or(0)));
+        entries.truncate(max_entries);
+        entries
+    }
+
+    /// Returns the index of the entry in the direction of `pointer`, if the
+    /// pointer is far enough from the center.
+    fn entry_at(&self, pointer: Pos2, num_entries: usize) -> Option<usize> {
+        let offset = pointer - self.center;
+        if num_entries == 0 || offset.length() < Self::DEAD_ZONE {
+            return None;
+        }
+        let slice = TAU / num_entries as f32;
+        // Entries start at the top of the circle and go clockwise.
+        let angle = (offset.angle() + TAU / 4.0).rem_euclid(TAU);
+        Some((angle / slice).round() as usize % num_entries)
+    }
+
+    /// Draws the menu. When `released` is true, the gesture that opened the
+    /// menu has ended, and the entry under the cursor (if any) is returned.
+    pub fn show<NodeTemplate>(
+        &self,
+        ctx: &Context,
+        entries: Vec<(NodeTemplate, String)>,
+        released: bool,
+    ) -> Option<NodeTemplate> {
+        let visuals = &ctx.style().visuals;
+        let background_color = visuals.window_fill().linear_multiply(0.87);
+        let text_color = visuals.text_color();
+        let highlight_color = visuals.widgets.hovered.bg_fill;
+
+        let painter = ctx.layer_painter(LayerId::new(Order::Foreground, Id::new("radial_menu")));
+        let pointer = ctx.pointer_hover_pos().unwrap_or(self.center);
+        let hovered = self.entry_at(pointer, entries.len());
+
+        painter.circle_filled(self.center, Self::RADIUS + 40.0, background_color);
+        painter.circle_stroke(
+            self.center,
+            Self::DEAD_ZONE,
+            Stroke::new(1.0, highlight_color),
+        );
+        if hovered.is_some() {
+            painter.line_segment([self.center, pointer], Stroke::new(2.0, highlight_color));
+        }
+
+        let font = TextStyle::Button.resolve(&ctx.style());
+        let slice = TAU / entries.len().max(1) as f32;
+        for (idx, (_, label)) in entries.iter().enumerate() {
+            let angle = idx as f32 * slice - TAU / 4.0;
+            let pos = self.center + Vec2::angled(angle) * Self::RADIUS;
+            let galley = painter.layout_no_wrap(label.clone(), font.clone(), text_color);
+            let rect = Rect::from_center_size(pos, galley.size());
+            if hovered == Some(idx) {
+                painter.rect_filled(rect.expand(4.0), 4.0, highlight_color);
+            }
+            painter.galley(rect.min, galley);
+        }
+
+        if released {
+            hovered.map(|idx| entries.into_iter().nth(idx).unwrap().0)
+        } else {
+            None
+        }
+    }
+}
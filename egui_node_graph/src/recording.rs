@@ -0,0 +1,217 @@
+use super::*;
+
+#[cfg(feature = "persistence")]
+use serde::{Deserialize, Serialize};
+
+/// The replica id of the editor states a recording is replayed onto, see
+/// [`GraphEditorState::start_sync`].
+pub const REPLAY_REPLICA: u64 = u64::MAX;
+
+/// A [`SyncOp`] and the time it happened at, in seconds.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "persistence", derive(Serialize, Deserialize))]
+pub struct RecordedOp<NodeData, DataType, ValueType> {
+    pub time: f64,
+    pub op: SyncOp<NodeData, DataType, ValueType>,
+}
+
+/// A recording of an editing session, which can be replayed onto a fresh
+/// editor state, e.g. to reproduce a bug or for tutorials and demos.
+///
+/// Recording builds on the operations used to mirror editors: start with the
+/// operations returned by [`GraphEditorState::start_sync`], then record the
+/// ones returned by [`GraphEditorState::sync_ops`] every frame.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "persistence", derive(Serialize, Deserialize))]
+pub struct SessionRecording<NodeData, DataType, ValueType> {
+    pub ops: Vec<RecordedOp<NodeData, DataType, ValueType>>,
+}
+
+impl<NodeData, DataType, ValueType> Default for SessionRecording<NodeData, DataType, ValueType> {
+    fn default() -> Self {
+        Self { ops: Vec::new() }
+    }
+}
+
+impl<NodeData, DataType, ValueType> SessionRecording<NodeData, DataType, ValueType>
+where
+    NodeData: Clone,
+    DataType: Clone,
+    ValueType: Clone + PartialEq,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the operations that happened at `time`, in seconds. This is
+    /// usually the time of the egui input, `ctx.input(|i| i.time)`.
+    pub fn record(
+        &mut self,
+        time: f64,
+        ops: impl IntoIterator<Item = SyncOp<NodeData, DataType, ValueType>>,
+    ) {
+        self.ops
+            .extend(ops.into_iter().map(|op| RecordedOp { time, op }));
+    }
+
+    /// The time between the first and the last recorded operation.
+    pub fn duration(&self) -> f64 {
+        match (self.ops.first(), self.ops.last()) {
+            (Some(first), Some(last)) => last.time - first.time,
+            _ => 0.0,
+        }
+    }
+
+    /// Applies the whole recording to `state` at once. The state should be
+    /// fresh, and is made to sync with [`REPLAY_REPLICA`].
+    pub fn replay<NodeTemplate, UserState, ConnectionData: Default>(
+        &self,
+        state: &mut GraphEditorState<
+            NodeData,
+            DataType,
+            ValueType,
+            NodeTemplate,
+            UserState,
+            ConnectionData,
+        >,
+    ) {
+        if state.sync.is_none() {
+            let _ = state.start_sync(REPLAY_REPLICA);
+        }
+        for recorded in &self.ops {
+            state.apply_op(recorded.op.clone());
+        }
+    }
+}
+
+/// Replays a [`SessionRecording`] over time, with the same pace it was
+/// recorded at, or faster or slower with [`SessionReplay::speed`].
+#[derive(Clone, Debug)]
+pub struct SessionReplay<NodeData, DataType, ValueType> {
+    pub recording: SessionRecording<NodeData, DataType, ValueType>,
+    /// How fast the recording is played. Defaults to 1.0.
+    pub speed: f64,
+    /// The index of the next operation to apply.
+    next: usize,
+    /// The time the replay started at, filled in on the first update.
+    start: Option<f64>,
+}
+
+impl<NodeData, DataType, ValueType> SessionReplay<NodeData, DataType, ValueType>
+where
+    NodeData: Clone,
+    DataType: Clone,
+    ValueType: Clone + PartialEq,
+{
+    pub fn new(recording: SessionRecording<NodeData, DataType, ValueType>) -> Self {
+        Self {
+            recording,
+            speed: 1.0,
+            next: 0,
+            start: None,
+        }
+    }
+
+    /// Applies the operations that are due at `time`, in seconds, to
+    /// `state`, which should be fresh when the replay starts. Call this every
+    /// frame, and request a repaint while it returns false. Returns true once
+    /// the whole recording was applied.
+    pub fn update<NodeTemplate, UserState, ConnectionData: Default>(
+        &mut self,
+        state: &mut GraphEditorState<
+            NodeData,
+            DataType,
+            ValueType,
+            NodeTemplate,
+            UserState,
+            ConnectionData,
+        >,
+        time: f64,
+    ) -> bool {
+        if state.sync.is_none() {
+            let _ = state.start_sync(REPLAY_REPLICA);
+        }
+        let start = *self.start.get_or_insert(time);
+        let first = match self.recording.ops.first() {
+            Some(first) => first.time,
+            None => return true,
+        };
+        let elapsed = (time - start) * self.speed;
+        while let Some(recorded) = self.recording.ops.get(self.next) {
+            if recorded.time - first > elapsed {
+                return false;
+            }
+            state.apply_op(recorded.op.clone());
+            self.next += 1;
+        }
+        true
+    }
+
+    /// Whether the whole recording was applied.
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.recording.ops.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use egui::{pos2, Pos2};
+
+    type TestState = GraphEditorState<(), u32, u32, (), ()>;
+
+    /// Records a node being created at the start, and moved two seconds
+    /// later.
+    fn record() -> SessionRecording<(), u32, u32> {
+        let mut state = TestState::default();
+        let node_id = state.graph.add_node("a".into(), (), |_, _| {});
+        state.node_order.push(node_id);
+        state.node_positions.insert(node_id, pos2(0.0, 0.0));
+
+        let mut recording = SessionRecording::new();
+        recording.record(5.0, state.start_sync(1));
+        let node = state.sync.as_ref().unwrap().op_id(node_id).unwrap();
+        recording.record(
+            7.0,
+            [SyncOp::MoveNode {
+                node,
+                pos: pos2(100.0, 0.0),
+            }],
+        );
+        recording
+    }
+
+    fn positions(state: &TestState) -> Vec<Pos2> {
+        state
+            .node_order
+            .iter()
+            .map(|node_id| state.node_positions[*node_id])
+            .collect()
+    }
+
+    #[test]
+    fn test_replay() {
+        let recording = record();
+        assert_eq!(recording.duration(), 2.0);
+
+        let mut state = TestState::default();
+        recording.replay(&mut state);
+        assert_eq!(positions(&state), vec![pos2(100.0, 0.0)]);
+    }
+
+    #[test]
+    fn test_replay_over_time() {
+        let mut replay = SessionReplay::new(record());
+        replay.speed = 2.0;
+        let mut state = TestState::default();
+
+        // The replay starts at the first update, whatever the time.
+        assert!(!replay.update(&mut state, 100.0));
+        assert_eq!(positions(&state), vec![pos2(0.0, 0.0)]);
+        assert!(!replay.update(&mut state, 100.5));
+        assert_eq!(positions(&state), vec![pos2(0.0, 0.0)]);
+        assert!(replay.update(&mut state, 101.0));
+        assert!(replay.is_finished());
+        assert_eq!(positions(&state), vec![pos2(100.0, 0.0)]);
+    }
+}
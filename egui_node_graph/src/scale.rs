@@ -0,0 +1,114 @@
+use egui::{
+    style::{Spacing, WidgetVisuals, Widgets},
+    Margin, Rounding, Stroke, Style, Vec2, Visuals,
+};
+
+/// Scales the sizes of egui styling types, used to draw the contents of the
+/// nodes at the zoom level of the graph.
+pub trait Scale {
+    fn scale(&mut self, amount: f32);
+
+    fn scaled(&self, amount: f32) -> Self
+    where
+        Self: Clone,
+    {
+        let mut scaled = self.clone();
+        scaled.scale(amount);
+        scaled
+    }
+}
+
+impl Scale for Vec2 {
+    fn scale(&mut self, amount: f32) {
+        *self *= amount;
+    }
+}
+
+impl Scale for Margin {
+    fn scale(&mut self, amount: f32) {
+        self.left *= amount;
+        self.right *= amount;
+        self.top *= amount;
+        self.bottom *= amount;
+    }
+}
+
+impl Scale for Rounding {
+    fn scale(&mut self, amount: f32) {
+        self.ne *= amount;
+        self.nw *= amount;
+        self.se *= amount;
+        self.sw *= amount;
+    }
+}
+
+impl Scale for Stroke {
+    fn scale(&mut self, amount: f32) {
+        self.width *= amount;
+    }
+}
+
+impl Scale for WidgetVisuals {
+    fn scale(&mut self, amount: f32) {
+        self.bg_stroke.scale(amount);
+        self.fg_stroke.scale(amount);
+        self.rounding.scale(amount);
+        self.expansion *= amount;
+    }
+}
+
+impl Scale for Widgets {
+    fn scale(&mut self, amount: f32) {
+        self.noninteractive.scale(amount);
+        self.inactive.scale(amount);
+        self.hovered.scale(amount);
+        self.active.scale(amount);
+        self.open.scale(amount);
+    }
+}
+
+impl Scale for Visuals {
+    fn scale(&mut self, amount: f32) {
+        self.widgets.scale(amount);
+        self.selection.stroke.scale(amount);
+        self.window_rounding.scale(amount);
+        self.window_stroke.scale(amount);
+        self.menu_rounding.scale(amount);
+    }
+}
+
+impl Scale for Spacing {
+    fn scale(&mut self, amount: f32) {
+        self.item_spacing.scale(amount);
+        self.window_margin.scale(amount);
+        self.button_padding.scale(amount);
+        self.menu_margin.scale(amount);
+        self.indent *= amount;
+        self.interact_size.scale(amount);
+        self.slider_width *= amount;
+        self.combo_width *= amount;
+        self.text_edit_width *= amount;
+        self.icon_width *= amount;
+        self.icon_width_inner *= amount;
+        self.icon_spacing *= amount;
+        self.tooltip_width *= amount;
+        self.combo_height *= amount;
+        self.scroll_bar_width *= amount;
+        self.scroll_handle_min_length *= amount;
+        self.scroll_bar_inner_margin *= amount;
+        self.scroll_bar_outer_margin *= amount;
+    }
+}
+
+impl Scale for Style {
+    fn scale(&mut self, amount: f32) {
+        if let Some(font_id) = &mut self.override_font_id {
+            font_id.size *= amount;
+        }
+        for font_id in self.text_styles.values_mut() {
+            font_id.size *= amount;
+        }
+        self.spacing.scale(amount);
+        self.visuals.scale(amount);
+    }
+}
@@ -0,0 +1,230 @@
+use egui::{Align, Key, Layout, Modifiers, Rect, Vec2};
+
+use crate::{EditorStrings, PortSide};
+
+/// The gestures that can open the [`crate::RadialMenu`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RadialMenuGesture {
+    /// Hold the key down over the editor, flick the mouse towards an entry
+    /// and release the key to create it.
+    HoldKey(Key),
+    /// Drag on the background with the secondary mouse button and release
+    /// over an entry to create it. A plain secondary click still opens the
+    /// node finder.
+    SecondaryDrag,
+}
+
+/// The direction text flows in the nodes and the node finder, see
+/// [`EditorSettings::layout_direction`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LayoutDirection {
+    #[default]
+    LeftToRight,
+    /// For right-to-left scripts like Arabic or Hebrew. Contents are aligned
+    /// to the right, and the ports on the left and right edges swap sides.
+    RightToLeft,
+}
+
+impl LayoutDirection {
+    /// A layout that places widgets in a row, starting on the side text
+    /// starts.
+    pub fn horizontal(self) -> Layout {
+        match self {
+            Self::LeftToRight => Layout::left_to_right(Align::Center),
+            Self::RightToLeft => Layout::right_to_left(Align::Center),
+        }
+    }
+
+    /// A layout that places widgets in a column, aligned to the side text
+    /// starts.
+    pub fn vertical(self) -> Layout {
+        match self {
+            Self::LeftToRight => Layout::top_down(Align::Min),
+            Self::RightToLeft => Layout::top_down(Align::Max),
+        }
+    }
+
+    /// The edge where a port set to `side` is drawn. Left and right are
+    /// swapped when right-to-left.
+    pub fn port_side(self, side: PortSide) -> PortSide {
+        match (self, side) {
+            (Self::RightToLeft, PortSide::Left) => PortSide::Right,
+            (Self::RightToLeft, PortSide::Right) => PortSide::Left,
+            _ => side,
+        }
+    }
+}
+
+/// What happens when a connection is made to an input that is already
+/// connected, see [`EditorSettings::occupied_input`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OccupiedInputPolicy {
+    /// The previous connection is removed, and reported with a
+    /// [`crate::NodeResponse::DisconnectEvent`].
+    #[default]
+    Replace,
+    /// The new connection is rejected. Occupied inputs don't accept drops.
+    Reject,
+    /// The new connection is added next to the previous ones, for inputs
+    /// that take the values of several outputs.
+    Append,
+}
+
+/// Behavior settings for the graph editor. These are configured by the
+/// application and are not part of the serialized editor state.
+#[derive(Clone, Debug)]
+pub struct EditorSettings {
+    /// The gesture used to open the radial quick menu. When `None`, the menu
+    /// is disabled.
+    pub radial_menu_gesture: Option<RadialMenuGesture>,
+    /// The maximum amount of templates shown in the radial quick menu.
+    pub radial_menu_size: usize,
+    /// When a connection is dropped on empty canvas, open the node finder
+    /// with the templates that can be connected to it.
+    pub finder_on_connection_drop: bool,
+    /// When set, the canvas is limited to this rect, in graph coordinates.
+    /// Panning and node dragging are kept inside it, and its border is
+    /// drawn in the background.
+    pub canvas_bounds: Option<Rect>,
+    /// Show scrollbars along the bottom and right edges of the editor, sized
+    /// by the extents of the graph relative to the visible part.
+    pub show_scrollbars: bool,
+    /// Show a minimap in a corner of the editor, with all the nodes and the
+    /// visible part of the graph. Clicking or dragging on it moves the
+    /// viewport there. Its look is set by [`crate::MinimapStyle`].
+    pub show_minimap: bool,
+    /// After a pan ends, the viewport keeps gliding and slows down with this
+    /// friction. Higher values stop it sooner. When `None`, the viewport
+    /// stops as soon as the pan ends.
+    pub kinetic_pan_friction: Option<f32>,
+    /// Duration, in seconds, of the viewport animations started by methods
+    /// like [`crate::GraphEditorState::center_on`]. Zero makes the viewport
+    /// jump instead.
+    pub viewport_animation_duration: f32,
+    /// Holding these modifiers while dragging on the background draws a
+    /// rectangle to zoom into, instead of a box selection. When `None`, the
+    /// zoom region tool is disabled.
+    pub zoom_region_modifiers: Option<Modifiers>,
+    /// Holding this key zooms out to show the whole graph, with the previous
+    /// viewport outlined. Clicking a spot goes back to the previous zoom
+    /// there, and releasing the key goes back to the previous viewport. When
+    /// `None`, the overview is disabled.
+    pub overview_key: Option<Key>,
+    /// Pressing this key fits the selected nodes in the editor, or all of
+    /// them when none are selected, see
+    /// [`crate::GraphEditorState::frame_nodes`]. When `None`, there's no
+    /// shortcut.
+    pub frame_key: Option<Key>,
+    /// The smallest zoom factor reachable by pinching or scrolling.
+    pub min_zoom: f32,
+    /// The largest zoom factor reachable by pinching or scrolling.
+    pub max_zoom: f32,
+    /// Double clicking a connection removes it, emitting a
+    /// [`crate::NodeResponse::DisconnectEvent`].
+    pub delete_connection_on_double_click: bool,
+    /// Clicking a connection selects it. The selected connection is removed
+    /// with the delete or backspace key, or by clicking the button drawn at
+    /// its middle, emitting a [`crate::NodeResponse::DisconnectEvent`].
+    pub select_connections: bool,
+    /// How far the arrow keys move the selected nodes, in graph units. This
+    /// is one unit of the grid the layout is adjusted on.
+    pub nudge_step: f32,
+    /// How far the arrow keys move the selected nodes while shift is held.
+    pub nudge_large_step: f32,
+    /// How much the weight of a connection changes per point its label is
+    /// dragged, see [`crate::ConnectionDataTrait::weight`].
+    pub weight_drag_speed: f32,
+    /// Double clicking the empty canvas opens the node finder at that
+    /// position. This is an alternative to the secondary click, for users
+    /// who pan with the secondary button.
+    pub finder_on_double_click: bool,
+    /// Show a handle on the right edge of the nodes, which can be dragged to
+    /// change their width.
+    pub resizable_nodes: bool,
+    /// Skip laying out the nodes outside the editor. Their rect and ports
+    /// are taken from the last frame they were drawn on, so connections to
+    /// them are still drawn. This saves most of the cost of large graphs
+    /// where only part of the nodes is in view.
+    pub cull_offscreen_nodes: bool,
+    /// The most changes kept in the undo history, see
+    /// [`crate::GraphEditorState::update_history`].
+    pub history_limit: usize,
+    /// Undo with ctrl+Z, and redo with ctrl+shift+Z or ctrl+Y. On macOS,
+    /// cmd is used instead of ctrl.
+    pub history_shortcuts: bool,
+    /// Copy with ctrl+C, paste with ctrl+V and duplicate with ctrl+D, see
+    /// [`crate::GraphEditorState::update_clipboard`]. On macOS, cmd is used
+    /// instead of ctrl.
+    pub clipboard_shortcuts: bool,
+    /// Freezes the structure of the graph: nodes can't be moved, resized,
+    /// created or deleted, and connections can't be changed. The value
+    /// widgets of the nodes stay interactive, so users can tweak parameters
+    /// without breaking the graph.
+    pub lock_layout: bool,
+    /// New nodes that would land on top of an existing node are shifted by
+    /// this offset, in graph coordinates, until they have a free spot. See
+    /// [`crate::GraphEditorState::free_position`]. When `None`, nodes are
+    /// placed exactly where they were created.
+    pub placement_offset: Option<Vec2>,
+    /// The direction of the text in the nodes and the node finder. This
+    /// aligns the contents and mirrors the ports, but text is still shaped by
+    /// egui.
+    pub layout_direction: LayoutDirection,
+    /// The text of the built-in UI, like the node context menu.
+    pub strings: EditorStrings,
+    /// Checks the editor state with [`crate::GraphEditorState::validate`]
+    /// every frame before drawing, and reports the problems found with a
+    /// [`crate::NodeResponse::InconsistentState`]. This helps catch bugs in
+    /// code that edits the graph, but is costly for big graphs.
+    pub check_consistency: bool,
+    /// What happens when a connection is made to an input that is already
+    /// connected. Connecting the same output and input twice is ignored,
+    /// unless [`EditorSettings::allow_parallel_connections`] is set.
+    pub occupied_input: OccupiedInputPolicy,
+    /// Allow connecting the outputs of a node to its own inputs, e.g. for
+    /// state machines. These connections loop over the top of the node.
+    pub allow_self_connections: bool,
+    /// Allow connecting the same output and input more than once, for
+    /// multigraphs like event routing. Parallel connections are drawn fanned
+    /// out, so they can be told apart.
+    pub allow_parallel_connections: bool,
+}
+
+impl Default for EditorSettings {
+    fn default() -> Self {
+        Self {
+            radial_menu_gesture: None,
+            radial_menu_size: 8,
+            finder_on_connection_drop: false,
+            canvas_bounds: None,
+            show_scrollbars: false,
+            show_minimap: false,
+            kinetic_pan_friction: Some(5.0),
+            viewport_animation_duration: 0.3,
+            zoom_region_modifiers: Some(Modifiers::ALT),
+            overview_key: Some(Key::Z),
+            frame_key: Some(Key::F),
+            min_zoom: 0.2,
+            max_zoom: 4.0,
+            nudge_step: 10.0,
+            nudge_large_step: 50.0,
+            weight_drag_speed: 0.01,
+            delete_connection_on_double_click: true,
+            select_connections: true,
+            finder_on_double_click: false,
+            resizable_nodes: false,
+            cull_offscreen_nodes: true,
+            history_limit: 100,
+            history_shortcuts: true,
+            clipboard_shortcuts: true,
+            lock_layout: false,
+            placement_offset: Some(Vec2::splat(20.0)),
+            layout_direction: LayoutDirection::LeftToRight,
+            strings: EditorStrings::default(),
+            check_consistency: false,
+            occupied_input: OccupiedInputPolicy::Replace,
+            allow_self_connections: false,
+            allow_parallel_connections: false,
+        }
+    }
+}
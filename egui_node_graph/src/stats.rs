@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+
+use super::*;
+
+/// Summary figures about a [`Graph`], returned by [`Graph::stats`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GraphStats {
+    /// The number of nodes.
+    pub node_count: usize,
+    /// The number of connections.
+    pub connection_count: usize,
+    /// The number of nodes in the longest chain of connected nodes. Nodes
+    /// that are part of a cycle are not counted.
+    pub max_depth: usize,
+    /// The number of groups of nodes that are connected to each other, but
+    /// not to the rest of the graph. A node without connections is a group on
+    /// its own.
+    pub component_count: usize,
+    /// The number of nodes with each label. Nodes get the label of their
+    /// template when created, see [`NodeTemplateTrait::node_graph_label`].
+    pub nodes_per_label: HashMap<String, usize>,
+}
+
+impl<NodeData, DataType, ValueType, ConnectionData>
+    Graph<NodeData, DataType, ValueType, ConnectionData>
+{
+    /// Computes summary figures about the graph, e.g. for document info panels
+    /// or to enforce size limits.
+    pub fn stats(&self) -> GraphStats {
+        // Connections to parameters or nodes that no longer exist are
+        // skipped.
+        let edges: Vec<(NodeId, NodeId)> = self
+            .iter_connections()
+            .filter_map(|(input, output)| {
+                let from = self.try_get_output(output)?.node;
+                let to = self.try_get_input(input)?.node;
+                match self.nodes.contains_key(from) && self.nodes.contains_key(to) {
+                    true => Some((from, to)),
+                    false => None,
+                }
+            })
+            .collect();
+
+        let mut nodes_per_label = HashMap::new();
+        for (_, node) in &self.nodes {
+            *nodes_per_label.entry(node.label.clone()).or_default() += 1;
+        }
+
+        GraphStats {
+            node_count: self.nodes.len(),
+            connection_count: edges.len(),
+            max_depth: self.max_depth(&edges),
+            component_count: self.component_count(&edges),
+            nodes_per_label,
+        }
+    }
+
+    /// Returns the length of the longest path through the `edges`, counted in
+    /// nodes. Visits the nodes in topological order, so cycles are skipped.
+    fn max_depth(&self, edges: &[(NodeId, NodeId)]) -> usize {
+        let mut incoming: SecondaryMap<NodeId, usize> =
+            self.iter_nodes().map(|node| (node, 0)).collect();
+        let mut outgoing: SecondaryMap<NodeId, Vec<NodeId>> = SecondaryMap::new();
+        for &(from, to) in edges {
+            if let (Some(count), Some(entry)) = (incoming.get_mut(to), outgoing.entry(from)) {
+                *count += 1;
+                entry.or_default().push(to);
+            }
+        }
+
+        let mut depth: SecondaryMap<NodeId, usize> =
+            self.iter_nodes().map(|node| (node, 1)).collect();
+        let mut ready: Vec<NodeId> = self
+            .iter_nodes()
+            .filter(|node| incoming.get(*node) == Some(&0))
+            .collect();
+        let mut max_depth = 0;
+        while let Some(node) = ready.pop() {
+            let node_depth = depth.get(node).copied().unwrap_or(1);
+            max_depth = max_depth.max(node_depth);
+            for &next in outgoing.get(node).into_iter().flatten() {
+                if let Some(next_depth) = depth.get_mut(next) {
+                    *next_depth = (*next_depth).max(node_depth + 1);
+                }
+                if let Some(count) = incoming.get_mut(next) {
+                    *count -= 1;
+                    if *count == 0 {
+                        ready.push(next);
+                    }
+                }
+            }
+        }
+        max_depth
+    }
+
+    /// Returns the number of connected components of the graph, ignoring the
+    /// direction of the `edges`.
+    fn component_count(&self, edges: &[(NodeId, NodeId)]) -> usize {
+        fn root(parents: &SecondaryMap<NodeId, NodeId>, mut node: NodeId) -> NodeId {
+            while parents[node] != node {
+                node = parents[node];
+            }
+            node
+        }
+
+        let mut parents: SecondaryMap<NodeId, NodeId> =
+            self.iter_nodes().map(|node| (node, node)).collect();
+        let mut count = self.nodes.len();
+        for &(from, to) in edges {
+            let (a, b) = (root(&parents, from), root(&parents, to));
+            if a != b {
+                parents[a] = b;
+                count -= 1;
+            }
+        }
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    type TestGraph = Graph<(), u32, u32>;
+
+    #[test]
+    fn test_stats() {
+        let mut graph = TestGraph::new();
+        assert_eq!(graph.stats(), GraphStats::default());
+
+        // A chain of three nodes, a branch off its first node, and a node on
+        // its own.
+        let a = add_node(&mut graph, "value");
+        let b = add_node(&mut graph, "add");
+        let c = add_node(&mut graph, "add");
+        let d = add_node(&mut graph, "value");
+        add_node(&mut graph, "value");
+        connect(&mut graph, a, b);
+        connect(&mut graph, b, c);
+        connect(&mut graph, a, d);
+
+        let stats = graph.stats();
+        assert_eq!(stats.node_count, 5);
+        assert_eq!(stats.connection_count, 3);
+        assert_eq!(stats.max_depth, 3);
+        assert_eq!(stats.component_count, 2);
+        assert_eq!(stats.nodes_per_label["value"], 3);
+        assert_eq!(stats.nodes_per_label["add"], 2);
+
+        // The nodes of a cycle, and the nodes after it, don't count towards
+        // the depth. Only the node on its own is left.
+        connect(&mut graph, c, a);
+        assert_eq!(graph.stats().max_depth, 1);
+    }
+}
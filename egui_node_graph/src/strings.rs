@@ -0,0 +1,47 @@
+/// The text of the built-in UI of the editor, so applications can translate
+/// it. Set it in [`crate::EditorSettings::strings`]. Defaults to English.
+///
+/// ```ignore
+/// state.settings.strings = EditorStrings {
+///     extract: "Extraire".into(),
+///     replace_with: "Remplacer par".into(),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EditorStrings {
+    /// The entry of the node context menu that removes the node and connects
+    /// its neighbors.
+    pub extract: String,
+    /// The tooltip of [`EditorStrings::extract`].
+    pub extract_tooltip: String,
+    /// The entry of the node context menu that pins the node.
+    pub pin: String,
+    /// The entry of the node context menu that unpins a pinned node.
+    pub unpin: String,
+    /// The tooltip of [`EditorStrings::pin`] and [`EditorStrings::unpin`].
+    pub pin_tooltip: String,
+    /// The submenu of the node context menu with the templates that can
+    /// replace the node.
+    pub replace_with: String,
+    /// The placeholder of the search field of the node finder.
+    pub finder_hint: String,
+    /// The tooltip of ports with several connections. `{count}` is replaced
+    /// with the number of connections.
+    pub port_connections: String,
+}
+
+impl Default for EditorStrings {
+    fn default() -> Self {
+        Self {
+            extract: "Extract".into(),
+            extract_tooltip: "Disconnect the node and connect its neighbors directly".into(),
+            pin: "Pin".into(),
+            unpin: "Unpin".into(),
+            pin_tooltip: "Lock the position of the node".into(),
+            replace_with: "Replace with".into(),
+            finder_hint: "Search nodes".into(),
+            port_connections: "{count} connections".into(),
+        }
+    }
+}
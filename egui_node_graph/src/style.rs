@@ -0,0 +1,418 @@
+use egui::{Align2, Color32, Rect, Rgba, Shape, Stroke, TextStyle, Vec2};
+
+/// Visual settings for the graph editor. These are configured by the
+/// application and are not part of the serialized editor state. All sizes are
+/// in points at a zoom level of 1.0, and get scaled with the zoom.
+#[derive(Clone, Debug)]
+pub struct EditorStyle {
+    /// The radius of the circle drawn for each port, see
+    /// [`crate::DataTypeTrait::draw_port`].
+    pub port_radius: f32,
+    /// Half the side of the square around each port that reacts to the mouse.
+    /// This is independent of [`EditorStyle::port_radius`], so ports can be
+    /// easier to hit on touch screens without being drawn bigger.
+    pub port_hit_radius: f32,
+    /// Extra distance around the hit square of a port where connections can
+    /// still be dropped on it. When several ports are in reach, the nearest
+    /// one is connected.
+    pub port_drop_padding: f32,
+    /// The space between the edges of the nodes and their contents,
+    /// horizontally and vertically.
+    pub node_margin: Vec2,
+    /// The corner radius of the nodes.
+    pub node_rounding: f32,
+    /// The background color of the nodes. When `None`, the window fill color
+    /// of the egui visuals is used.
+    pub node_background: Option<Color32>,
+    /// The color of the text in the nodes. When `None`, the text color of
+    /// the egui visuals is used.
+    pub node_text_color: Option<Color32>,
+    /// The color of the titlebar of the nodes, unless
+    /// [`crate::NodeDataTrait::titlebar_color`] sets one. When `None`, the
+    /// node background is lightened.
+    pub titlebar_color: Option<Color32>,
+    /// The minimum height of the titlebar of the nodes. The titlebar grows
+    /// to fit the label and [`crate::NodeDataTrait::top_bar_ui`] from here.
+    pub titlebar_min_height: f32,
+    /// The text style of the node labels in the titlebar.
+    pub title_text_style: TextStyle,
+    /// The text style of the input rows. It's set as the
+    /// [`egui::Style::override_text_style`] while the inline widgets of the
+    /// inputs are drawn, so the default labels and most widgets use it.
+    pub input_text_style: TextStyle,
+    /// The text style of the output rows, set like
+    /// [`EditorStyle::input_text_style`] while
+    /// [`crate::NodeDataTrait::output_ui`] is drawn.
+    pub output_text_style: TextStyle,
+    /// A soft shadow drawn behind the nodes. When `None`, nodes have no
+    /// shadow.
+    pub node_shadow: Option<NodeShadow>,
+    /// The outline drawn around selected nodes.
+    pub selection: SelectionStyle,
+    /// The fill of the box drawn while box selecting.
+    pub selection_box_fill: Color32,
+    /// The outline of the box drawn while box selecting. Its color is also
+    /// used for the zoom region. The width is in screen points.
+    pub selection_box_stroke: Stroke,
+    /// The opacity of disabled nodes and the connections attached to them,
+    /// see [`crate::NodeDataTrait::is_disabled`].
+    pub disabled_opacity: f32,
+    /// The look of the connections between ports.
+    pub connection: ConnectionStyle,
+    /// When set, the nodes at both ends of the connection under the mouse are
+    /// outlined with this color, like selected nodes are.
+    pub connection_hover_node_outline: Option<Color32>,
+    /// When set, a ring of this color is drawn around every port that
+    /// accepts the connection being dragged.
+    pub compatible_port_highlight: Option<Color32>,
+    /// When set, ports with more than one connection show the count in a
+    /// badge of this color, next to the port.
+    pub connection_count_badge: Option<Color32>,
+    /// The text style of the weight labels drawn on connections, see
+    /// [`crate::ConnectionDataTrait::weight`].
+    pub connection_weight_text_style: TextStyle,
+    /// The height of the bar drawn under the titlebar of nodes that report
+    /// their progress, see [`crate::NodeDataTrait::progress`].
+    pub progress_bar_height: f32,
+    /// How the execution status of the nodes is shown, see
+    /// [`crate::NodeDataTrait::status`].
+    pub status: NodeStatusStyle,
+    /// The gradient used to tint the nodes by the metric from
+    /// [`crate::NodeDataTrait::heatmap_value`].
+    pub heatmap: HeatmapStyle,
+    /// How long, in seconds, newly created nodes are outlined with the
+    /// primary selection color, fading out, so users notice where they were
+    /// placed. Zero disables the highlight.
+    pub creation_highlight_duration: f32,
+    /// The look of the minimap, see [`crate::EditorSettings::show_minimap`].
+    pub minimap: MinimapStyle,
+}
+
+impl Default for EditorStyle {
+    fn default() -> Self {
+        Self {
+            port_radius: 5.0,
+            port_hit_radius: 5.0,
+            port_drop_padding: 5.0,
+            node_margin: Vec2::new(15.0, 5.0),
+            node_rounding: 4.0,
+            node_background: None,
+            node_text_color: None,
+            titlebar_color: None,
+            titlebar_min_height: 0.0,
+            title_text_style: TextStyle::Button,
+            input_text_style: TextStyle::Body,
+            output_text_style: TextStyle::Body,
+            node_shadow: None,
+            selection: SelectionStyle::default(),
+            selection_box_fill: Color32::from_rgba_unmultiplied(200, 200, 200, 20),
+            selection_box_stroke: Stroke::new(
+                3.0,
+                Color32::from_rgba_unmultiplied(200, 200, 200, 180),
+            ),
+            disabled_opacity: 0.4,
+            connection: ConnectionStyle::default(),
+            connection_hover_node_outline: None,
+            compatible_port_highlight: Some(Color32::from_white_alpha(60)),
+            connection_count_badge: Some(Color32::from_gray(70)),
+            connection_weight_text_style: TextStyle::Small,
+            progress_bar_height: 3.0,
+            status: NodeStatusStyle::default(),
+            heatmap: HeatmapStyle::default(),
+            creation_highlight_duration: 0.6,
+            minimap: MinimapStyle::default(),
+        }
+    }
+}
+
+/// The look of the outline drawn around selected nodes. The first node in
+/// [`crate::GraphEditorState::selected_nodes`] is the primary selection, e.g.
+/// the node that was clicked, and the rest are secondary.
+#[derive(Clone, Copy, Debug)]
+pub struct SelectionStyle {
+    /// The outline color of the primary selected node.
+    pub primary_color: Color32,
+    /// The outline color of the other selected nodes.
+    pub secondary_color: Color32,
+    /// The thickness of the outline.
+    pub width: f32,
+    /// The corner radius of the outline.
+    pub rounding: f32,
+    /// The width of a soft glow around the outline. Zero disables the glow.
+    pub glow: f32,
+}
+
+impl Default for SelectionStyle {
+    fn default() -> Self {
+        Self {
+            primary_color: Color32::from_gray(204),
+            secondary_color: Color32::from_gray(140),
+            width: 1.0,
+            rounding: 4.0,
+            glow: 0.0,
+        }
+    }
+}
+
+impl SelectionStyle {
+    const GLOW_LAYERS: usize = 4;
+
+    /// Returns the shape of an outline with the given color for a node
+    /// occupying `rect`. The sizes are scaled by `zoom`.
+    pub fn shape(&self, rect: Rect, zoom: f32, color: Color32) -> Shape {
+        let outline_rect = rect.expand(self.width * zoom);
+        let rounding = self.rounding * zoom;
+        let glow_color = color.linear_multiply(0.5 / Self::GLOW_LAYERS as f32);
+        let glow = (0..Self::GLOW_LAYERS)
+            .filter(|_| self.glow > 0.0)
+            .map(|layer| {
+                let expansion = self.glow * zoom * (1.0 - layer as f32 / Self::GLOW_LAYERS as f32);
+                Shape::rect_filled(
+                    outline_rect.expand(expansion),
+                    rounding + expansion,
+                    glow_color,
+                )
+            });
+        Shape::Vec(
+            glow.chain(std::iter::once(Shape::rect_filled(
+                outline_rect,
+                rounding,
+                color,
+            )))
+            .collect(),
+        )
+    }
+}
+
+/// The look of the connections, drawn as cubic bezier curves that leave and
+/// reach the ports in the direction of their [`crate::PortSide`].
+#[derive(Clone, Copy, Debug)]
+pub struct ConnectionStyle {
+    /// The stroke width of connections.
+    pub width: f32,
+    /// The stroke width of the connection under the mouse.
+    pub hover_width: f32,
+    /// The width of the soft edge drawn around connections. Zero disables it.
+    pub feathering: f32,
+    /// How far the curve keeps the direction of the ports, as a fraction of
+    /// the distance between its ends. Zero draws straight lines, and bigger
+    /// values draw rounder curves.
+    pub curvature: f32,
+    /// When set, all connections are drawn with this color instead of the
+    /// [`crate::DataTypeTrait::data_type_color`] of their data type.
+    pub color: Option<Color32>,
+}
+
+impl Default for ConnectionStyle {
+    fn default() -> Self {
+        Self {
+            width: 5.0,
+            hover_width: 7.0,
+            feathering: 1.5,
+            curvature: 0.5,
+            color: None,
+        }
+    }
+}
+
+/// A soft shadow drawn behind nodes, made of layered translucent rects.
+#[derive(Clone, Copy, Debug)]
+pub struct NodeShadow {
+    /// Offset of the shadow from the node. Selected nodes use twice this
+    /// offset, so they look raised above the others.
+    pub offset: Vec2,
+    /// How far the shadow fades out past the edges of the node.
+    pub blur: f32,
+    /// The color of the shadow where it is most opaque.
+    pub color: Color32,
+}
+
+impl Default for NodeShadow {
+    fn default() -> Self {
+        Self {
+            offset: Vec2::new(2.0, 4.0),
+            blur: 8.0,
+            color: Color32::from_black_alpha(96),
+        }
+    }
+}
+
+impl NodeShadow {
+    const LAYERS: usize = 4;
+
+    /// Returns the shape of the shadow for a node occupying `rect`, which has
+    /// the given corner rounding. The offset and blur are scaled by `zoom`.
+    pub fn shape(&self, rect: Rect, rounding: f32, zoom: f32, raised: bool) -> Shape {
+        let offset = if raised {
+            self.offset * 2.0
+        } else {
+            self.offset
+        } * zoom;
+        let layer_color = self.color.linear_multiply(1.0 / Self::LAYERS as f32);
+        // The layers overlap towards the node, so the shadow gets more opaque
+        // closer to it.
+        Shape::Vec(
+            (0..Self::LAYERS)
+                .map(|layer| {
+                    let expansion = self.blur * zoom * (1.0 - layer as f32 / Self::LAYERS as f32);
+                    Shape::rect_filled(
+                        rect.translate(offset).expand(expansion),
+                        rounding + expansion,
+                        layer_color,
+                    )
+                })
+                .collect(),
+        )
+    }
+}
+
+/// The execution status of a node, reported by
+/// [`crate::NodeDataTrait::status`] so pipeline execution can be followed in
+/// the editor.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NodeStatus {
+    #[default]
+    Idle,
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+/// Where the color of a [`NodeStatus`] is shown on the node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NodeStatusIndicator {
+    /// An outline around the node, like the selection outline. Selected
+    /// nodes show the selection outline instead.
+    Outline,
+    /// A small circle on the top left corner of the node.
+    Badge,
+}
+
+/// The colors of each [`NodeStatus`]. Idle nodes are drawn as usual.
+#[derive(Clone, Copy, Debug)]
+pub struct NodeStatusStyle {
+    pub indicator: NodeStatusIndicator,
+    pub queued: Color32,
+    pub running: Color32,
+    pub succeeded: Color32,
+    pub failed: Color32,
+    /// The radius of the [`NodeStatusIndicator::Badge`].
+    pub badge_radius: f32,
+}
+
+impl Default for NodeStatusStyle {
+    fn default() -> Self {
+        Self {
+            indicator: NodeStatusIndicator::Outline,
+            queued: Color32::from_rgb(120, 120, 200),
+            running: Color32::from_rgb(230, 180, 40),
+            succeeded: Color32::from_rgb(80, 180, 80),
+            failed: Color32::from_rgb(220, 60, 60),
+            badge_radius: 5.0,
+        }
+    }
+}
+
+impl NodeStatusStyle {
+    /// Returns the color for `status`, or `None` for idle nodes.
+    pub fn color(&self, status: NodeStatus) -> Option<Color32> {
+        match status {
+            NodeStatus::Idle => None,
+            NodeStatus::Queued => Some(self.queued),
+            NodeStatus::Running => Some(self.running),
+            NodeStatus::Succeeded => Some(self.succeeded),
+            NodeStatus::Failed => Some(self.failed),
+        }
+    }
+}
+
+/// A gradient to tint the node backgrounds by a normalized metric, e.g. for
+/// profiling overlays.
+#[derive(Clone, Debug)]
+pub struct HeatmapStyle {
+    /// The colors of the gradient, evenly spaced from 0.0 to 1.0.
+    pub gradient: Vec<Color32>,
+    /// How much the node background is tinted towards the gradient color,
+    /// from 0.0 to 1.0.
+    pub strength: f32,
+}
+
+impl Default for HeatmapStyle {
+    fn default() -> Self {
+        Self {
+            gradient: vec![
+                Color32::from_rgb(40, 80, 200),
+                Color32::from_rgb(230, 200, 40),
+                Color32::from_rgb(220, 50, 40),
+            ],
+            strength: 0.5,
+        }
+    }
+}
+
+impl HeatmapStyle {
+    /// Returns the color of the gradient at `value`, which is clamped to the
+    /// range from 0.0 to 1.0.
+    pub fn color(&self, value: f32) -> Color32 {
+        match self.gradient.len() {
+            0 => Color32::TRANSPARENT,
+            1 => self.gradient[0],
+            len => {
+                let pos = value.clamp(0.0, 1.0) * (len - 1) as f32;
+                let idx = (pos as usize).min(len - 2);
+                let t = pos - idx as f32;
+                let from = Rgba::from(self.gradient[idx]);
+                let to = Rgba::from(self.gradient[idx + 1]);
+                (from * (1.0 - t) + to * t).into()
+            }
+        }
+    }
+
+    /// Tints the `background` color by `value`.
+    pub fn tint(&self, background: Color32, value: f32) -> Color32 {
+        let strength = self.strength.clamp(0.0, 1.0);
+        let tinted =
+            Rgba::from(background) * (1.0 - strength) + Rgba::from(self.color(value)) * strength;
+        tinted.into()
+    }
+}
+
+/// The look of the minimap drawn in a corner of the editor. Unlike the other
+/// sizes of the style, its sizes are in screen points and don't change with
+/// the zoom.
+#[derive(Clone, Copy, Debug)]
+pub struct MinimapStyle {
+    /// The corner of the editor the minimap is drawn in.
+    pub corner: Align2,
+    pub size: Vec2,
+    /// The space between the minimap and the edges of the editor.
+    pub margin: f32,
+    pub background: Color32,
+    /// The color of the nodes. Selected nodes use the selection color of
+    /// the egui visuals instead.
+    pub node_color: Color32,
+    /// The outline of the part of the graph visible in the editor.
+    pub viewport_stroke: Stroke,
+}
+
+impl Default for MinimapStyle {
+    fn default() -> Self {
+        Self {
+            corner: Align2::RIGHT_BOTTOM,
+            size: Vec2::new(200.0, 150.0),
+            margin: 12.0,
+            background: Color32::from_black_alpha(180),
+            node_color: Color32::from_gray(120),
+            viewport_stroke: Stroke::new(1.0, Color32::from_gray(220)),
+        }
+    }
+}
+
+impl MinimapStyle {
+    /// Returns the rect of the minimap in an editor occupying `editor_rect`.
+    pub fn rect(&self, editor_rect: Rect) -> Rect {
+        self.corner
+            .align_size_within_rect(self.size, editor_rect.shrink(self.margin))
+    }
+}
@@ -0,0 +1,279 @@
+use super::*;
+use egui::Pos2;
+
+#[cfg(feature = "persistence")]
+use serde::{Deserialize, Serialize};
+
+/// A change made in a [`GraphEditorState`], to be sent to another instance
+/// of the editor that mirrors it. See [`GraphEditorState::sync_ops`].
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "persistence", derive(Serialize, Deserialize))]
+pub enum SyncOp<NodeData, DataType, ValueType> {
+    /// A change to the graph.
+    Graph(GraphOp<NodeData, DataType, ValueType>),
+    /// A node was moved to `pos`, in graph coordinates.
+    MoveNode { node: OpId, pos: Pos2 },
+    /// A node was renamed.
+    RenameNode { node: OpId, label: String },
+}
+
+impl<NodeData, DataType, ValueType, NodeTemplate, UserState, ConnectionData>
+    GraphEditorState<NodeData, DataType, ValueType, NodeTemplate, UserState, ConnectionData>
+where
+    NodeData: Clone,
+    DataType: Clone,
+    ValueType: Clone + PartialEq,
+    ConnectionData: Default,
+{
+    /// Starts mirroring this editor with other instances, which are
+    /// identified by a different `replica` id each. Returns the operations
+    /// that recreate the current graph on an instance that starts empty.
+    pub fn start_sync(&mut self, replica: u64) -> Vec<SyncOp<NodeData, DataType, ValueType>> {
+        let mut replica = OpReplica::new(replica);
+        let mut ops = Vec::new();
+        for &node_id in &self.node_order {
+            ops.push(SyncOp::Graph(replica.insert_node(&self.graph, node_id)));
+            ops.extend(self.move_op(&replica, node_id));
+        }
+        // One operation per input, with all of its connections.
+        let mut inputs: Vec<InputId> = self.graph.iter_connections().map(|(i, _)| i).collect();
+        inputs.sort();
+        inputs.dedup();
+        for input in inputs {
+            ops.extend(
+                replica
+                    .set_connection(&self.graph, input)
+                    .map(SyncOp::Graph),
+            );
+        }
+        replica.value_changes(&self.graph);
+        self.sync = Some(replica);
+        ops
+    }
+
+    /// Returns the operations for the changes reported by the `responses`
+    /// of [`GraphEditorState::draw_graph_editor`], plus the input values
+    /// changed by the widgets. Call this after drawing the editor each frame,
+    /// and send the operations to the other instances, which pass them to
+    /// [`GraphEditorState::apply_op`]. Returns nothing until
+    /// [`GraphEditorState::start_sync`] is called.
+    pub fn sync_ops<UserResponse>(
+        &mut self,
+        responses: &[NodeResponse<UserResponse, NodeData>],
+    ) -> Vec<SyncOp<NodeData, DataType, ValueType>>
+    where
+        UserResponse: UserResponseTrait,
+        NodeData: NodeDataTrait,
+    {
+        let mut replica = match self.sync.take() {
+            Some(replica) => replica,
+            None => return Vec::new(),
+        };
+        let mut ops = Vec::new();
+
+        // Replaced nodes are sent first, so the connection changes that come
+        // with them refer to the new node.
+        for response in responses {
+            if let NodeResponse::ReplacedNode { node_id, .. } = response {
+                ops.extend(replica.remove_node(*node_id).map(SyncOp::Graph));
+                ops.push(SyncOp::Graph(replica.insert_node(&self.graph, *node_id)));
+                ops.extend(self.move_op(&replica, *node_id));
+            }
+        }
+
+        let mut moved = Vec::new();
+        for response in responses {
+            match response {
+                NodeResponse::CreatedNode(node_id) => {
+                    ops.push(SyncOp::Graph(replica.insert_node(&self.graph, *node_id)));
+                    ops.extend(self.move_op(&replica, *node_id));
+                }
+                NodeResponse::DeleteNodeFull { node_id, .. } => {
+                    ops.extend(replica.remove_node(*node_id).map(SyncOp::Graph));
+                }
+                NodeResponse::ConnectEventEnded { input, .. }
+                | NodeResponse::DisconnectEvent { input, .. } => {
+                    ops.extend(
+                        replica
+                            .set_connection(&self.graph, *input)
+                            .map(SyncOp::Graph),
+                    );
+                }
+                NodeResponse::MoveNode { node, .. } => {
+                    // Dragging a selected node moves the whole selection.
+                    if self.selected_nodes.contains(node) {
+                        moved.extend(self.selected_nodes.iter().copied());
+                    } else {
+                        moved.push(*node);
+                    }
+                }
+                NodeResponse::RenameNode {
+                    node, new_label, ..
+                } => {
+                    if let Some(id) = replica.op_id(*node) {
+                        ops.push(SyncOp::RenameNode {
+                            node: id,
+                            label: new_label.clone(),
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+        moved.sort();
+        moved.dedup();
+        for node_id in moved {
+            ops.extend(self.move_op(&replica, node_id));
+        }
+
+        ops.extend(
+            replica
+                .value_changes(&self.graph)
+                .into_iter()
+                .map(SyncOp::Graph),
+        );
+        self.sync = Some(replica);
+        ops
+    }
+
+    /// Applies an operation received from another instance of the editor.
+    /// Does nothing until [`GraphEditorState::start_sync`] is called.
+    pub fn apply_op(&mut self, op: SyncOp<NodeData, DataType, ValueType>) {
+        let replica = match &mut self.sync {
+            Some(replica) => replica,
+            None => return,
+        };
+        match op {
+            SyncOp::Graph(op) => {
+                let removed = match &op {
+                    GraphOp::RemoveNode { id } => replica.node_id(*id),
+                    _ => None,
+                };
+                let inserted = match &op {
+                    GraphOp::InsertNode { id, .. } => Some(*id),
+                    _ => None,
+                };
+                replica.apply(&mut self.graph, op);
+
+                if let Some(node_id) = removed {
+                    self.node_positions.remove(node_id);
+                    self.node_widths.remove(node_id);
+                    self.pinned_nodes.remove(&node_id);
+                    self.selected_nodes.retain(|id| *id != node_id);
+                    self.node_order.retain(|id| *id != node_id);
+                }
+                if let Some(node_id) = inserted.and_then(|id| replica.node_id(id)) {
+                    if !self.node_order.contains(&node_id) {
+                        self.node_order.push(node_id);
+                        self.node_positions.insert(node_id, Pos2::ZERO);
+                    }
+                }
+            }
+            SyncOp::MoveNode { node, pos } => {
+                if let Some(node_id) = replica.node_id(node) {
+                    self.node_positions.insert(node_id, pos);
+                }
+            }
+            SyncOp::RenameNode { node, label } => {
+                if let Some(node) = replica
+                    .node_id(node)
+                    .and_then(|node_id| self.graph.nodes.get_mut(node_id))
+                {
+                    node.label = label;
+                }
+            }
+        }
+    }
+
+    fn move_op(
+        &self,
+        replica: &OpReplica<ValueType>,
+        node_id: NodeId,
+    ) -> Option<SyncOp<NodeData, DataType, ValueType>> {
+        Some(SyncOp::MoveNode {
+            node: replica.op_id(node_id)?,
+            pos: *self.node_positions.get(node_id)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use egui::pos2;
+
+    type TestState = GraphEditorState<(), u32, u32, (), ()>;
+
+    /// The label, position and connected nodes of each node, in drawing
+    /// order.
+    fn snapshot(state: &TestState) -> Vec<(String, Pos2, Vec<String>)> {
+        let graph = &state.graph;
+        state
+            .node_order
+            .iter()
+            .map(|&node_id| {
+                (
+                    graph[node_id].label.clone(),
+                    state.node_positions[node_id],
+                    connected_labels(graph, node_id),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_sync() {
+        let mut ours = TestState::default();
+        let a = add_editor_node(&mut ours, "a", pos2(0.0, 0.0));
+        let b = add_editor_node(&mut ours, "b", pos2(0.0, 100.0));
+        let sum = add_editor_node(&mut ours, "sum", pos2(200.0, 50.0));
+        let sum_in = ours.graph[sum].get_input("in").unwrap();
+        for node_id in [a, b] {
+            let out = ours.graph[node_id].get_output("out").unwrap();
+            ours.graph.append_connection(out, sum_in).unwrap();
+        }
+
+        let mut theirs = TestState::default();
+        let ops = ours.start_sync(1);
+        // Nothing happens until the instance starts syncing.
+        for op in ops.clone() {
+            theirs.apply_op(op);
+        }
+        assert!(theirs.graph.nodes.is_empty());
+
+        let _ = theirs.start_sync(2);
+        for op in ops {
+            theirs.apply_op(op);
+        }
+        assert_eq!(snapshot(&theirs), snapshot(&ours));
+
+        let replica = ours.sync.as_ref().unwrap();
+        let (a_id, b_id) = (replica.op_id(a).unwrap(), replica.op_id(b).unwrap());
+        theirs.apply_op(SyncOp::RenameNode {
+            node: a_id,
+            label: "renamed".into(),
+        });
+        theirs.apply_op(SyncOp::MoveNode {
+            node: a_id,
+            pos: pos2(-50.0, 0.0),
+        });
+        theirs.apply_op(SyncOp::Graph(GraphOp::RemoveNode { id: b_id }));
+        // Operations on removed nodes are ignored.
+        theirs.apply_op(SyncOp::RenameNode {
+            node: b_id,
+            label: "removed".into(),
+        });
+        assert_eq!(
+            snapshot(&theirs),
+            vec![
+                ("renamed".to_string(), pos2(-50.0, 0.0), vec![]),
+                (
+                    "sum".to_string(),
+                    pos2(200.0, 50.0),
+                    vec!["renamed".to_string()]
+                ),
+            ]
+        );
+    }
+}
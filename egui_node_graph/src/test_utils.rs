@@ -0,0 +1,97 @@
+use super::*;
+
+/// Adds a node with an input named "in" and an output named "out", both of
+/// data type 0.
+pub fn add_node<NodeData: Default, ConnectionData>(
+    graph: &mut Graph<NodeData, u32, u32, ConnectionData>,
+    label: &str,
+) -> NodeId {
+    graph.add_node(label.into(), NodeData::default(), |graph, node_id| {
+        graph.add_input_param(
+            node_id,
+            "in".into(),
+            0,
+            0,
+            InputParamKind::ConnectionOrConstant,
+            true,
+        );
+        graph.add_output_param(node_id, "out".into(), 0);
+    })
+}
+
+/// Connects the "out" output of `from` to the "in" input of `to`, next to
+/// the other connections of the input.
+pub fn connect<NodeData, ConnectionData: Default>(
+    graph: &mut Graph<NodeData, u32, u32, ConnectionData>,
+    from: NodeId,
+    to: NodeId,
+) {
+    connect_with_data(graph, from, to, ConnectionData::default());
+}
+
+/// Like [`connect`], with the given connection data.
+pub fn connect_with_data<NodeData, ConnectionData>(
+    graph: &mut Graph<NodeData, u32, u32, ConnectionData>,
+    from: NodeId,
+    to: NodeId,
+    data: ConnectionData,
+) {
+    let output = graph[from].get_output("out").unwrap();
+    let input = graph[to].get_input("in").unwrap();
+    graph
+        .append_connection_with_data(output, input, data)
+        .unwrap();
+}
+
+/// The labels of the nodes connected to the "in" input of `node_id`, in the
+/// order of the connections.
+pub fn connected_labels<NodeData, ConnectionData>(
+    graph: &Graph<NodeData, u32, u32, ConnectionData>,
+    node_id: NodeId,
+) -> Vec<String> {
+    let input = graph[node_id].get_input("in").unwrap();
+    graph
+        .connection_ids(input)
+        .iter()
+        .map(|id| graph[graph[graph[*id].output].node].label.clone())
+        .collect()
+}
+
+/// Adds a node like [`add_node`], drawn last at `pos`.
+pub fn add_editor_node<NodeData: Default, NodeTemplate, UserState, ConnectionData>(
+    state: &mut GraphEditorState<NodeData, u32, u32, NodeTemplate, UserState, ConnectionData>,
+    label: &str,
+    pos: egui::Pos2,
+) -> NodeId {
+    let node_id = add_node(&mut state.graph, label);
+    state.node_order.push(node_id);
+    state.node_positions.insert(node_id, pos);
+    node_id
+}
+
+/// The node data of the tests that need a [`NodeDataTrait`] implementation.
+#[derive(Clone, Debug, Default)]
+pub struct TestNode;
+
+#[derive(Clone, Debug)]
+pub struct TestResponse;
+
+impl UserResponseTrait for TestResponse {}
+
+impl NodeDataTrait for TestNode {
+    type Response = TestResponse;
+    type UserState = ();
+    type DataType = u32;
+    type ValueType = u32;
+    type ConnectionData = u32;
+
+    fn bottom_ui(
+        &self,
+        _ui: &mut egui::Ui,
+        _node_id: NodeId,
+        _graph: &Graph<Self, u32, u32, u32>,
+        _user_state: &mut (),
+    ) -> Vec<NodeResponse<TestResponse, Self>> {
+        Vec::new()
+    }
+}
@@ -0,0 +1,645 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+use super::*;
+use egui::{pos2, Pos2};
+
+/// Tells the text format how to name templates and read and write values.
+/// See [`GraphEditorState::to_text`] for a description of the format.
+pub trait GraphTextFormat {
+    type NodeData;
+    type NodeTemplate;
+    type ValueType;
+
+    /// Returns the template with the given name.
+    fn template(&self, name: &str) -> Option<Self::NodeTemplate>;
+
+    /// Returns the name of the template a node was created from, which
+    /// [`GraphTextFormat::template`] turns back into the template.
+    fn template_name(&self, node: &Node<Self::NodeData>) -> String;
+
+    /// Parses the value of an input. `current` is the value set by the
+    /// template, e.g. to tell which kind of value to parse. Returns `None`
+    /// when the text is not a valid value.
+    fn parse_value(&self, text: &str, current: &Self::ValueType) -> Option<Self::ValueType>;
+
+    /// Writes the value of an input. Values that return `None` are left out,
+    /// and get the value set by the template when the text is read back.
+    fn format_value(&self, value: &Self::ValueType) -> Option<String>;
+}
+
+enum Statement {
+    Node {
+        name: String,
+        template: String,
+        args: Vec<(String, String)>,
+        pos: Option<Pos2>,
+        line: usize,
+    },
+    Connection {
+        from: (String, String),
+        to: (String, String),
+        line: usize,
+    },
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    fn line(&self) -> usize {
+        self.text[..self.pos].matches('\n').count() + 1
+    }
+
+    fn error(&self, message: impl Into<String>) -> EguiGraphError {
+        EguiGraphError::TextParse(self.line(), message.into())
+    }
+
+    /// Skips whitespace, statement separators and `#` comments.
+    fn skip_space(&mut self, separators: bool) {
+        loop {
+            let rest = self.rest();
+            let trimmed =
+                rest.trim_start_matches(|c: char| c.is_whitespace() || (separators && c == ';'));
+            self.pos += rest.len() - trimmed.len();
+            if trimmed.starts_with('#') {
+                self.pos += trimmed.find('\n').unwrap_or(trimmed.len());
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_space(false);
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), EguiGraphError> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected `{}`", token)))
+        }
+    }
+
+    fn quoted(&mut self) -> Result<String, EguiGraphError> {
+        let mut result = String::new();
+        let mut chars = self.rest().char_indices().skip(1);
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.pos += i + 1;
+                    return Ok(result);
+                }
+                '\\' => match chars.next() {
+                    Some((_, c)) => result.push(c),
+                    None => break,
+                },
+                c => result.push(c),
+            }
+        }
+        Err(self.error("unterminated string"))
+    }
+
+    /// Parses an identifier, or any name in double quotes.
+    fn name(&mut self) -> Result<String, EguiGraphError> {
+        self.skip_space(false);
+        if self.rest().starts_with('"') {
+            return self.quoted();
+        }
+        let len = self
+            .rest()
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(self.rest().len());
+        if len == 0 {
+            return Err(self.error("expected a name"));
+        }
+        let name = self.rest()[..len].to_owned();
+        self.pos += len;
+        Ok(name)
+    }
+
+    /// Parses a value, which is either quoted or runs until the next `,` or
+    /// `)`.
+    fn value(&mut self) -> Result<String, EguiGraphError> {
+        self.skip_space(false);
+        if self.rest().starts_with('"') {
+            return self.quoted();
+        }
+        let len = self.rest().find([',', ')']).unwrap_or(self.rest().len());
+        let value = self.rest()[..len].trim().to_owned();
+        self.pos += len;
+        Ok(value)
+    }
+
+    fn number(&mut self) -> Result<f32, EguiGraphError> {
+        self.skip_space(false);
+        let len = self
+            .rest()
+            .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-'))
+            .unwrap_or(self.rest().len());
+        let number = self.rest()[..len]
+            .parse()
+            .map_err(|_| self.error("expected a number"))?;
+        self.pos += len;
+        Ok(number)
+    }
+
+    fn statement(&mut self) -> Result<Statement, EguiGraphError> {
+        let line = self.line();
+        let name = self.name()?;
+        if self.eat(":") {
+            let template = self.name()?;
+            let mut args = Vec::new();
+            self.expect("(")?;
+            if !self.eat(")") {
+                loop {
+                    let param = self.name()?;
+                    self.expect("=")?;
+                    args.push((param, self.value()?));
+                    if self.eat(")") {
+                        break;
+                    }
+                    self.expect(",")?;
+                }
+            }
+            let pos = if self.eat("@") {
+                let x = self.number()?;
+                self.expect(",")?;
+                Some(pos2(x, self.number()?))
+            } else {
+                None
+            };
+            Ok(Statement::Node {
+                name,
+                template,
+                args,
+                pos,
+                line,
+            })
+        } else {
+            self.expect(".")?;
+            let from = (name, self.name()?);
+            self.expect("->")?;
+            let node = self.name()?;
+            self.expect(".")?;
+            let to = (node, self.name()?);
+            Ok(Statement::Connection { from, to, line })
+        }
+    }
+
+    fn statements(&mut self) -> Result<Vec<Statement>, EguiGraphError> {
+        let mut statements = Vec::new();
+        loop {
+            self.skip_space(true);
+            if self.rest().is_empty() {
+                return Ok(statements);
+            }
+            statements.push(self.statement()?);
+        }
+    }
+}
+
+/// Writes a name as an identifier when possible, and quoted otherwise.
+fn write_name(out: &mut String, name: &str) {
+    let is_ident = !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+    if is_ident {
+        out.push_str(name);
+    } else {
+        write_quoted(out, name);
+    }
+}
+
+fn write_quoted(out: &mut String, text: &str) {
+    out.push('"');
+    for c in text.chars() {
+        if c == '"' || c == '\\' {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out.push('"');
+}
+
+impl<NodeData, DataType, ValueType, NodeTemplate, UserState, ConnectionData>
+    GraphEditorState<NodeData, DataType, ValueType, NodeTemplate, UserState, ConnectionData>
+where
+    NodeTemplate: NodeTemplateTrait<
+        NodeData = NodeData,
+        DataType = DataType,
+        ValueType = ValueType,
+        ConnectionData = ConnectionData,
+        UserState = UserState,
+    >,
+    ConnectionData: Default,
+{
+    /// Writes the graph in a small text format, which can be read back with
+    /// [`GraphEditorState::add_text`]. Each node is written as its name, its
+    /// template, the values of its unconnected inputs and its position,
+    /// followed by the connections from an output to an input:
+    ///
+    /// ```text
+    /// add1: Add(a=2, b=3) @ 0, 0
+    /// mul1: Multiply(y=1.5) @ 250, 0
+    /// add1.out -> mul1.x
+    /// ```
+    ///
+    /// Statements are separated by whitespace or `;`, and `#` starts a
+    /// comment. Names that aren't identifiers, and values that contain `,`
+    /// or `)`, are written in double quotes.
+    pub fn to_text(
+        &self,
+        format: &impl GraphTextFormat<
+            NodeData = NodeData,
+            NodeTemplate = NodeTemplate,
+            ValueType = ValueType,
+        >,
+    ) -> String {
+        let mut out = String::new();
+        let mut names: SecondaryMap<NodeId, String> = SecondaryMap::new();
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        let mut issued: HashSet<String> = HashSet::new();
+
+        for &node_id in &self.node_order {
+            let node = &self.graph[node_id];
+            let mut base: String = node
+                .label
+                .chars()
+                .filter(|c| c.is_alphanumeric())
+                .flat_map(char::to_lowercase)
+                .collect();
+            if base.is_empty() || base.starts_with(|c: char| c.is_numeric()) {
+                base.insert_str(0, "node");
+            }
+            // Labels ending in a digit can produce the name of another
+            // node, like "Add 1" and the eleventh "Add", so the count is
+            // bumped until the name is unused.
+            let count = counts.entry(base.clone()).or_default();
+            let name = loop {
+                *count += 1;
+                let name = format!("{}{}", base, count);
+                if issued.insert(name.clone()) {
+                    break name;
+                }
+            };
+
+            out.push_str(&name);
+            out.push_str(": ");
+            write_name(&mut out, &format.template_name(node));
+            out.push('(');
+            let args = node.inputs.iter().filter_map(|(param, input)| {
+                if self.graph.connection(*input).is_some() {
+                    return None;
+                }
+                Some((param, format.format_value(&self.graph[*input].value)?))
+            });
+            for (i, (param, value)) in args.enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                write_name(&mut out, param);
+                out.push('=');
+                if value.contains([',', ')', '"']) || value.trim() != value {
+                    write_quoted(&mut out, &value);
+                } else {
+                    out.push_str(&value);
+                }
+            }
+            out.push(')');
+            if let Some(pos) = self.node_positions.get(node_id) {
+                let _ = write!(out, " @ {}, {}", pos.x.round(), pos.y.round());
+            }
+            out.push('\n');
+            names.insert(node_id, name);
+        }
+
+        for &node_id in &self.node_order {
+            for (input_name, input) in &self.graph[node_id].inputs {
+                for &id in self.graph.connection_ids(*input) {
+                    let output = self.graph[id].output;
+                    let output_node = self.graph[output].node;
+                    let output_name = self.graph[output_node]
+                        .outputs
+                        .iter()
+                        .find(|(_, id)| *id == output)
+                        .map(|(name, _)| name);
+                    if let (Some(from), Some(output_name)) = (names.get(output_node), output_name) {
+                        out.push_str(from);
+                        out.push('.');
+                        write_name(&mut out, output_name);
+                        out.push_str(" -> ");
+                        out.push_str(&names[node_id]);
+                        out.push('.');
+                        write_name(&mut out, input_name);
+                        out.push('\n');
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    /// Adds the nodes and connections written in `text` to the graph, see
+    /// [`GraphEditorState::to_text`] for the format. Nodes without a position
+    /// are placed in columns, after the nodes they are connected from.
+    ///
+    /// Returns the created nodes, keyed by their name in the text. Nothing is
+    /// added when the text has an error.
+    pub fn add_text(
+        &mut self,
+        text: &str,
+        format: &impl GraphTextFormat<
+            NodeData = NodeData,
+            NodeTemplate = NodeTemplate,
+            ValueType = ValueType,
+        >,
+        user_state: &mut UserState,
+    ) -> Result<HashMap<String, NodeId>, EguiGraphError> {
+        let statements = Parser { text, pos: 0 }.statements()?;
+        let mut nodes = HashMap::new();
+        let result = self.add_statements(&statements, format, user_state, &mut nodes);
+        if result.is_err() {
+            for node_id in nodes.values() {
+                let _ = self.graph.remove_node(*node_id);
+                self.node_positions.remove(*node_id);
+                self.node_order.retain(|id| id != node_id);
+            }
+        }
+        result.map(|_| nodes)
+    }
+
+    fn add_statements(
+        &mut self,
+        statements: &[Statement],
+        format: &impl GraphTextFormat<
+            NodeData = NodeData,
+            NodeTemplate = NodeTemplate,
+            ValueType = ValueType,
+        >,
+        user_state: &mut UserState,
+        nodes: &mut HashMap<String, NodeId>,
+    ) -> Result<(), EguiGraphError> {
+        let error = |line: usize, message: String| EguiGraphError::TextParse(line, message);
+        let mut unplaced = Vec::new();
+
+        for statement in statements {
+            if let Statement::Node {
+                name,
+                template,
+                args,
+                pos,
+                line,
+            } = statement
+            {
+                if nodes.contains_key(name) {
+                    return Err(error(*line, format!("node `{}` is defined twice", name)));
+                }
+                let template = format
+                    .template(template)
+                    .ok_or_else(|| error(*line, format!("unknown template `{}`", template)))?;
+                let node_id = self.graph.add_node(
+                    template.node_graph_label(user_state),
+                    template.user_data(user_state),
+                    |graph, node_id| {
+                        template.build_node(graph, user_state, node_id);
+                        graph[node_id].template_version = template.version(user_state);
+                    },
+                );
+                nodes.insert(name.clone(), node_id);
+                self.node_order.push(node_id);
+                self.node_positions
+                    .insert(node_id, pos.unwrap_or(Pos2::ZERO));
+                if pos.is_none() {
+                    unplaced.push(node_id);
+                }
+
+                for (param, text) in args {
+                    let input = self.graph[node_id].get_input(param).map_err(|_| {
+                        error(*line, format!("`{}` has no input `{}`", name, param))
+                    })?;
+                    let value = format
+                        .parse_value(text, &self.graph[input].value)
+                        .ok_or_else(|| error(*line, format!("invalid value `{}`", text)))?;
+                    self.graph[input].value = value;
+                }
+            }
+        }
+
+        let mut edges = Vec::new();
+        for statement in statements {
+            if let Statement::Connection { from, to, line } = statement {
+                let node = |name: &String| {
+                    nodes
+                        .get(name)
+                        .copied()
+                        .ok_or_else(|| error(*line, format!("unknown node `{}`", name)))
+                };
+                let (from_node, to_node) = (node(&from.0)?, node(&to.0)?);
+                let output = self.graph[from_node].get_output(&from.1).map_err(|_| {
+                    error(*line, format!("`{}` has no output `{}`", from.0, from.1))
+                })?;
+                let input = self.graph[to_node]
+                    .get_input(&to.1)
+                    .map_err(|_| error(*line, format!("`{}` has no input `{}`", to.0, to.1)))?;
+                self.graph
+                    .append_connection(output, input)
+                    .map_err(|err| error(*line, err.to_string()))?;
+                edges.push((from_node, to_node));
+            }
+        }
+
+        // Nodes go one column to the right of the nodes they are connected
+        // from. Relaxing once per node is enough for graphs without cycles.
+        let mut columns: HashMap<NodeId, usize> = HashMap::new();
+        for _ in 0..unplaced.len() {
+            for &(from, to) in &edges {
+                let column = columns.get(&from).copied().unwrap_or(0) + 1;
+                if column > columns.get(&to).copied().unwrap_or(0) {
+                    columns.insert(to, column);
+                }
+            }
+        }
+        let mut rows: HashMap<usize, usize> = HashMap::new();
+        for node_id in unplaced {
+            let column = columns.get(&node_id).copied().unwrap_or(0);
+            let row = rows.entry(column).or_default();
+            self.node_positions
+                .insert(node_id, pos2(column as f32 * 250.0, *row as f32 * 150.0));
+            *row += 1;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Copy)]
+    struct TestTemplate;
+
+    impl NodeTemplateTrait for TestTemplate {
+        type NodeData = ();
+        type DataType = u32;
+        type ValueType = String;
+        type ConnectionData = ();
+        type UserState = ();
+        type CategoryType = ();
+
+        fn node_finder_label(&self, _user_state: &mut ()) -> std::borrow::Cow<'_, str> {
+            "Test".into()
+        }
+
+        fn node_graph_label(&self, _user_state: &mut ()) -> String {
+            "Test".into()
+        }
+
+        fn user_data(&self, _user_state: &mut ()) {}
+
+        fn build_node(
+            &self,
+            graph: &mut Graph<(), u32, String>,
+            _user_state: &mut (),
+            node_id: NodeId,
+        ) {
+            for name in ["a", "first value"] {
+                graph.add_input_param(
+                    node_id,
+                    name.into(),
+                    0,
+                    String::new(),
+                    InputParamKind::ConnectionOrConstant,
+                    true,
+                );
+            }
+            graph.add_output_param(node_id, "out".into(), 0);
+        }
+    }
+
+    struct TestFormat;
+
+    impl GraphTextFormat for TestFormat {
+        type NodeData = ();
+        type NodeTemplate = TestTemplate;
+        type ValueType = String;
+
+        fn template(&self, name: &str) -> Option<TestTemplate> {
+            match name {
+                "Test" => Some(TestTemplate),
+                _ => None,
+            }
+        }
+
+        fn template_name(&self, _node: &Node<()>) -> String {
+            "Test".into()
+        }
+
+        fn parse_value(&self, text: &str, _current: &String) -> Option<String> {
+            Some(text.to_owned())
+        }
+
+        fn format_value(&self, value: &String) -> Option<String> {
+            match value.is_empty() {
+                true => None,
+                false => Some(value.clone()),
+            }
+        }
+    }
+
+    type TestState = GraphEditorState<(), u32, String, TestTemplate, ()>;
+
+    fn input_value(state: &TestState, node_id: NodeId, name: &str) -> String {
+        let input = state.graph[node_id].get_input(name).unwrap();
+        state.graph[input].value.clone()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let text = r#"
+            # Quoted names and values, with escapes.
+            test1: Test("first value"="a, (b)", a=" say \"hi\" \\ ") @ 10, 20
+            test2: Test() @ 250, 0
+            test3: Test(a=plain) @ 250, 150
+            test1.out -> test2.a
+            test3.out -> test2.a
+        "#;
+        let mut state = TestState::default();
+        let nodes = state.add_text(text, &TestFormat, &mut ()).unwrap();
+        assert_eq!(nodes.len(), 3);
+        assert_eq!(input_value(&state, nodes["test1"], "first value"), "a, (b)");
+        assert_eq!(input_value(&state, nodes["test1"], "a"), r#" say "hi" \ "#);
+        assert_eq!(state.node_positions[nodes["test1"]], pos2(10.0, 20.0));
+        let test2_a = state.graph[nodes["test2"]].get_input("a").unwrap();
+        assert_eq!(state.graph.connection_count(test2_a), 2);
+
+        let written = state.to_text(&TestFormat);
+        let mut copy = TestState::default();
+        let copied = copy.add_text(&written, &TestFormat, &mut ()).unwrap();
+        assert_eq!(copy.to_text(&TestFormat), written);
+        for (name, node_id) in &nodes {
+            for param in ["a", "first value"] {
+                assert_eq!(
+                    input_value(&copy, copied[name], param),
+                    input_value(&state, *node_id, param)
+                );
+            }
+        }
+        let copy_a = copy.graph[copied["test2"]].get_input("a").unwrap();
+        assert_eq!(copy.graph.connection_count(copy_a), 2);
+
+        // "Test 1" and the eleventh "Test" both make the name test11.
+        let text: String = (1..=12)
+            .map(|i| format!("test{}: Test(a={})\n", i, i))
+            .collect();
+        let mut state = TestState::default();
+        let nodes = state.add_text(&text, &TestFormat, &mut ()).unwrap();
+        state.graph[nodes["test1"]].label = "Test 1".into();
+        let written = state.to_text(&TestFormat);
+        let mut copy = TestState::default();
+        let copied = copy.add_text(&written, &TestFormat, &mut ()).unwrap();
+        assert_eq!(copied.len(), 12);
+        let mut values: Vec<_> = copied
+            .values()
+            .map(|node_id| input_value(&copy, *node_id, "a"))
+            .collect();
+        values.sort_by_key(|value| value.parse::<u32>().unwrap());
+        let expected: Vec<_> = (1..=12).map(|i| i.to_string()).collect();
+        assert_eq!(values, expected);
+    }
+
+    #[test]
+    fn test_errors() {
+        let cases = [
+            ("test1: Test()\ntest2: Missing()", 2, "unknown template"),
+            ("test1: Test()\n\ntest1.out -> test1.b", 3, "has no input"),
+            ("test1: Test(b=1)", 1, "has no input"),
+            ("test1: Test()\ntest1: Test()", 2, "defined twice"),
+            ("test1: Test(a=\"open)", 1, "unterminated"),
+        ];
+        for (text, expected_line, expected_message) in cases {
+            let mut state = TestState::default();
+            match state.add_text(text, &TestFormat, &mut ()) {
+                Err(EguiGraphError::TextParse(line, message)) => {
+                    assert_eq!(line, expected_line, "{}", text);
+                    assert!(message.contains(expected_message), "{}", message);
+                }
+                other => panic!("unexpected result for {:?}: {:?}", text, other),
+            }
+            // Nothing is added when the text has an error.
+            assert!(state.graph.nodes.is_empty());
+            assert!(state.node_order.is_empty());
+        }
+    }
+}
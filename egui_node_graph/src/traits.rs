@@ -34,7 +34,8 @@ pub trait WidgetValueTrait: Default {
     /// to implement handling of side effects. If unsure, the response Vec can
     /// be empty.
     ///
-    /// Shows the input name label by default.
+    /// Shows the input name label by default, truncated to fit the node, with
+    /// the [`EditorStyle::input_text_style`].
     fn value_widget_connected(
         &mut self,
         param_name: &str,
@@ -43,12 +44,109 @@ pub trait WidgetValueTrait: Default {
         _user_state: &mut Self::UserState,
         _node_data: &Self::NodeData,
     ) -> Vec<Self::Response> {
-        ui.label(param_name);
+        let text_style = ui
+            .style()
+            .override_text_style
+            .clone()
+            .unwrap_or(egui::TextStyle::Body);
+        truncated_label(ui, param_name, text_style, ui.available_width());
 
         Default::default()
     }
 }
 
+/// This trait must be implemented by the `ConnectionData` generic parameter of
+/// the [`Graph`], the data carried by each connection, like a weight or a
+/// delay. It's implemented for `()`, for graphs where connections don't carry
+/// data.
+///
+/// New connections made in the editor start with the [`Default`] value.
+pub trait ConnectionDataTrait<UserState>: Default {
+    /// Whether clicking a connection opens a popup with its
+    /// [`ConnectionDataTrait::data_widget`]. Defaults to true.
+    fn has_widget(&self, _user_state: &mut UserState) -> bool {
+        true
+    }
+
+    /// Shows the widgets to edit the data of the connection from `output`
+    /// to `input`, in a popup next to the connection. Returns whether the
+    /// data changed, which is reported with a
+    /// [`NodeResponse::ConnectionDataChanged`].
+    fn data_widget(
+        &mut self,
+        ui: &mut egui::Ui,
+        output: OutputId,
+        input: InputId,
+        user_state: &mut UserState,
+    ) -> bool;
+
+    /// The numeric weight of the connection, e.g. for neural networks or
+    /// mixers. It's drawn as a label at the midpoint of the connection,
+    /// which can be dragged sideways to change it. Defaults to `None`, for
+    /// connections without a weight.
+    fn weight(&self) -> Option<f32> {
+        None
+    }
+
+    /// Sets the weight of the connection after its label was dragged, see
+    /// [`ConnectionDataTrait::weight`].
+    fn set_weight(&mut self, _weight: f32) {}
+}
+
+impl<UserState> ConnectionDataTrait<UserState> for () {
+    fn has_widget(&self, _user_state: &mut UserState) -> bool {
+        false
+    }
+
+    fn data_widget(
+        &mut self,
+        _ui: &mut egui::Ui,
+        _output: OutputId,
+        _input: InputId,
+        _user_state: &mut UserState,
+    ) -> bool {
+        false
+    }
+}
+
+/// Connection data holding a numeric weight, which starts at 1.0. The weight
+/// is shown on the connection, and can be edited by dragging it or in the
+/// connection popup.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConnectionWeight(pub f32);
+
+impl Default for ConnectionWeight {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+impl<UserState> ConnectionDataTrait<UserState> for ConnectionWeight {
+    fn data_widget(
+        &mut self,
+        ui: &mut egui::Ui,
+        _output: OutputId,
+        _input: InputId,
+        _user_state: &mut UserState,
+    ) -> bool {
+        ui.horizontal(|ui| {
+            ui.label("Weight");
+            ui.add(egui::DragValue::new(&mut self.0).speed(0.01))
+                .changed()
+        })
+        .inner
+    }
+
+    fn weight(&self) -> Option<f32> {
+        Some(self.0)
+    }
+
+    fn set_weight(&mut self, weight: f32) {
+        self.0 = weight;
+    }
+}
+
 /// This trait must be implemented by the `DataType` generic parameter of the
 /// [`Graph`]. This trait tells the library how to visually expose data types
 /// to the user.
@@ -84,7 +182,58 @@ pub trait DataTypeTrait<UserState>: PartialEq + Eq {
     ///     }
     /// }
     /// ```
-    fn name(&self) -> std::borrow::Cow<str>;
+    fn name(&self) -> std::borrow::Cow<'_, str>;
+
+    /// Draws a connection carrying this data type. Override this to fully
+    /// customize how connections look, e.g. to vary the thickness with the
+    /// signal strength.
+    ///
+    /// The default implementation draws a bezier curve with the
+    /// [`DataTypeTrait::data_type_color`], or the [`ConnectionStyle::color`]
+    /// when set, see [`draw_connection`].
+    fn draw_connection(
+        &self,
+        painter: &egui::Painter,
+        info: &ConnectionDrawInfo,
+        user_state: &mut UserState,
+    ) {
+        let color = info
+            .color
+            .unwrap_or_else(|| self.data_type_color(user_state));
+        draw_connection(painter, info, color);
+    }
+
+    /// Draws a port of this data type. Override this to customize the look of
+    /// ports, e.g. their shape or how connected ports are marked. Hit testing
+    /// and dragging are handled by the library regardless.
+    ///
+    /// The default implementation draws a circle with the
+    /// [`DataTypeTrait::data_type_color`], which turns white when hovered or
+    /// highlighted. Bidirectional ports are drawn as a diamond.
+    fn draw_port(&self, painter: &egui::Painter, info: &PortDrawInfo, user_state: &mut UserState) {
+        let color = if info.hovered || info.highlighted {
+            egui::Color32::WHITE
+        } else {
+            self.data_type_color(user_state)
+        }
+        .linear_multiply(info.opacity);
+        if info.bidirectional {
+            let (c, r) = (info.center, info.radius * 1.3);
+            let points = vec![
+                c - egui::Vec2::Y * r,
+                c + egui::Vec2::X * r,
+                c + egui::Vec2::Y * r,
+                c - egui::Vec2::X * r,
+            ];
+            painter.add(egui::Shape::convex_polygon(
+                points,
+                color,
+                egui::Stroke::NONE,
+            ));
+        } else {
+            painter.circle(info.center, info.radius, color, egui::Stroke::NONE);
+        }
+    }
 }
 
 /// This trait must be implemented for the `NodeData` generic parameter of the
@@ -101,13 +250,26 @@ where
     type DataType;
     /// Must be set to the custom user `ValueType` type
     type ValueType;
+    /// Must be set to the custom user `ConnectionData` type, or `()` when
+    /// connections don't carry data
+    type ConnectionData: Clone + std::fmt::Debug;
 
     /// Additional UI elements to draw in the nodes, after the parameters.
+    ///
+    /// The node grows vertically to fit its contents, so tall widgets like
+    /// plots or images can be added here. The available width is the width
+    /// of the node contents when the node is resized, or the maximum from
+    /// [`NodeDataTrait::max_size`] otherwise. Widgets that fill the
+    /// available height, like a `Plot` without a height, take what's left of
+    /// the maximum height below the parameters.
+    ///
+    /// The `user_state` can be modified directly, e.g. by a button that
+    /// selects the node for a preview, instead of returning a response.
     fn bottom_ui(
         &self,
         ui: &mut egui::Ui,
         node_id: NodeId,
-        graph: &Graph<Self, Self::DataType, Self::ValueType>,
+        graph: &Graph<Self, Self::DataType, Self::ValueType, Self::ConnectionData>,
         user_state: &mut Self::UserState,
     ) -> Vec<NodeResponse<Self::Response, Self>>
     where
@@ -118,7 +280,7 @@ where
         &self,
         _ui: &mut egui::Ui,
         _node_id: NodeId,
-        _graph: &Graph<Self, Self::DataType, Self::ValueType>,
+        _graph: &Graph<Self, Self::DataType, Self::ValueType, Self::ConnectionData>,
         _user_state: &mut Self::UserState,
     ) -> Vec<NodeResponse<Self::Response, Self>>
     where
@@ -129,35 +291,171 @@ where
 
     /// UI to draw for each output
     ///
-    /// Defaults to showing param_name as a simple label.
+    /// Defaults to showing param_name as a simple label, truncated to fit the
+    /// node, with the [`EditorStyle::output_text_style`].
     fn output_ui(
         &self,
         ui: &mut egui::Ui,
         _node_id: NodeId,
-        _graph: &Graph<Self, Self::DataType, Self::ValueType>,
+        _graph: &Graph<Self, Self::DataType, Self::ValueType, Self::ConnectionData>,
         _user_state: &mut Self::UserState,
         param_name: &str,
     ) -> Vec<NodeResponse<Self::Response, Self>>
     where
         Self::Response: UserResponseTrait,
     {
-        ui.label(param_name);
+        let text_style = ui
+            .style()
+            .override_text_style
+            .clone()
+            .unwrap_or(egui::TextStyle::Body);
+        truncated_label(ui, param_name, text_style, ui.available_width());
 
         Default::default()
     }
 
+    /// The current value of an output, e.g. the result of evaluating the
+    /// graph, shown next to the output label. This makes values visible on
+    /// the graph without a separate inspector. For anything other than a
+    /// short text, override [`NodeDataTrait::output_ui`] instead.
+    ///
+    /// This is evaluated every frame. Defaults to not showing any value.
+    fn output_value(
+        &self,
+        _node_id: NodeId,
+        _output: OutputId,
+        _graph: &Graph<Self, Self::DataType, Self::ValueType, Self::ConnectionData>,
+        _user_state: &mut Self::UserState,
+    ) -> Option<String> {
+        None
+    }
+
+    /// An image to preview in the node body, after the parameters, e.g. the
+    /// output of a shader or compositing node. Returns the texture and its
+    /// size, used to fit the preview to the node width keeping the aspect
+    /// ratio. Clicking the preview opens a larger view of it.
+    ///
+    /// This is evaluated every frame. Defaults to no preview.
+    fn preview_texture(
+        &self,
+        _node_id: NodeId,
+        _graph: &Graph<Self, Self::DataType, Self::ValueType, Self::ConnectionData>,
+        _user_state: &mut Self::UserState,
+    ) -> Option<(egui::TextureId, egui::Vec2)> {
+        None
+    }
+
     /// Set background color on titlebar
     /// If the return value is None, the default color is set.
+    ///
+    /// This is evaluated every frame, so it can be used to tint nodes by
+    /// category or by runtime status, e.g. red while the node is failing.
     fn titlebar_color(
         &self,
         _ui: &egui::Ui,
         _node_id: NodeId,
-        _graph: &Graph<Self, Self::DataType, Self::ValueType>,
+        _graph: &Graph<Self, Self::DataType, Self::ValueType, Self::ConnectionData>,
+        _user_state: &mut Self::UserState,
+    ) -> Option<egui::Color32> {
+        None
+    }
+
+    /// Set the background color of the node body, e.g. to tell node
+    /// categories apart along with [`NodeDataTrait::titlebar_color`]. If the
+    /// return value is None, [`EditorStyle::node_background`] is used.
+    fn background_color(
+        &self,
+        _ui: &egui::Ui,
+        _node_id: NodeId,
+        _graph: &Graph<Self, Self::DataType, Self::ValueType, Self::ConnectionData>,
         _user_state: &mut Self::UserState,
     ) -> Option<egui::Color32> {
         None
     }
 
+    /// The minimum size of the node, in points at a zoom level of 1.0. The node
+    /// grows to fit its contents from here. Defaults to no minimum.
+    fn min_size(
+        &self,
+        _node_id: NodeId,
+        _graph: &Graph<Self, Self::DataType, Self::ValueType, Self::ConnectionData>,
+        _user_state: &mut Self::UserState,
+    ) -> egui::Vec2 {
+        egui::Vec2::ZERO
+    }
+
+    /// The space available to the contents of the node, in points at a zoom
+    /// level of 1.0. Widgets that take all the available space, like wrapping
+    /// text, are limited by this. A width set by the user with the resize
+    /// handle takes precedence over the maximum width.
+    ///
+    /// Defaults to 200 points wide and 400 points high, the
+    /// [`GraphNodeWidget::MAX_NODE_SIZE`]. The node grows taller than the
+    /// maximum height when its contents need it.
+    fn max_size(
+        &self,
+        _node_id: NodeId,
+        _graph: &Graph<Self, Self::DataType, Self::ValueType, Self::ConnectionData>,
+        _user_state: &mut Self::UserState,
+    ) -> egui::Vec2 {
+        crate::editor_ui::DEFAULT_MAX_NODE_SIZE.into()
+    }
+
+    /// The progress of a long-running task of the node, e.g. its evaluation,
+    /// between 0.0 and 1.0. When set, it's shown as a thin bar under the
+    /// titlebar, see [`EditorStyle::progress_bar_height`].
+    ///
+    /// This is evaluated every frame. Defaults to no progress bar.
+    fn progress(
+        &self,
+        _node_id: NodeId,
+        _graph: &Graph<Self, Self::DataType, Self::ValueType, Self::ConnectionData>,
+        _user_state: &mut Self::UserState,
+    ) -> Option<f32> {
+        None
+    }
+
+    /// The execution status of the node, shown with the colors of
+    /// [`EditorStyle::status`].
+    ///
+    /// This is evaluated every frame. Defaults to [`NodeStatus::Idle`].
+    fn status(
+        &self,
+        _node_id: NodeId,
+        _graph: &Graph<Self, Self::DataType, Self::ValueType, Self::ConnectionData>,
+        _user_state: &mut Self::UserState,
+    ) -> NodeStatus {
+        NodeStatus::Idle
+    }
+
+    /// A metric of the node normalized between 0.0 and 1.0, like its cost or
+    /// memory usage. When set, the node background is tinted with the color
+    /// of [`EditorStyle::heatmap`] for this value.
+    ///
+    /// This is evaluated every frame. Defaults to no tint.
+    fn heatmap_value(
+        &self,
+        _node_id: NodeId,
+        _graph: &Graph<Self, Self::DataType, Self::ValueType, Self::ConnectionData>,
+        _user_state: &mut Self::UserState,
+    ) -> Option<f32> {
+        None
+    }
+
+    /// Whether the node is disabled or bypassed. Disabled nodes, and the
+    /// connections attached to them, are drawn with the reduced
+    /// [`EditorStyle::disabled_opacity`]. They can still be edited.
+    ///
+    /// This is evaluated every frame.
+    fn is_disabled(
+        &self,
+        _node_id: NodeId,
+        _graph: &Graph<Self, Self::DataType, Self::ValueType, Self::ConnectionData>,
+        _user_state: &mut Self::UserState,
+    ) -> bool {
+        false
+    }
+
     /// Separator to put between elements in the node.
     ///
     /// Invoked between inputs, outputs and bottom UI. Useful for
@@ -171,15 +469,76 @@ where
         _ui: &mut egui::Ui,
         _node_id: NodeId,
         _param_id: AnyParameterId,
-        _graph: &Graph<Self, Self::DataType, Self::ValueType>,
+        _graph: &Graph<Self, Self::DataType, Self::ValueType, Self::ConnectionData>,
+        _user_state: &mut Self::UserState,
+    ) {
+    }
+
+    /// Paints below the nodes and connections of the graph, e.g. watermarks
+    /// or region highlights. Use the `view` to convert between graph and
+    /// screen coordinates so the drawing follows the canvas.
+    ///
+    /// Default implementation does nothing.
+    fn canvas_background(
+        _painter: &egui::Painter,
+        _view: &CanvasView,
+        _user_state: &mut Self::UserState,
+    ) {
+    }
+
+    /// Paints below the node, before it is drawn, e.g. a halo or a glow. The
+    /// node hasn't been laid out yet at this point, so `node_rect` is the
+    /// screen rect of the node on the previous frame, which is empty on the
+    /// first frame the node is shown.
+    ///
+    /// Default implementation does nothing.
+    fn before_draw(
+        &self,
+        _painter: &egui::Painter,
+        _node_rect: egui::Rect,
+        _node_id: NodeId,
+        _graph: &Graph<Self, Self::DataType, Self::ValueType, Self::ConnectionData>,
+        _user_state: &mut Self::UserState,
+    ) {
+    }
+
+    /// Paints over the node, after it is drawn, e.g. badges or annotations.
+    /// The `node_rect` is the screen rect of the node, including its
+    /// background.
+    ///
+    /// Default implementation does nothing.
+    fn after_draw(
+        &self,
+        _painter: &egui::Painter,
+        _node_rect: egui::Rect,
+        _node_id: NodeId,
+        _graph: &Graph<Self, Self::DataType, Self::ValueType, Self::ConnectionData>,
         _user_state: &mut Self::UserState,
     ) {
     }
 
+    /// Whether a connection from `output` to `input` can be made, where
+    /// `input` belongs to this node. Ports that would make an invalid
+    /// connection don't accept the connection being dragged. This applies to
+    /// all ports, including bidirectional ones.
+    ///
+    /// Default implementation allows all connections between ports of the
+    /// same data type.
+    fn can_connect(
+        &self,
+        _node_id: NodeId,
+        _output: OutputId,
+        _input: InputId,
+        _graph: &Graph<Self, Self::DataType, Self::ValueType, Self::ConnectionData>,
+        _user_state: &mut Self::UserState,
+    ) -> bool {
+        true
+    }
+
     fn can_delete(
         &self,
         _node_id: NodeId,
-        _graph: &Graph<Self, Self::DataType, Self::ValueType>,
+        _graph: &Graph<Self, Self::DataType, Self::ValueType, Self::ConnectionData>,
         _user_state: &mut Self::UserState,
     ) -> bool {
         true
@@ -192,6 +551,36 @@ where
 pub trait NodeTemplateIter {
     type Item;
     fn all_kinds(&self) -> Vec<Self::Item>;
+
+    /// Returns at most `limit` templates matching the search `query` typed
+    /// in the node finder, best matches first. The query is empty when the
+    /// finder opens.
+    ///
+    /// Implement this for large template libraries: when it returns `Some`,
+    /// the node finder lists these results instead of calling
+    /// [`NodeTemplateIter::all_kinds`], and only searches again when the
+    /// query changes. Defaults to `None`, which lists all the templates by
+    /// category.
+    fn query(&self, _query: &str, _limit: usize) -> Option<Vec<Self::Item>> {
+        None
+    }
+}
+
+impl<T: NodeTemplateIter> NodeTemplateIter for &T {
+    type Item = T::Item;
+    fn all_kinds(&self) -> Vec<Self::Item> {
+        (*self).all_kinds()
+    }
+    fn query(&self, query: &str, limit: usize) -> Option<Vec<Self::Item>> {
+        (*self).query(query, limit)
+    }
+}
+
+impl<T: Clone> NodeTemplateIter for Vec<T> {
+    type Item = T;
+    fn all_kinds(&self) -> Vec<Self::Item> {
+        self.clone()
+    }
 }
 
 /// Describes a category of nodes.
@@ -214,7 +603,7 @@ impl CategoryTrait for () {
     }
 }
 
-impl<'a> CategoryTrait for &'a str {
+impl CategoryTrait for &str {
     fn name(&self) -> String {
         self.to_string()
     }
@@ -237,6 +626,9 @@ pub trait NodeTemplateTrait: Clone {
     type DataType;
     /// Must be set to the custom user `ValueType` type
     type ValueType;
+    /// Must be set to the custom user `ConnectionData` type, or `()` when
+    /// connections don't carry data
+    type ConnectionData;
     /// Must be set to the custom user `UserState` type
     type UserState;
     /// Must be a type that implements the [`CategoryTrait`] trait.
@@ -251,17 +643,23 @@ pub trait NodeTemplateTrait: Clone {
     /// The return type is Cow<str> to allow returning owned or borrowed values
     /// more flexibly. Refer to the documentation for `DataTypeTrait::name` for
     /// more information
-    fn node_finder_label(&self, user_state: &mut Self::UserState) -> std::borrow::Cow<str>;
+    fn node_finder_label(&self, user_state: &mut Self::UserState) -> std::borrow::Cow<'_, str>;
 
     /// Vec of categories to which the node belongs.
     ///
     /// It's often useful to organize similar nodes into categories, which will
-    /// then be used by the node finder to show a more manageable UI, especially
-    /// if the node template are numerous.
+    /// then be used by the node finder and the node context menu to show a
+    /// more manageable UI, especially if the node template are numerous.
     fn node_finder_categories(&self, _user_state: &mut Self::UserState) -> Vec<Self::CategoryType> {
         Vec::default()
     }
 
+    /// Keywords that also find the template when searching in the node
+    /// finder, e.g. synonyms of its label. Defaults to no tags.
+    fn tags(&self, _user_state: &mut Self::UserState) -> Vec<String> {
+        Vec::default()
+    }
+
     /// Returns a descriptive name for the node kind, used in the graph.
     fn node_graph_label(&self, user_state: &mut Self::UserState) -> String;
 
@@ -273,12 +671,159 @@ pub trait NodeTemplateTrait: Clone {
     /// parameters.
     fn build_node(
         &self,
-        graph: &mut Graph<Self::NodeData, Self::DataType, Self::ValueType>,
+        graph: &mut Graph<Self::NodeData, Self::DataType, Self::ValueType, Self::ConnectionData>,
         user_state: &mut Self::UserState,
         node_id: NodeId,
     );
+
+    /// Describes how the existing node `node_id` is converted into a node of
+    /// this template, when it's replaced with
+    /// [`GraphEditorState::replace_node`]. Use it to map renamed parameters
+    /// and to convert values, e.g. to migrate the nodes of old documents to a
+    /// newer version of the template. The old node, including its user data,
+    /// is still in the `graph` when this is called.
+    ///
+    /// Defaults to no explicit mapping, so parameters are matched by name and
+    /// data type.
+    fn conversion_from(
+        &self,
+        _node_id: NodeId,
+        _graph: &Graph<Self::NodeData, Self::DataType, Self::ValueType, Self::ConnectionData>,
+        _user_state: &mut Self::UserState,
+    ) -> NodeConversion<Self::ValueType> {
+        NodeConversion::default()
+    }
+
+    /// The version of this template. Nodes store the version of the template
+    /// they were created from, so bump it when the parameters of the template
+    /// change, and handle the older nodes in
+    /// [`NodeTemplateTrait::upgrade_node`]. Defaults to 0.
+    fn version(&self, _user_state: &mut Self::UserState) -> u32 {
+        0
+    }
+
+    /// Upgrades the node `node_id`, which was created by the version
+    /// `from_version` of this template, e.g. to rename its ports or convert
+    /// its values. This is called by [`GraphEditorState::upgrade_nodes`],
+    /// which then sets the node to the current version. Defaults to doing
+    /// nothing.
+    fn upgrade_node(
+        &self,
+        _node_id: NodeId,
+        _from_version: u32,
+        _graph: &mut Graph<Self::NodeData, Self::DataType, Self::ValueType, Self::ConnectionData>,
+        _user_state: &mut Self::UserState,
+    ) {
+    }
 }
 
 /// The custom user response types when drawing nodes in the graph must
 /// implement this trait.
 pub trait UserResponseTrait: Clone + std::fmt::Debug {}
+
+/// The stages used to draw a node in the graph editor. Every stage has a
+/// default implementation, so custom widgets only need to override the stages
+/// they want to change. The defaults are available as methods of
+/// [`GraphNodeWidget`], so overrides can also wrap them.
+///
+/// Use [`GraphEditorState::draw_graph_editor_with_widget`] to draw the graph
+/// with a custom node widget.
+pub trait NodeWidget<NodeData>
+where
+    NodeData: NodeDataTrait,
+    NodeData::Response: UserResponseTrait,
+    NodeData::DataType: DataTypeTrait<NodeData::UserState>,
+    NodeData::ValueType: WidgetValueTrait<
+        Response = NodeData::Response,
+        UserState = NodeData::UserState,
+        NodeData = NodeData,
+    >,
+{
+    /// Draws the contents of the titlebar.
+    fn title_ui(
+        &mut self,
+        node: &mut GraphNodeWidget<
+            '_,
+            NodeData,
+            NodeData::DataType,
+            NodeData::ValueType,
+            NodeData::ConnectionData,
+        >,
+        ui: &mut egui::Ui,
+        user_state: &mut NodeData::UserState,
+        responses: &mut Vec<NodeResponse<NodeData::Response, NodeData>>,
+    ) {
+        node.title_ui(ui, user_state, responses)
+    }
+
+    /// Draws the body of the node. Must record the vertical position of each
+    /// port in the `layout`, which is later used to draw the ports.
+    fn params_ui(
+        &mut self,
+        node: &mut GraphNodeWidget<
+            '_,
+            NodeData,
+            NodeData::DataType,
+            NodeData::ValueType,
+            NodeData::ConnectionData,
+        >,
+        ui: &mut egui::Ui,
+        user_state: &mut NodeData::UserState,
+        layout: &mut NodeLayout,
+        responses: &mut Vec<NodeResponse<NodeData::Response, NodeData>>,
+    ) {
+        node.params_ui(ui, user_state, layout, responses)
+    }
+
+    /// Draws the ports of the node and handles the interaction with them.
+    fn ports_ui(
+        &mut self,
+        node: &mut GraphNodeWidget<
+            '_,
+            NodeData,
+            NodeData::DataType,
+            NodeData::ValueType,
+            NodeData::ConnectionData,
+        >,
+        ui: &mut egui::Ui,
+        user_state: &mut NodeData::UserState,
+        layout: &NodeLayout,
+        responses: &mut Vec<NodeResponse<NodeData::Response, NodeData>>,
+    ) {
+        node.ports_ui(ui, user_state, layout, responses)
+    }
+
+    /// Returns the shape to paint behind the node contents.
+    fn background(
+        &mut self,
+        node: &mut GraphNodeWidget<
+            '_,
+            NodeData,
+            NodeData::DataType,
+            NodeData::ValueType,
+            NodeData::ConnectionData,
+        >,
+        ui: &mut egui::Ui,
+        user_state: &mut NodeData::UserState,
+        layout: &NodeLayout,
+    ) -> egui::Shape {
+        node.background(ui, user_state, layout)
+    }
+}
+
+/// The [`NodeWidget`] used by [`GraphEditorState::draw_graph_editor`], which
+/// uses the default implementation for all stages.
+pub struct DefaultNodeWidget;
+
+impl<NodeData> NodeWidget<NodeData> for DefaultNodeWidget
+where
+    NodeData: NodeDataTrait,
+    NodeData::Response: UserResponseTrait,
+    NodeData::DataType: DataTypeTrait<NodeData::UserState>,
+    NodeData::ValueType: WidgetValueTrait<
+        Response = NodeData::Response,
+        UserState = NodeData::UserState,
+        NodeData = NodeData,
+    >,
+{
+}
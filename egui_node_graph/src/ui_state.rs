@@ -1,20 +1,138 @@
 use super::*;
-use std::marker::PhantomData;
+use std::{
+    collections::{HashMap, HashSet},
+    marker::PhantomData,
+};
 
 #[cfg(feature = "persistence")]
 use serde::{Deserialize, Serialize};
 
-#[derive(Default, Copy, Clone)]
+#[derive(Copy, Clone)]
 #[cfg_attr(feature = "persistence", derive(Serialize, Deserialize))]
 pub struct PanZoom {
     pub pan: egui::Vec2,
     pub zoom: f32,
 }
 
+impl Default for PanZoom {
+    fn default() -> Self {
+        Self {
+            pan: egui::Vec2::ZERO,
+            zoom: 1.0,
+        }
+    }
+}
+
+/// The rect and port positions of a node, in graph coordinates relative to
+/// the position of the node, see [`GraphEditorState::node_layouts`].
+#[derive(Clone, Debug)]
+pub struct CachedNodeLayout {
+    pub rect: egui::Rect,
+    pub ports: Vec<(AnyParameterId, egui::Vec2)>,
+}
+
+/// The shadow and background of a node tessellated into a mesh, see
+/// [`GraphEditorState::node_shapes`]. The mesh is painted again as long as
+/// the node paints the same shapes with the same tessellation settings, so a
+/// node that isn't moved, zoomed, resized, hovered or selected skips the
+/// tessellation of its chrome.
+#[derive(Clone, Debug)]
+pub struct CachedNodeShapes {
+    /// The shapes the mesh was made from, in screen coordinates.
+    pub shapes: Vec<egui::Shape>,
+    pub pixels_per_point: f32,
+    pub options: egui::epaint::TessellationOptions,
+    pub mesh: egui::Shape,
+}
+
+impl CachedNodeShapes {
+    /// Returns `shapes` tessellated into a single mesh, reusing the mesh
+    /// cached for `node_id` when nothing changed since it was made. Shapes
+    /// holding meshes or paint callbacks can't be merged, and are returned
+    /// as they are.
+    pub(crate) fn tessellate(
+        cache: &mut SecondaryMap<NodeId, Self>,
+        node_id: NodeId,
+        ctx: &egui::Context,
+        shapes: Vec<egui::Shape>,
+    ) -> egui::Shape {
+        fn can_merge(shape: &egui::Shape) -> bool {
+            match shape {
+                egui::Shape::Vec(shapes) => shapes.iter().all(can_merge),
+                egui::Shape::Mesh(_) | egui::Shape::Callback(_) => false,
+                _ => true,
+            }
+        }
+        if !shapes.iter().all(can_merge) {
+            cache.remove(node_id);
+            return egui::Shape::Vec(shapes);
+        }
+
+        let pixels_per_point = ctx.pixels_per_point();
+        let options = ctx.tessellation_options(|options| *options);
+        if let Some(cached) = cache.get(node_id) {
+            if cached.pixels_per_point == pixels_per_point
+                && cached.options == options
+                && cached.shapes == shapes
+            {
+                return cached.mesh.clone();
+            }
+        }
+
+        let (font_tex_size, prepared_discs) = ctx.fonts(|fonts| {
+            (
+                fonts.font_image_size(),
+                fonts.texture_atlas().lock().prepared_discs(),
+            )
+        });
+        let mut tessellator = egui::epaint::Tessellator::new(
+            pixels_per_point,
+            options,
+            font_tex_size,
+            prepared_discs,
+        );
+        let mut mesh = egui::Mesh::default();
+        for shape in &shapes {
+            tessellator.tessellate_shape(shape.clone(), &mut mesh);
+        }
+        let mesh = egui::Shape::mesh(mesh);
+        cache.insert(
+            node_id,
+            Self {
+                shapes,
+                pixels_per_point,
+                options,
+                mesh: mesh.clone(),
+            },
+        );
+        mesh
+    }
+}
+
+/// An ongoing animation of the viewport, started with
+/// [`GraphEditorState::animate_viewport_to`].
+#[derive(Clone, Copy, Debug)]
+pub struct ViewportAnimation {
+    /// The graph position that will be at the center of the editor.
+    pub target_center: egui::Pos2,
+    /// The zoom at the end of the animation.
+    pub target_zoom: f32,
+    /// The start time, center and zoom of the animation. Filled in when the
+    /// animation is first drawn.
+    pub start: Option<(f64, egui::Pos2, f32)>,
+}
+
 #[derive(Clone)]
 #[cfg_attr(feature = "persistence", derive(Serialize, Deserialize))]
-pub struct GraphEditorState<NodeData, DataType, ValueType, NodeTemplate, UserState> {
-    pub graph: Graph<NodeData, DataType, ValueType>,
+pub struct GraphEditorState<
+    NodeData,
+    DataType,
+    ValueType,
+    NodeTemplate,
+    UserState,
+    ConnectionData = (),
+> {
+    pub graph: Graph<NodeData, DataType, ValueType, ConnectionData>,
     /// Nodes are drawn in this order. Draw order is important because nodes
     /// that are drawn last are on top.
     pub node_order: Vec<NodeId>,
@@ -26,17 +144,120 @@ pub struct GraphEditorState<NodeData, DataType, ValueType, NodeTemplate, UserSta
     pub selected_nodes: Vec<NodeId>,
     /// The mouse drag start position for an ongoing box selection.
     pub ongoing_box_selection: Option<egui::Pos2>,
+    /// The mouse drag start position for an ongoing zoom region, see
+    /// [`EditorSettings::zoom_region_modifiers`].
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub ongoing_zoom_region: Option<egui::Pos2>,
     /// The position of each node.
     pub node_positions: SecondaryMap<NodeId, egui::Pos2>,
+    /// The width of the nodes that were resized by the user. Other nodes fit
+    /// their contents. See [`EditorSettings::resizable_nodes`].
+    #[cfg_attr(feature = "persistence", serde(default))]
+    pub node_widths: SecondaryMap<NodeId, f32>,
+    /// The nodes that can't be dragged, e.g. to anchor the final output of
+    /// the graph. Layout tools should leave these in place too. See
+    /// [`GraphEditorState::set_pinned`].
+    #[cfg_attr(feature = "persistence", serde(default))]
+    pub pinned_nodes: HashSet<NodeId>,
     /// The node finder is used to create new nodes.
     pub node_finder: Option<NodeFinder<NodeTemplate>>,
+    /// The radial quick menu, open while its gesture is held.
+    pub radial_menu: Option<RadialMenu>,
+    /// The context menu of a node, opened by secondary clicking it. Holds the
+    /// node and the screen position of the menu.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub node_menu: Option<(NodeId, egui::Pos2)>,
+    /// The popup to edit the data of a connection, opened by clicking it.
+    /// Holds the connection and the screen position of the popup.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub connection_popup: Option<(ConnectionId, egui::Pos2)>,
+    /// How many nodes have been created from each template, keyed by the
+    /// template's node finder label. Used to fill the radial quick menu.
+    #[cfg_attr(feature = "persistence", serde(default))]
+    pub template_usage: HashMap<String, u32>,
+    /// The values the inputs of new nodes start from, overriding the ones set
+    /// by their template. Keyed by the template's node finder label, then by
+    /// the input name. See [`GraphEditorState::set_template_default`].
+    #[cfg_attr(feature = "persistence", serde(default))]
+    pub template_defaults: HashMap<String, HashMap<String, ValueType>>,
+    /// The reroute points of each connection, keyed by its input, in graph
+    /// coordinates. Connections pass through their reroute points in order.
+    #[cfg_attr(feature = "persistence", serde(default))]
+    pub reroute_points: SecondaryMap<InputId, Vec<egui::Pos2>>,
+    /// The reroute point being dragged, as its connection input and index.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub dragged_reroute_point: Option<(InputId, usize)>,
+    /// The connection whose weight label is being dragged.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub dragged_weight: Option<ConnectionId>,
+    /// The nodes created recently from the editor, and the time their
+    /// creation highlight started, filled in when first drawn. See
+    /// [`EditorStyle::creation_highlight_duration`].
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub created_nodes: SecondaryMap<NodeId, Option<f64>>,
+    /// The layout of each node the last time it was drawn, used for the nodes
+    /// that aren't laid out, see [`EditorSettings::cull_offscreen_nodes`].
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub node_layouts: SecondaryMap<NodeId, CachedNodeLayout>,
+    /// The tessellated shadow and background of each node the last time it
+    /// was drawn.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub node_shapes: SecondaryMap<NodeId, CachedNodeShapes>,
+    /// A node created for a port, to be aligned with the port once it's
+    /// drawn. Holds the node, its port that was connected, and the original
+    /// port.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub pending_alignment: Option<(NodeId, AnyParameterId, AnyParameterId)>,
     /// The panning of the graph viewport.
     pub pan_zoom: PanZoom,
+    /// The velocity of the viewport, in points per second, while it glides
+    /// after a pan. See [`EditorSettings::kinetic_pan_friction`].
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub pan_velocity: egui::Vec2,
+    /// The node under the mouse, if any. This is updated while drawing the
+    /// nodes, so it can be used after [`GraphEditorState::draw_graph_editor`]
+    /// returns, in the same frame.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub hovered_node: Option<NodeId>,
+    /// The connection under the mouse on the last frame, if any.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub hovered_connection: Option<ConnectionId>,
+    /// The connection selected by clicking it, see
+    /// [`EditorSettings::select_connections`].
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub selected_connection: Option<ConnectionId>,
+    /// The ongoing viewport animation, if any.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub viewport_animation: Option<ViewportAnimation>,
+    /// The viewport to go back to while the overview is shown, see
+    /// [`EditorSettings::overview_key`].
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub overview: Option<PanZoom>,
+    /// The part of the graph to fit in the editor on the next frame, see
+    /// [`GraphEditorState::frame_nodes`].
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub pending_frame: Option<egui::Rect>,
+    /// Behavior settings for the editor.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub settings: EditorSettings,
+    /// Visual settings for the editor.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub style: EditorStyle,
+    /// Mirrors the editor with other instances, once
+    /// [`GraphEditorState::start_sync`] is called.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pub sync: Option<OpReplica<ValueType>>,
+    /// The undo history, see [`GraphEditorState::update_history`].
+    #[cfg_attr(feature = "persistence", serde(skip, default = "EditHistory::default"))]
+    pub history: EditHistory<NodeData, DataType, ValueType, ConnectionData>,
+    /// The nodes copied with [`GraphEditorState::copy_selection`].
+    #[cfg_attr(feature = "persistence", serde(skip, default = "Option::default"))]
+    pub clipboard: Option<NodeClipboard<NodeData, DataType, ValueType, ConnectionData>>,
     pub _user_state: PhantomData<fn() -> UserState>,
 }
 
-impl<NodeData, DataType, ValueType, NodeKind, UserState>
-    GraphEditorState<NodeData, DataType, ValueType, NodeKind, UserState>
+impl<NodeData, DataType, ValueType, NodeKind, UserState, ConnectionData>
+    GraphEditorState<NodeData, DataType, ValueType, NodeKind, UserState, ConnectionData>
 {
     pub fn new(default_zoom: f32) -> Self {
         Self {
@@ -47,9 +268,144 @@ impl<NodeData, DataType, ValueType, NodeKind, UserState>
             ..Default::default()
         }
     }
+
+    /// Moves the viewport so the given position, in graph coordinates, is at
+    /// the center of the editor. The movement is animated over
+    /// [`EditorSettings::viewport_animation_duration`].
+    pub fn center_on(&mut self, pos: egui::Pos2) {
+        self.animate_viewport_to(pos, self.pan_zoom.zoom);
+    }
+
+    /// Moves and zooms the viewport so the whole graph fits in the editor,
+    /// see [`GraphEditorState::frame_nodes`].
+    pub fn zoom_to_fit(&mut self) {
+        let nodes: Vec<NodeId> = self.graph.iter_nodes().collect();
+        self.frame_nodes(&nodes);
+    }
+
+    /// Moves and zooms the viewport so the given nodes fit in the editor,
+    /// within [`EditorSettings::min_zoom`] and [`EditorSettings::max_zoom`].
+    /// The size of the editor is only known when it's drawn, so the movement
+    /// starts on the next frame, and is animated like
+    /// [`GraphEditorState::animate_viewport_to`]. Nodes that were never
+    /// drawn are taken as a point at their position.
+    pub fn frame_nodes(&mut self, nodes: &[NodeId]) {
+        let bounds = nodes
+            .iter()
+            .filter_map(|node_id| {
+                let pos = *self.node_positions.get(*node_id)?;
+                Some(match self.node_layouts.get(*node_id) {
+                    Some(layout) => layout.rect.translate(pos.to_vec2()),
+                    None => egui::Rect::from_min_max(pos, pos),
+                })
+            })
+            .fold(egui::Rect::NOTHING, |bounds, rect| bounds.union(rect));
+        if bounds.min.x <= bounds.max.x {
+            self.pending_frame = Some(bounds);
+        }
+    }
+
+    /// Moves the viewport so the given position, in graph coordinates, is at
+    /// the center of the editor with the given zoom. The movement is animated
+    /// over [`EditorSettings::viewport_animation_duration`].
+    pub fn animate_viewport_to(&mut self, center: egui::Pos2, zoom: f32) {
+        self.viewport_animation = Some(ViewportAnimation {
+            target_center: center,
+            target_zoom: zoom,
+            start: None,
+        });
+    }
+
+    /// Returns a position near `pos`, in graph coordinates, that isn't on
+    /// top of an existing node. Starting at `pos`, the position is shifted by
+    /// [`EditorSettings::placement_offset`] while it's inside the rect of a
+    /// node, or closer than the offset to its position, so stacked nodes
+    /// cascade instead of hiding each other. Nodes that were never drawn have
+    /// no known size, and only their position is checked.
+    pub fn free_position(&self, pos: egui::Pos2) -> egui::Pos2 {
+        let offset = match self.settings.placement_offset {
+            Some(offset) if offset != egui::Vec2::ZERO => offset,
+            _ => return pos,
+        };
+        let is_taken = |pos: egui::Pos2| {
+            self.node_positions.iter().any(|(node_id, other)| {
+                let inside = match self.node_layouts.get(node_id) {
+                    Some(layout) => layout.rect.translate(other.to_vec2()).contains(pos),
+                    None => false,
+                };
+                inside || other.distance(pos) < offset.length()
+            })
+        };
+        let mut pos = pos;
+        while is_taken(pos) {
+            pos += offset;
+        }
+        pos
+    }
+
+    /// Pins or unpins the node. Pinned nodes keep their position when
+    /// dragged, alone or as part of the selection.
+    pub fn set_pinned(&mut self, node_id: NodeId, pinned: bool) {
+        if pinned {
+            self.pinned_nodes.insert(node_id);
+        } else {
+            self.pinned_nodes.remove(&node_id);
+        }
+    }
+
+    /// Whether the node is pinned, see [`GraphEditorState::set_pinned`].
+    pub fn is_pinned(&self, node_id: NodeId) -> bool {
+        self.pinned_nodes.contains(&node_id)
+    }
+
+    /// The position of the node in [`GraphEditorState::node_order`], where 0
+    /// is drawn at the back. Returns `None` if the node isn't in the graph.
+    pub fn draw_index(&self, node_id: NodeId) -> Option<usize> {
+        self.node_order.iter().position(|id| *id == node_id)
+    }
+
+    /// Moves the node to the given position of
+    /// [`GraphEditorState::node_order`], clamped to its length. Does nothing
+    /// if the node isn't in the graph.
+    pub fn set_draw_index(&mut self, node_id: NodeId, index: usize) {
+        if let Some(old_index) = self.draw_index(node_id) {
+            self.node_order.remove(old_index);
+            let index = index.min(self.node_order.len());
+            self.node_order.insert(index, node_id);
+        }
+    }
+
+    /// Draws the node on top of all other nodes. This is what happens when a
+    /// node is clicked or dragged.
+    pub fn bring_to_front(&mut self, node_id: NodeId) {
+        self.set_draw_index(node_id, usize::MAX);
+    }
+
+    /// Draws the node behind all other nodes, e.g. for a backdrop. Nodes are
+    /// brought to the front when dragged, so a backdrop that must stay behind
+    /// should be sent back after every frame it was moved.
+    pub fn send_to_back(&mut self, node_id: NodeId) {
+        self.set_draw_index(node_id, 0);
+    }
+
+    /// Draws the node above the next node in
+    /// [`GraphEditorState::node_order`].
+    pub fn bring_forward(&mut self, node_id: NodeId) {
+        if let Some(index) = self.draw_index(node_id) {
+            self.set_draw_index(node_id, index + 1);
+        }
+    }
+
+    /// Draws the node below the previous node in
+    /// [`GraphEditorState::node_order`].
+    pub fn send_backward(&mut self, node_id: NodeId) {
+        if let Some(index) = self.draw_index(node_id) {
+            self.set_draw_index(node_id, index.saturating_sub(1));
+        }
+    }
 }
-impl<NodeData, DataType, ValueType, NodeKind, UserState> Default
-    for GraphEditorState<NodeData, DataType, ValueType, NodeKind, UserState>
+impl<NodeData, DataType, ValueType, NodeKind, UserState, ConnectionData> Default
+    for GraphEditorState<NodeData, DataType, ValueType, NodeKind, UserState, ConnectionData>
 {
     fn default() -> Self {
         Self {
@@ -58,14 +414,149 @@ impl<NodeData, DataType, ValueType, NodeKind, UserState> Default
             connection_in_progress: Default::default(),
             selected_nodes: Default::default(),
             ongoing_box_selection: Default::default(),
+            ongoing_zoom_region: None,
             node_positions: Default::default(),
+            node_widths: Default::default(),
+            pinned_nodes: Default::default(),
+            created_nodes: Default::default(),
+            node_layouts: Default::default(),
+            node_shapes: Default::default(),
+            pending_alignment: Default::default(),
             node_finder: Default::default(),
+            radial_menu: Default::default(),
+            node_menu: None,
+            connection_popup: None,
+            template_usage: Default::default(),
+            template_defaults: Default::default(),
+            reroute_points: Default::default(),
+            dragged_reroute_point: None,
+            dragged_weight: None,
             pan_zoom: Default::default(),
+            pan_velocity: egui::Vec2::ZERO,
+            hovered_node: None,
+            hovered_connection: None,
+            selected_connection: None,
+            viewport_animation: None,
+            overview: None,
+            pending_frame: None,
+            settings: Default::default(),
+            sync: None,
+            history: Default::default(),
+            clipboard: None,
+            style: Default::default(),
             _user_state: Default::default(),
         }
     }
 }
 
+impl<NodeData, DataType, ValueType, NodeTemplate, UserState, ConnectionData>
+    GraphEditorState<NodeData, DataType, ValueType, NodeTemplate, UserState, ConnectionData>
+where
+    NodeTemplate: NodeTemplateTrait<
+        NodeData = NodeData,
+        DataType = DataType,
+        ValueType = ValueType,
+        ConnectionData = ConnectionData,
+        UserState = UserState,
+    >,
+{
+    /// Upgrades the nodes created by an older version of their template, see
+    /// [`NodeTemplateTrait::upgrade_node`]. Call this after loading a
+    /// document. `template_of` returns the template a node was created from,
+    /// e.g. from its user data. Returns the upgraded nodes.
+    pub fn upgrade_nodes(
+        &mut self,
+        user_state: &mut UserState,
+        template_of: impl Fn(&Node<NodeData>) -> Option<NodeTemplate>,
+    ) -> Vec<NodeId> {
+        let mut upgraded = Vec::new();
+        for node_id in self.graph.iter_nodes().collect::<Vec<_>>() {
+            let template = match template_of(&self.graph[node_id]) {
+                Some(template) => template,
+                None => continue,
+            };
+            let from_version = self.graph[node_id].template_version;
+            let version = template.version(user_state);
+            if from_version < version {
+                template.upgrade_node(node_id, from_version, &mut self.graph, user_state);
+                self.graph[node_id].template_version = version;
+                upgraded.push(node_id);
+            }
+        }
+        upgraded
+    }
+
+    /// Makes the input `input_name` of the nodes created from `template`
+    /// start with `value`, instead of the value set by the template. This is
+    /// stored in the editor state, so each document can have its own
+    /// defaults.
+    pub fn set_template_default(
+        &mut self,
+        template: &NodeTemplate,
+        input_name: &str,
+        value: ValueType,
+        user_state: &mut UserState,
+    ) {
+        self.template_defaults
+            .entry(template.node_finder_label(user_state).into_owned())
+            .or_default()
+            .insert(input_name.to_owned(), value);
+    }
+
+    /// Returns the value the input `input_name` of new nodes created from
+    /// `template` starts with, when overridden.
+    pub fn template_default(
+        &self,
+        template: &NodeTemplate,
+        input_name: &str,
+        user_state: &mut UserState,
+    ) -> Option<&ValueType> {
+        self.template_defaults
+            .get(template.node_finder_label(user_state).as_ref())?
+            .get(input_name)
+    }
+
+    /// Goes back to the values set by `template` for its new nodes.
+    pub fn reset_template_defaults(&mut self, template: &NodeTemplate, user_state: &mut UserState) {
+        self.template_defaults
+            .remove(template.node_finder_label(user_state).as_ref());
+    }
+}
+
+impl<NodeData, DataType, ValueType, NodeTemplate, UserState, ConnectionData>
+    GraphEditorState<NodeData, DataType, ValueType, NodeTemplate, UserState, ConnectionData>
+where
+    NodeTemplate: NodeTemplateTrait<
+        NodeData = NodeData,
+        DataType = DataType,
+        ValueType = ValueType,
+        ConnectionData = ConnectionData,
+        UserState = UserState,
+    >,
+    ValueType: Clone,
+{
+    /// Makes the current values of the unconnected inputs of `node_id` the
+    /// defaults for the new nodes created from `template`, see
+    /// [`GraphEditorState::set_template_default`].
+    pub fn set_template_defaults_from_node(
+        &mut self,
+        template: &NodeTemplate,
+        node_id: NodeId,
+        user_state: &mut UserState,
+    ) {
+        let graph = &self.graph;
+        let defaults = self
+            .template_defaults
+            .entry(template.node_finder_label(user_state).into_owned())
+            .or_default();
+        for (name, input) in &graph[node_id].inputs {
+            if graph.connection(*input).is_none() {
+                defaults.insert(name.clone(), graph[*input].value.clone());
+            }
+        }
+    }
+}
+
 impl PanZoom {
     pub fn adjust_zoom(
         &mut self,
@@ -80,4 +571,13 @@ impl PanZoom {
         self.zoom += zoom_delta;
         self.pan += point * zoom_delta;
     }
+
+    /// Multiplies the zoom by `factor`, keeping the graph position under
+    /// `point` in place. The `point` is relative to the top left corner of
+    /// the editor.
+    pub fn zoom_around(&mut self, factor: f32, point: egui::Vec2, zoom_min: f32, zoom_max: f32) {
+        let graph_point = (point - self.pan) / self.zoom;
+        self.zoom = (self.zoom * factor).clamp(zoom_min, zoom_max);
+        self.pan = point - graph_point * self.zoom;
+    }
 }
@@ -1,3 +1,5 @@
+use egui::Visuals;
+
 pub trait ColorUtils {
     /// Multiplies the color rgb values by `factor`, keeping alpha untouched.
     fn lighten(&self, factor: f32) -> Self;
@@ -13,3 +15,36 @@ impl ColorUtils for egui::Color32 {
         )
     }
 }
+
+/// Multiplies all the colors in `visuals` by `opacity`, so anything drawn with
+/// them looks faded.
+pub fn fade_visuals(visuals: &mut Visuals, opacity: f32) {
+    let fade = |color: &mut egui::Color32| *color = color.linear_multiply(opacity);
+    if let Some(color) = &mut visuals.override_text_color {
+        fade(color);
+    }
+    let widgets = &mut visuals.widgets;
+    for widget in [
+        &mut widgets.noninteractive,
+        &mut widgets.inactive,
+        &mut widgets.hovered,
+        &mut widgets.active,
+        &mut widgets.open,
+    ] {
+        fade(&mut widget.bg_fill);
+        fade(&mut widget.weak_bg_fill);
+        fade(&mut widget.bg_stroke.color);
+        fade(&mut widget.fg_stroke.color);
+    }
+    fade(&mut visuals.selection.bg_fill);
+    fade(&mut visuals.selection.stroke.color);
+    fade(&mut visuals.hyperlink_color);
+    fade(&mut visuals.faint_bg_color);
+    fade(&mut visuals.extreme_bg_color);
+    fade(&mut visuals.code_bg_color);
+    fade(&mut visuals.warn_fg_color);
+    fade(&mut visuals.error_fg_color);
+    fade(&mut visuals.window_fill);
+    fade(&mut visuals.window_stroke.color);
+    fade(&mut visuals.panel_fill);
+}
@@ -0,0 +1,333 @@
+use std::collections::HashSet;
+
+use super::*;
+
+/// An inconsistency found by [`GraphEditorState::validate`]. These can only
+/// come from documents that were edited or corrupted outside of the editor,
+/// and would otherwise make the editor panic later on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValidationIssue {
+    /// An input whose node doesn't exist, or doesn't list it.
+    OrphanInput(InputId),
+    /// An output whose node doesn't exist, or doesn't list it.
+    OrphanOutput(OutputId),
+    /// A node lists an input that doesn't exist, or belongs to another node.
+    MissingInput { node: NodeId, input: InputId },
+    /// A node lists an output that doesn't exist, or belongs to another node.
+    MissingOutput { node: NodeId, output: OutputId },
+    /// A connection to an input or from an output that doesn't exist.
+    DanglingConnection { input: InputId, output: OutputId },
+    /// [`Graph::input_connections`] doesn't list the connections of an input
+    /// exactly once each.
+    StaleConnectionIndex(InputId),
+    /// A bidirectional parameter is paired with one that doesn't exist.
+    BrokenPairing(AnyParameterId),
+    /// A parameter group of a node contains a parameter that isn't one of
+    /// the node's.
+    StaleGroupParam { node: NodeId, param: AnyParameterId },
+    /// A node is missing from [`GraphEditorState::node_order`].
+    UnorderedNode(NodeId),
+    /// [`GraphEditorState::node_order`] contains a node that doesn't exist,
+    /// or contains it more than once.
+    StaleOrderEntry(NodeId),
+    /// A node has no position in [`GraphEditorState::node_positions`].
+    MissingPosition(NodeId),
+    /// [`GraphEditorState::node_positions`] has a position for a node that
+    /// doesn't exist.
+    StalePosition(NodeId),
+    /// [`GraphEditorState::selected_nodes`] contains a node that doesn't
+    /// exist.
+    StaleSelection(NodeId),
+}
+
+/// The result of [`GraphEditorState::validate`] or
+/// [`GraphEditorState::repair`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    /// Returns true when no issues were found.
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+impl<NodeData, DataType, ValueType, ConnectionData>
+    Graph<NodeData, DataType, ValueType, ConnectionData>
+{
+    /// Checks that the parameters, connections and groups of the graph refer
+    /// to each other consistently.
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        let mut issues = Vec::new();
+        let has_input = |node_id: NodeId, input: InputId| {
+            self.nodes
+                .get(node_id)
+                .into_iter()
+                .flat_map(|node| node.input_ids())
+                .any(|id| id == input)
+        };
+        let has_output = |node_id: NodeId, output: OutputId| {
+            self.nodes
+                .get(node_id)
+                .into_iter()
+                .flat_map(|node| node.output_ids())
+                .any(|id| id == output)
+        };
+
+        for (input_id, input) in &self.inputs {
+            if !has_input(input.node, input_id) {
+                issues.push(ValidationIssue::OrphanInput(input_id));
+            }
+            if let Some(output) = input.paired_output {
+                if !self.outputs.contains_key(output) {
+                    issues.push(ValidationIssue::BrokenPairing(AnyParameterId::Input(
+                        input_id,
+                    )));
+                }
+            }
+        }
+        for (output_id, output) in &self.outputs {
+            if !has_output(output.node, output_id) {
+                issues.push(ValidationIssue::OrphanOutput(output_id));
+            }
+            if let Some(input) = output.paired_input {
+                if !self.inputs.contains_key(input) {
+                    issues.push(ValidationIssue::BrokenPairing(AnyParameterId::Output(
+                        output_id,
+                    )));
+                }
+            }
+        }
+
+        for (node_id, node) in &self.nodes {
+            for input in node.input_ids() {
+                if self.inputs.get(input).map(|param| param.node) != Some(node_id) {
+                    issues.push(ValidationIssue::MissingInput {
+                        node: node_id,
+                        input,
+                    });
+                }
+            }
+            for output in node.output_ids() {
+                if self.outputs.get(output).map(|param| param.node) != Some(node_id) {
+                    issues.push(ValidationIssue::MissingOutput {
+                        node: node_id,
+                        output,
+                    });
+                }
+            }
+            for group in &node.groups {
+                for &param in &group.params {
+                    let owned = match param {
+                        AnyParameterId::Input(input) => has_input(node_id, input),
+                        AnyParameterId::Output(output) => has_output(node_id, output),
+                    };
+                    if !owned {
+                        issues.push(ValidationIssue::StaleGroupParam {
+                            node: node_id,
+                            param,
+                        });
+                    }
+                }
+            }
+        }
+
+        for (input, output) in self.iter_connections() {
+            if !self.inputs.contains_key(input) || !self.outputs.contains_key(output) {
+                issues.push(ValidationIssue::DanglingConnection { input, output });
+            }
+        }
+        let mut indexed = HashSet::new();
+        for (input, ids) in &self.input_connections {
+            let stale = ids.iter().any(|&id| {
+                self.connections.get(id).map(|c| c.input) != Some(input) || !indexed.insert(id)
+            });
+            if stale {
+                issues.push(ValidationIssue::StaleConnectionIndex(input));
+            }
+        }
+        for (id, connection) in &self.connections {
+            if !indexed.contains(&id) {
+                issues.push(ValidationIssue::StaleConnectionIndex(connection.input));
+            }
+        }
+
+        issues
+    }
+}
+
+impl<NodeData, DataType, ValueType, NodeTemplate, UserState, ConnectionData>
+    GraphEditorState<NodeData, DataType, ValueType, NodeTemplate, UserState, ConnectionData>
+{
+    /// Checks the referential integrity of a loaded document: the graph, see
+    /// [`Graph::validate`], and the editor state that refers to its nodes.
+    pub fn validate(&self) -> ValidationReport {
+        let mut issues = self.graph.validate();
+
+        let mut ordered = HashSet::new();
+        for &node_id in &self.node_order {
+            if !self.graph.nodes.contains_key(node_id) || !ordered.insert(node_id) {
+                issues.push(ValidationIssue::StaleOrderEntry(node_id));
+            }
+        }
+        for node_id in self.graph.iter_nodes() {
+            if !ordered.contains(&node_id) {
+                issues.push(ValidationIssue::UnorderedNode(node_id));
+            }
+            if !self.node_positions.contains_key(node_id) {
+                issues.push(ValidationIssue::MissingPosition(node_id));
+            }
+        }
+        for node_id in self.node_positions.keys() {
+            if !self.graph.nodes.contains_key(node_id) {
+                issues.push(ValidationIssue::StalePosition(node_id));
+            }
+        }
+        for &node_id in &self.selected_nodes {
+            if !self.graph.nodes.contains_key(node_id) {
+                issues.push(ValidationIssue::StaleSelection(node_id));
+            }
+        }
+
+        ValidationReport { issues }
+    }
+
+    /// Fixes the issues found by [`GraphEditorState::validate`], and returns
+    /// them. Broken references are removed, unordered nodes are drawn on top
+    /// and nodes without a position are placed at the origin. The other
+    /// per-node editor state, like widths and pins, is cleaned up as well.
+    ///
+    /// Call this after building or editing the graph programmatically,
+    /// before it's drawn.
+    pub fn repair(&mut self) -> ValidationReport {
+        let report = self.validate();
+        let graph = &mut self.graph;
+
+        for issue in &report.issues {
+            match *issue {
+                ValidationIssue::OrphanInput(input) => {
+                    graph.inputs.remove(input);
+                }
+                ValidationIssue::OrphanOutput(output) => {
+                    graph.outputs.remove(output);
+                }
+                ValidationIssue::MissingInput { node, input } => {
+                    graph[node].inputs.retain(|(_, id)| *id != input);
+                }
+                ValidationIssue::MissingOutput { node, output } => {
+                    graph[node].outputs.retain(|(_, id)| *id != output);
+                }
+                ValidationIssue::BrokenPairing(AnyParameterId::Input(input)) => {
+                    if let Some(input) = graph.inputs.get_mut(input) {
+                        input.paired_output = None;
+                    }
+                }
+                ValidationIssue::BrokenPairing(AnyParameterId::Output(output)) => {
+                    if let Some(output) = graph.outputs.get_mut(output) {
+                        output.paired_input = None;
+                    }
+                }
+                ValidationIssue::StaleGroupParam { node, param } => {
+                    for group in &mut graph[node].groups {
+                        group.params.retain(|id| *id != param);
+                    }
+                }
+                ValidationIssue::UnorderedNode(node_id) => self.node_order.push(node_id),
+                ValidationIssue::MissingPosition(node_id) => {
+                    self.node_positions.insert(node_id, egui::Pos2::ZERO);
+                }
+                ValidationIssue::DanglingConnection { .. }
+                | ValidationIssue::StaleConnectionIndex(_)
+                | ValidationIssue::StalePosition(_)
+                | ValidationIssue::StaleOrderEntry(_)
+                | ValidationIssue::StaleSelection(_) => {}
+            }
+        }
+
+        // Removing orphan params can leave more connections dangling, so
+        // these are checked again rather than taken from the report.
+        let (inputs, outputs) = (&graph.inputs, &graph.outputs);
+        graph.connections.retain(|_, connection| {
+            inputs.contains_key(connection.input) && outputs.contains_key(connection.output)
+        });
+        // The index keeps the order of the connections it still agrees on,
+        // and connections missing from it are listed last.
+        let connections = &graph.connections;
+        let mut indexed = HashSet::new();
+        graph.input_connections.retain(|input, ids| {
+            ids.retain(|&id| {
+                connections.get(id).map(|c| c.input) == Some(input) && indexed.insert(id)
+            });
+            !ids.is_empty()
+        });
+        for (id, connection) in &graph.connections {
+            if !indexed.contains(&id) {
+                if let Some(ids) = graph.input_connections.entry(connection.input) {
+                    ids.or_default().push(id);
+                }
+            }
+        }
+        let nodes = &graph.nodes;
+        let mut ordered = HashSet::new();
+        self.node_order
+            .retain(|node_id| nodes.contains_key(*node_id) && ordered.insert(*node_id));
+        self.selected_nodes
+            .retain(|node_id| nodes.contains_key(*node_id));
+        self.node_positions
+            .retain(|node_id, _| nodes.contains_key(node_id));
+        self.node_widths
+            .retain(|node_id, _| nodes.contains_key(node_id));
+        self.pinned_nodes
+            .retain(|node_id| nodes.contains_key(*node_id));
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    type TestState = GraphEditorState<(), u32, u32, (), ()>;
+
+    #[test]
+    fn test_repair() {
+        let mut state = TestState::default();
+        let a = add_editor_node(&mut state, "a", egui::Pos2::ZERO);
+        let b = add_editor_node(&mut state, "b", egui::Pos2::ZERO);
+        let a_out = state.graph[a].get_output("out").unwrap();
+        let b_in = state.graph[b].get_input("in").unwrap();
+        state.graph.add_connection(a_out, b_in).unwrap();
+        assert!(state.validate().is_valid());
+
+        // Remove a node from the graph only.
+        let c = add_editor_node(&mut state, "c", egui::Pos2::ZERO);
+        state.graph.remove_node(c).unwrap();
+        state.selected_nodes.push(c);
+        // Remove the output behind the graph's back, which leaves the
+        // connection dangling.
+        state.graph.outputs.remove(a_out);
+        state.node_positions.remove(b);
+
+        let report = state.validate();
+        assert!(report
+            .issues
+            .contains(&ValidationIssue::DanglingConnection {
+                input: b_in,
+                output: a_out,
+            }));
+        assert!(report.issues.contains(&ValidationIssue::MissingPosition(b)));
+        assert!(report.issues.contains(&ValidationIssue::StaleSelection(c)));
+        assert!(report.issues.contains(&ValidationIssue::StaleOrderEntry(c)));
+
+        assert_eq!(state.repair(), report);
+        assert!(state.validate().is_valid());
+        assert_eq!(state.graph.connection_count(b_in), 0);
+        assert!(state.graph.connections.is_empty());
+        assert_eq!(state.node_order, vec![a, b]);
+        assert!(state.selected_nodes.is_empty());
+    }
+}
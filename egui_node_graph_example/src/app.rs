@@ -8,6 +8,7 @@ use egui_node_graph::*;
 /// The NodeData holds a custom data struct inside each node. It's useful to
 /// store additional information that doesn't live in parameters. For this
 /// example, the node data stores the template (i.e. the "type") of the node.
+#[derive(Clone)]
 #[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
 pub struct MyNodeData {
     template: MyNodeTemplate,
@@ -16,7 +17,7 @@ pub struct MyNodeData {
 /// `DataType`s are what defines the possible range of connections when
 /// attaching two ports together. The graph UI will make sure to not allow
 /// attaching incompatible datatypes.
-#[derive(PartialEq, Eq)]
+#[derive(Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
 pub enum MyDataType {
     Scalar,
@@ -30,7 +31,7 @@ pub enum MyDataType {
 /// this library makes no attempt to check this consistency. For instance, it is
 /// up to the user code in this example to make sure no parameter is created
 /// with a DataType of Scalar and a ValueType of Vec2.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 #[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
 pub enum MyValueType {
     Vec2 { value: egui::Vec2 },
@@ -124,6 +125,7 @@ impl NodeTemplateTrait for MyNodeTemplate {
     type NodeData = MyNodeData;
     type DataType = MyDataType;
     type ValueType = MyValueType;
+    type ConnectionData = ();
     type UserState = MyGraphState;
     type CategoryType = &'static str;
 
@@ -294,7 +296,7 @@ impl WidgetValueTrait for MyValueType {
         // inline parameter widgets.
         match self {
             MyValueType::Vec2 { value } => {
-                ui.label(param_name);
+                truncated_label(ui, param_name, TextStyle::Body, ui.available_width());
                 ui.horizontal(|ui| {
                     ui.label("x");
                     ui.add(DragValue::new(&mut value.x));
@@ -320,6 +322,7 @@ impl NodeDataTrait for MyNodeData {
     type UserState = MyGraphState;
     type DataType = MyDataType;
     type ValueType = MyValueType;
+    type ConnectionData = ();
 
     // This method will be called when drawing each node. This allows adding
     // extra ui elements inside the nodes. In this case, we create an "active"
@@ -366,6 +369,28 @@ impl NodeDataTrait for MyNodeData {
 
         responses
     }
+
+    // The titlebar color is evaluated every frame, so it can reflect runtime
+    // state. Here, the active node gets a golden titlebar, and vector nodes a
+    // blue one so they stand apart from the scalar ones.
+    fn titlebar_color(
+        &self,
+        _ui: &egui::Ui,
+        node_id: NodeId,
+        _graph: &Graph<MyNodeData, MyDataType, MyValueType>,
+        user_state: &mut Self::UserState,
+    ) -> Option<egui::Color32> {
+        if user_state.active_node == Some(node_id) {
+            return Some(egui::Color32::from_rgb(140, 110, 20));
+        }
+        match self.template {
+            MyNodeTemplate::MakeVector
+            | MyNodeTemplate::AddVector
+            | MyNodeTemplate::SubtractVector
+            | MyNodeTemplate::VectorTimesScalar => Some(egui::Color32::from_rgb(50, 80, 120)),
+            _ => None,
+        }
+    }
 }
 
 type MyGraph = Graph<MyNodeData, MyDataType, MyValueType>;
@@ -415,7 +440,7 @@ impl eframe::App for NodeGraphExample {
                 egui::widgets::global_dark_light_mode_switch(ui);
             });
         });
-        let graph_response = egui::CentralPanel::default()
+        let mut graph_response = egui::CentralPanel::default()
             .show(ctx, |ui| {
                 self.state.draw_graph_editor(
                     ui,
@@ -425,6 +450,12 @@ impl eframe::App for NodeGraphExample {
                 )
             })
             .inner;
+        // Copies, pastes and duplicates nodes with ctrl+C, ctrl+V and ctrl+D
+        let clipboard_responses = self.state.update_clipboard(ctx, &graph_response);
+        graph_response.node_responses.extend(clipboard_responses);
+        // Records the edits for undo and redo, with ctrl+Z and ctrl+Y
+        self.state
+            .update_history(ctx, &graph_response.node_responses);
         for node_response in graph_response.node_responses {
             // Here, we ignore all other graph events. But you may find
             // some use for them. For example, by playing a sound when a new